@@ -163,35 +163,7 @@ fn is_palindrome(m: Music) -> bool {
 /// that reverses the pitches in a line, but maintains
 /// the durations in the same order from beginning to end.
 fn retro_pitches(m: Music) -> Option<Music> {
-    let abs_pitches = Vec::from(m)
-        .into_iter()
-        .map(|m| {
-            if let Music::Prim(p) = m {
-                Some(p)
-            } else {
-                None
-            }
-        })
-        .collect::<Option<Vec<_>>>()?;
-
-    let (durations, pitches): (Vec<_>, Vec<_>) = abs_pitches
-        .into_iter()
-        .map(|p| match p {
-            Primitive::Note(d, p) => (d, Some(p)),
-            Primitive::Rest(d) => (d, None),
-        })
-        .unzip();
-
-    #[allow(clippy::option_if_let_else)]
-    let musics = pitches.into_iter().rev().zip(durations).map(|(p, d)| {
-        if let Some(p) = p {
-            Music::note(d, p)
-        } else {
-            Music::rest(d)
-        }
-    });
-
-    Some(Music::line(musics.collect()))
+    m.retrograde_pitches()
 }
 
 #[cfg(test)]
@@ -644,6 +616,9 @@ mod intervals {
 ///
 /// Try to parameterize things in such a way that, for example,
 /// with a simple change, you could generate an infinite _ascension_ as well.
+///
+/// The configurable version of this generator is available
+/// in the library as [`musik::music::generators::shepard_tone`].
 pub mod shepard_scale {
     use std::iter;
 
@@ -1,6 +1,8 @@
 //! Define operations with instruments.
 //! Currently, the only available are [MIDI instruments][Instrument],
 //! so here only the wrapping structure provided.
+use std::fmt;
+
 use crate::output::midi::instruments::Instrument;
 
 #[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Hash)]
@@ -28,3 +30,13 @@ impl From<String> for InstrumentName {
         Self::Custom(value)
     }
 }
+
+impl fmt::Display for InstrumentName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Midi(instrument) => write!(f, "{instrument:?}"),
+            Self::Percussion => write!(f, "Percussion"),
+            Self::Custom(name) => write!(f, "{name}"),
+        }
+    }
+}
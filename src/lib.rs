@@ -18,13 +18,15 @@ pub use self::{
         phrase::{self as attributes, PhraseAttribute},
         Music, NoteAttribute, Temporal,
     },
-    output::midi,
+    output::{midi, wav},
     prim::{
         duration::Dur,
         helpers::{self, pitch_class::accidentals},
-        interval::{ErrorOctaveTryFromNum, Interval, Octave},
-        pitch::{AbsPitch, ErrorPitchClipping, Pitch, PitchClass},
-        scale::KeySig,
+        interval::{ErrorOctaveTryFromNum, Interval, IntervalQuality, NamedInterval, Octave},
+        pitch::{AbsPitch, ErrorPitchClipping, Pitch, PitchClass, SpellingPreference},
+        scale::{scales_for_chord, ChordQuality, KeySig, Mode},
+        time_sig::TimeSignature,
+        tuning::{EqualTemperament, JustIntonation, Tuning, TuningSystem},
         volume::Volume,
     },
 };
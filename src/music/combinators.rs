@@ -1,4 +1,12 @@
-use super::{control::Control, Music, Primitive};
+use num_rational::Ratio;
+use num_traits::One as _;
+
+use crate::{
+    prim::duration::{Dur, DurT},
+    utils::LazyList,
+};
+
+use super::{control::Control, Music, Primitive, Temporal as _};
 
 impl<P> Primitive<P> {
     /// Implementation of _functor_ for [`Primitive`] type.
@@ -40,12 +48,100 @@ impl<P> Music<P> {
         }
     }
 
+    /// Transform all notes the same way as [`map`][Self::map] does,
+    /// but also supply the onset of every note:
+    /// the time from the beginning of the [`Music`] measured in whole notes.
+    ///
+    /// The onsets account for the [tempo changes][Control::Tempo],
+    /// so it allows to implement the position-dependent effects
+    /// (e.g. the gradual crescendo) without performing the [`Music`].
+    pub fn map_with_onset<U, F>(self, f: F) -> Music<U>
+    where
+        F: Fn(Dur, &P) -> U + Clone + 'static,
+        Control<P>: MapToOther<Control<U>>,
+    {
+        self.map_with_onset_from(Dur::ZERO, Ratio::one(), f)
+    }
+
+    /// The `tempo` converts the inner durations into the outer ones
+    /// by accumulating all the [`Control::Tempo`] modifiers above.
+    fn map_with_onset_from<U, F>(self, onset: Dur, tempo: Ratio<DurT>, f: F) -> Music<U>
+    where
+        F: Fn(Dur, &P) -> U + Clone + 'static,
+        Control<P>: MapToOther<Control<U>>,
+    {
+        match self {
+            Self::Prim(Primitive::Note(d, p)) => Music::note(d, f(onset, &p)),
+            Self::Prim(Primitive::Rest(d)) => Music::rest(d),
+            Self::Sequential(m1, m2) => {
+                let next = onset + m1.duration() / tempo;
+                m1.map_with_onset_from(onset, tempo, f.clone())
+                    + m2.map_with_onset_from(next, tempo, f)
+            }
+            Self::Lazy(it) => Music::lazy_line(it.scan(onset, move |onset, m| {
+                let start = *onset;
+                *onset = start + m.duration() / tempo;
+                Some(m.map_with_onset_from(start, tempo, f.clone()))
+            })),
+            Self::Parallel(m1, m2) => {
+                m1.map_with_onset_from(onset, tempo, f.clone())
+                    | m2.map_with_onset_from(onset, tempo, f)
+            }
+            Self::Modify(c, m) => {
+                let tempo = if let Control::Tempo(r) = c {
+                    tempo * r
+                } else {
+                    tempo
+                };
+                let m = m.map_with_onset_from(onset, tempo, f);
+                if let Some(control) = c.into_other() {
+                    m.with(control)
+                } else {
+                    m
+                }
+            }
+        }
+    }
+
     /// Folds the whole [`Music`] given rules
-    /// for folding every piece of its structure.
+    /// for folding every piece of its structure:
+    /// - `prim` converts a single [note or rest][Primitive];
+    /// - `seq` combines the results of [sequential][Self::Sequential] parts;
+    /// - `(init_lazy, fold_lazy)` folds every item of the [lazy line][Self::Lazy]
+    ///   starting from the `init_lazy` value;
+    /// - `par` combines the results of [parallel][Self::Parallel] parts;
+    /// - `modify` applies the [`Control`] to the result of the [modified][Self::Modify] part.
     ///
     /// Could provide framework for the implementation
-    /// of various transformations like `reverse()`
+    /// of various transformations like [`reverse()`][Self::reverse]
     /// or properties like `duration()`.
+    ///
+    /// ```
+    /// # use musik::{music::Primitive, Dur, Music};
+    /// let m = Music::line(vec![
+    ///     Music::rest(Dur::QUARTER),
+    ///     Music::note(Dur::HALF, musik::p!(C 4)),
+    /// ]);
+    ///
+    /// // count the notes, ignoring the rests
+    /// let notes = m.fold(
+    ///     |prim| usize::from(matches!(prim, Primitive::Note(..))),
+    ///     |a, b| a + b,
+    ///     (0, |a, b| a + b),
+    ///     |a, b| a + b,
+    ///     |_control, a| a,
+    /// );
+    /// assert_eq!(notes, 1);
+    /// ```
+    ///
+    /// # Panics
+    /// If the [`Music`] contains a [lazy line][Self::Lazy] known to be infinite,
+    /// i.e. reporting `usize::MAX` as the lower bound of its [`Iterator::size_hint`]
+    /// (e.g. [`repeat`][std::iter::repeat] or [`cycle`][Iterator::cycle]).
+    ///
+    /// The check is best-effort: the infinite lazy lines of unknown length
+    /// (e.g. built with [`from_fn`][std::iter::from_fn] or [`Iterator::filter`])
+    /// are not detected and the folding of them never returns.
     pub fn fold<U, Prim, Seq, Lazy, Par, Mod>(
         self,
         mut prim: Prim,
@@ -75,16 +171,19 @@ impl<P> Music<P> {
                 let u2 = m2.fold(prim, seq.clone(), (init_lazy, fold_lazy), par, modify);
                 seq(u1, u2)
             }
-            Self::Lazy(it) => it.fold(init_lazy.clone(), |acc, m| {
-                let u2 = m.fold(
-                    prim.clone(),
-                    seq.clone(),
-                    (init_lazy.clone(), fold_lazy.clone()),
-                    par.clone(),
-                    modify.clone(),
-                );
-                fold_lazy(acc, u2)
-            }),
+            Self::Lazy(it) => {
+                assert_finite(&it);
+                it.fold(init_lazy.clone(), |acc, m| {
+                    let u2 = m.fold(
+                        prim.clone(),
+                        seq.clone(),
+                        (init_lazy.clone(), fold_lazy.clone()),
+                        par.clone(),
+                        modify.clone(),
+                    );
+                    fold_lazy(acc, u2)
+                })
+            }
             Self::Parallel(m1, m2) => {
                 let u1 = m1.fold(
                     prim.clone(),
@@ -105,9 +204,12 @@ impl<P> Music<P> {
     /// Folds the whole [`Music`] given rules
     /// for folding every piece of its structure.
     ///
-    /// Could provide framework for the implementation
-    /// of various transformations like `reverse()`
-    /// or properties like `duration()`.
+    /// Works the same as [`fold`][Self::fold],
+    /// but does not consume the [`Music`].
+    ///
+    /// # Panics
+    /// If the [`Music`] contains a [lazy line][Self::Lazy] known to be infinite
+    /// (see [`fold`][Self::fold] for the details).
     pub fn fold_by_ref<U, Prim, Seq, Lazy, Par, Mod>(
         &self,
         mut prim: Prim,
@@ -137,16 +239,19 @@ impl<P> Music<P> {
                 let u2 = m2.fold_by_ref(prim, seq.clone(), (init_lazy, fold_lazy), par, modify);
                 seq(u1, u2)
             }
-            Self::Lazy(it) => it.clone().fold(init_lazy.clone(), |acc, m| {
-                let u2 = m.fold_by_ref(
-                    prim.clone(),
-                    seq.clone(),
-                    (init_lazy.clone(), fold_lazy.clone()),
-                    par.clone(),
-                    modify.clone(),
-                );
-                fold_lazy(acc, u2)
-            }),
+            Self::Lazy(it) => {
+                assert_finite(it);
+                it.clone().fold(init_lazy.clone(), |acc, m| {
+                    let u2 = m.fold_by_ref(
+                        prim.clone(),
+                        seq.clone(),
+                        (init_lazy.clone(), fold_lazy.clone()),
+                        par.clone(),
+                        modify.clone(),
+                    );
+                    fold_lazy(acc, u2)
+                })
+            }
             Self::Parallel(m1, m2) => {
                 let u1 = m1.fold_by_ref(
                     prim.clone(),
@@ -166,6 +271,66 @@ impl<P> Music<P> {
     }
 }
 
+impl<P> Music<P> {
+    /// The total number of nodes in the [`Music`] tree,
+    /// including all the [primitives][Self::Prim] and
+    /// the [lazy line][Self::Lazy] items.
+    ///
+    /// Useful to detect accidentally-exponential constructions.
+    ///
+    /// Returns `None` if the [`Music`] contains an infinite [lazy line][Self::Lazy].
+    pub fn node_count(&self) -> Option<usize> {
+        match self {
+            Self::Prim(_) => Some(1),
+            Self::Sequential(m1, m2) | Self::Parallel(m1, m2) => {
+                Some(1 + m1.node_count()? + m2.node_count()?)
+            }
+            Self::Lazy(it) => {
+                if is_infinite(it) {
+                    return None;
+                }
+                it.clone()
+                    .try_fold(1, |acc, m| m.node_count().map(|n| acc + n))
+            }
+            Self::Modify(_, m) => Some(1 + m.node_count()?),
+        }
+    }
+
+    /// The maximum nesting level of the [`Music`] tree.
+    /// A single [primitive][Self::Prim] has the depth of 1.
+    ///
+    /// Returns `None` if the [`Music`] contains an infinite [lazy line][Self::Lazy].
+    pub fn depth(&self) -> Option<usize> {
+        match self {
+            Self::Prim(_) => Some(1),
+            Self::Sequential(m1, m2) | Self::Parallel(m1, m2) => {
+                Some(1 + m1.depth()?.max(m2.depth()?))
+            }
+            Self::Lazy(it) => {
+                if is_infinite(it) {
+                    return None;
+                }
+                it.clone()
+                    .try_fold(0, |acc, m| m.depth().map(|d| acc.max(d)))
+                    .map(|d| d + 1)
+            }
+            Self::Modify(_, m) => Some(1 + m.depth()?),
+        }
+    }
+}
+
+fn is_infinite<T>(it: &LazyList<T>) -> bool {
+    let (lower_bound, _) = it.size_hint();
+    lower_bound == usize::MAX
+}
+
+/// Reject the lazy line which is surely infinite before folding it forever.
+///
+/// The infinite iterators of unknown length pass the check.
+fn assert_finite<T>(it: &LazyList<T>) {
+    assert!(!is_infinite(it), "Cannot fold the infinite lazy line");
+}
+
 /// Workaround for the lack of specialization.
 /// Useful to convert generic types
 /// with one generic argument to another one.
@@ -176,3 +341,131 @@ pub trait MapToOther<T> {
     /// Fallible convert into the target type.
     fn into_other(self) -> Option<T>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{n, Octave, Performable as _, Volume};
+
+    #[test]
+    fn map_with_increasing_onsets() {
+        let oc4 = Octave::OneLined;
+        let m = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::D(oc4, Dur::QUARTER),
+            Music::rest(Dur::HALF),
+            Music::E(oc4, Dur::QUARTER),
+        ]);
+
+        let onsets: Vec<_> = Vec::from(m.map_with_onset(|onset, _| onset))
+            .into_iter()
+            .filter_map(|m| m.as_note().map(|(_, onset)| *onset))
+            .collect();
+        assert_eq!(onsets, [Dur::ZERO, Dur::QUARTER, Dur::WHOLE]);
+    }
+
+    #[test]
+    fn map_with_onset_respects_tempo() {
+        let oc4 = Octave::OneLined;
+        let fast = Music::lazy_line(vec![Music::C(oc4, Dur::HALF); 3].into_iter()).with_tempo(2);
+        let m = Music::D(oc4, Dur::QUARTER) + fast;
+
+        // programmatic crescendo: every quarter is louder by 10
+        let crescendo = m.map_with_onset(|onset, &p| {
+            let steps = (onset.into_ratio::<u32>() * 4).to_integer();
+            (p, Volume::from(u8::try_from(60 + 10 * steps).unwrap()))
+        });
+        let volumes: Vec<_> = crescendo.perform().iter().map(|e| e.volume).collect();
+        assert_eq!(volumes, [60, 70, 80, 90].map(Volume::from));
+    }
+
+    fn inside_out(m: Music) -> Music {
+        m.fold(
+            Music::Prim,
+            |m1, m2| m1 | m2,
+            (Music::rest(Dur::ZERO), |acc, m| acc | m),
+            |m1, m2| m1 + m2,
+            |c, m| m.with(c),
+        )
+    }
+
+    #[test]
+    fn inside_out_symmetric_matrix() {
+        let m = Music::line(vec![
+            n!(C 4 / 4).into(),
+            Music::rest(Dur::QUARTER),
+            n!(D 4 / 4).into(),
+        ]) | Music::line(vec![
+            Music::rest(Dur::QUARTER),
+            Music::rest(Dur::QUARTER),
+            n!(F 4 / 4).into(),
+        ]) | Music::line(vec![
+            n!(D 4 / 4).into(),
+            n!(F 4 / 4).into(),
+            n!(E 4 / 4).into(),
+        ]);
+
+        let p1: Vec<_> = m.clone().perform().iter().collect();
+        let p2: Vec<_> = inside_out(m).perform().iter().collect();
+        assert_eq!(p1, p2);
+    }
+
+    #[test]
+    fn inside_out_turns_melody_into_chord() {
+        let melody = Music::lazy_line(
+            [n!(C 4 / 4), n!(E 4 / 4), n!(G 4 / 4)]
+                .into_iter()
+                .map(Music::from),
+        );
+        let chord = Music::chord(vec![
+            n!(C 4 / 4).into(),
+            n!(E 4 / 4).into(),
+            n!(G 4 / 4).into(),
+        ]);
+
+        let p1: Vec<_> = inside_out(melody).perform().iter().collect();
+        let p2: Vec<_> = chord.perform().iter().collect();
+        assert_eq!(p1, p2);
+    }
+
+    #[test]
+    #[should_panic(expected = "infinite lazy line")]
+    fn fold_infinite_lazy_line() {
+        let m = Music::lazy_line(std::iter::repeat(Music::from(n!(C 4 / 4))));
+        drop(inside_out(m));
+    }
+
+    #[test]
+    fn fold_lazy_line_of_unknown_length() {
+        let mut notes = vec![n!(C 4 / 4), n!(E 4 / 4)].into_iter();
+        // reports `(0, None)` as its size hint
+        let m = Music::lazy_line(std::iter::from_fn(move || notes.next().map(Music::from)));
+        let chord: Vec<_> = inside_out(m).perform().iter().collect();
+        assert_eq!(chord.len(), 2);
+        assert_eq!(chord[0].start_time, chord[1].start_time);
+    }
+
+    #[test]
+    fn node_count_and_depth() {
+        let note = Music::from(n!(C 4 / 4));
+        assert_eq!(note.node_count(), Some(1));
+        assert_eq!(note.depth(), Some(1));
+
+        // Seq(C, Par(Modify(E), G))
+        let m = note.clone() + (Music::from(n!(E 4 / 4)).with_tempo(2) | n!(G 4 / 4).into());
+        assert_eq!(m.node_count(), Some(6));
+        assert_eq!(m.depth(), Some(4));
+
+        let lazy = Music::lazy_line([note, m].into_iter());
+        assert_eq!(lazy.node_count(), Some(8));
+        assert_eq!(lazy.depth(), Some(5));
+    }
+
+    #[test]
+    fn node_count_and_depth_of_infinite_line() {
+        let m = Music::lazy_line(std::iter::repeat(Music::from(n!(C 4 / 4))));
+        assert_eq!(m.node_count(), None);
+        assert_eq!(m.depth(), None);
+    }
+}
@@ -1,4 +1,11 @@
-use crate::prim::{duration::Dur, interval::Octave, pitch::Pitch};
+use std::iter;
+
+use crate::prim::{
+    duration::Dur,
+    interval::{Interval, Octave},
+    pitch::{Pitch, PitchClass},
+    scale::KeySig,
+};
 
 use super::{Music, Primitive};
 
@@ -32,6 +39,54 @@ impl Music {
     def_note_constructor![Eff, Ef, E, Es, Ess];
     def_note_constructor![Fff, Ff, F, Fs, Fss];
     def_note_constructor![Gff, Gf, G, Gs, Gss];
+
+    /// Build an arbitrary [chord][Self::chord] from the `root`
+    /// and the [`Interval`]s of the other notes above it.
+    ///
+    /// Every interval is counted from the root (not from the previous note),
+    /// e.g. the major third, the perfect fifth and the minor seventh
+    /// (`[4, 7, 10]` semitones) form the dominant seventh chord.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Chord_(music)>
+    pub fn chord_from_intervals(root: Pitch, intervals: &[Interval], dur: Dur) -> Self {
+        let notes = iter::once(root)
+            .chain(intervals.iter().map(|&i| root.trans(i)))
+            .map(|p| Self::note(dur, p))
+            .collect();
+        Self::chord(notes)
+    }
+
+    /// Build the [`Music`] from the textual grid of the step sequencer
+    /// (or the piano roll) where every row defines the rhythm of a single [`Pitch`].
+    ///
+    /// Every character `x` (or `X`) of the row's pattern sounds the note
+    /// lasting for one `step` while any other character (e.g. `.`) is a rest.
+    /// The whitespaces are ignored, so they could separate the beats for readability:
+    /// `"x..x ..x."`.
+    ///
+    /// The rows are played in [parallel][Self::chord].
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Music_sequencer#Step_sequencers>
+    pub fn from_grid(rows: &[(Pitch, &str)], step: Dur) -> Self {
+        let voices = rows
+            .iter()
+            .map(|&(pitch, pattern)| {
+                let steps = pattern
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(|c| {
+                        if c.eq_ignore_ascii_case(&'x') {
+                            Self::note(step, pitch)
+                        } else {
+                            Self::rest(step)
+                        }
+                    })
+                    .collect();
+                Self::line(steps)
+            })
+            .collect();
+        Self::chord(voices)
+    }
 }
 
 /// Defines [rest][super::Primitive::Rest] constants of [`Music`]
@@ -101,4 +156,333 @@ impl<P> Music<P> {
     {
         Self::lazy_line(keys.map(move |pitch| Self::note(dur, pitch)))
     }
+
+    /// Arrange the sections of a piece by placing each of them
+    /// at the given offset from the start of the resulting [`Music`].
+    ///
+    /// The sections are [delayed][Self::with_delay] and played in [parallel][Self::chord],
+    /// so the overlapping sections sound together.
+    pub fn arrange(sections: Vec<(Dur, Self)>) -> Self {
+        let placed = sections
+            .into_iter()
+            .map(|(at, section)| {
+                if at == Dur::ZERO {
+                    section
+                } else {
+                    section.with_delay(at)
+                }
+            })
+            .collect();
+        Self::chord(placed)
+    }
+}
+
+impl KeySig {
+    /// Build a [line][Music::line] of diatonic chords
+    /// given by their [Roman numerals][roman] (e.g. `["ii", "V7", "I"]`)
+    /// with the root of the tonic chord in the given [`Octave`].
+    ///
+    /// The numerals from `I` to `VII` define the degree of the chord's root,
+    /// while the optional suffix `7` adds the seventh to the triad.
+    /// The quality of every chord is defined by the scale,
+    /// so the case of the numeral is not checked.
+    ///
+    /// [roman]: https://en.wikipedia.org/wiki/Roman_numeral_analysis
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Chord_progression>
+    pub fn progression(
+        self,
+        numerals: &[&str],
+        chord_dur: Dur,
+        oct: Octave,
+    ) -> Result<Music, String> {
+        let tonic = Pitch::new(self.pitch_class(), oct).abs();
+        let chords = numerals
+            .iter()
+            .map(|numeral| {
+                let (degree, with_seventh) = parse_roman_numeral(numeral)?;
+                let root = tonic.diatonic_trans(self, degree);
+                let size = if with_seventh { 4 } else { 3 };
+                let notes = (0..size)
+                    .map(|i| Music::note(chord_dur, Pitch::from(root.diatonic_trans(self, 2 * i))))
+                    .collect();
+                Ok(Music::chord(notes))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Music::line(chords))
+    }
+}
+
+/// Parse the Roman numeral (with the optional seventh)
+/// into the zero-based degree of the scale.
+fn parse_roman_numeral(numeral: &str) -> Result<(i8, bool), String> {
+    let (numeral, with_seventh) = numeral
+        .strip_suffix('7')
+        .map_or((numeral, false), |n| (n, true));
+
+    let degree = match numeral.to_uppercase().as_str() {
+        "I" => 0,
+        "II" => 1,
+        "III" => 2,
+        "IV" => 3,
+        "V" => 4,
+        "VI" => 5,
+        "VII" => 6,
+        _ => return Err(format!("Invalid Roman numeral of the chord: {numeral:?}")),
+    };
+    Ok((degree, with_seventh))
+}
+
+/// Build a chord from its symbol used in the jazz lead sheets
+/// (e.g. `"Cmaj7"`, `"Dm7"`, `"G7"`, `"F#dim"`, `"Bb13"`)
+/// with the root in the given [`Octave`].
+///
+/// The symbol consists of:
+/// - the root [pitch class][crate::PitchClass] with the optional accidental (`#` or `b`);
+/// - the optional quality: `maj` (`M`, `Δ`), `m` (`min`, `-`), `dim` (`°`), `ø` or `aug` (`+`);
+/// - the optional extension: `6`, `7`, `9`, `11` or `13`;
+/// - the optional altered fifth (`b5` or `#5`) and the suspension (`sus2`, `sus4` or `sus`).
+///
+/// See more: <https://en.wikipedia.org/wiki/Chord_names_and_symbols_(popular_music)>
+pub fn parse_chord_symbol(sym: &str, oct: Octave, dur: Dur) -> Result<Music, String> {
+    let (root, quality) = parse_chord_root(sym)?;
+    let intervals = parse_chord_quality(quality)
+        .ok_or_else(|| format!("Invalid quality {quality:?} of the chord {sym:?}"))?;
+
+    let root = Pitch::new(root, oct);
+    let notes = intervals
+        .into_iter()
+        .map(|i| Music::note(dur, root.trans(Interval::from(i))))
+        .collect();
+    Ok(Music::chord(notes))
+}
+
+/// Split the chord symbol into its root and the rest of the symbol.
+fn parse_chord_root(sym: &str) -> Result<(PitchClass, &str), String> {
+    let letter = sym
+        .get(..1)
+        .filter(|l| l.starts_with(|c: char| ('A'..='G').contains(&c)))
+        .ok_or_else(|| format!("The chord {sym:?} should start with the root note"))?;
+    let rest = &sym[1..];
+    let (accidental, rest) = [('#', "s"), ('b', "f")]
+        .into_iter()
+        .find_map(|(sign, accidental)| rest.strip_prefix(sign).map(|r| (accidental, r)))
+        .unwrap_or(("", rest));
+    let root = format!("{letter}{accidental}").parse()?;
+    Ok((root, rest))
+}
+
+/// The semitones of the chord's notes counted from its root.
+fn parse_chord_quality(quality: &str) -> Option<Vec<i8>> {
+    let (quality, suspended) = [("sus2", 2), ("sus4", 5), ("sus", 5)]
+        .into_iter()
+        .find_map(|(suffix, sus)| quality.strip_suffix(suffix).map(|q| (q, Some(sus))))
+        .unwrap_or((quality, None));
+    let (quality, altered_fifth) = [("b5", -1), ("#5", 1)]
+        .into_iter()
+        .find_map(|(suffix, alt)| quality.strip_suffix(suffix).map(|q| (q, alt)))
+        .unwrap_or((quality, 0));
+
+    let ext_start = quality
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(quality.len());
+    let (quality, extension) = quality.split_at(ext_start);
+
+    let (third, fifth, seventh) = match quality {
+        "" => (4, 7, 10),
+        "maj" | "M" | "Δ" => (4, 7, 11),
+        "m" | "min" | "-" => (3, 7, 10),
+        "dim" | "°" => (3, 6, 9),
+        "ø" => (3, 6, 10),
+        "aug" | "+" => (4, 8, 10),
+        _ => return None,
+    };
+    let mut intervals = vec![0, suspended.unwrap_or(third), fifth + altered_fifth];
+    let extensions: &[i8] = match extension {
+        "" => &[],
+        "6" => &[9],
+        "7" => &[seventh],
+        "9" => &[seventh, 14],
+        "11" => &[seventh, 14, 17],
+        "13" => &[seventh, 14, 17, 21],
+        _ => return None,
+    };
+    intervals.extend(extensions);
+    Some(intervals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use num_rational::Ratio;
+
+    use crate::{p, Performable as _, Temporal as _};
+
+    #[test]
+    fn two_five_one() {
+        let m = KeySig::Major(PitchClass::C)
+            .progression(&["ii", "V7", "I"], Dur::HALF, Octave::OneLined)
+            .unwrap();
+
+        let chord = |pitches: Vec<Pitch>| {
+            Music::chord(
+                pitches
+                    .into_iter()
+                    .map(|p| Music::note(Dur::HALF, p))
+                    .collect(),
+            )
+        };
+        assert_eq!(
+            m,
+            Music::line(vec![
+                chord(vec![p!(D 4), p!(F 4), p!(A 4)]),
+                chord(vec![p!(G 4), p!(B 4), p!(D 5), p!(F 5)]),
+                chord(vec![p!(C 4), p!(E 4), p!(G 4)]),
+            ])
+        );
+        assert_eq!(m.duration(), Dur::new(3, 2));
+    }
+
+    #[test]
+    fn invalid_numeral() {
+        let res = KeySig::default().progression(&["I", "VIII"], Dur::HALF, Octave::OneLined);
+        assert!(res.is_err());
+    }
+
+    fn chord(pitches: &[Pitch]) -> Music {
+        Music::chord(
+            pitches
+                .iter()
+                .map(|p| Music::note(Dur::WHOLE, *p))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn dominant_seventh_from_intervals() {
+        let intervals = [4, 7, 10].map(Interval::from);
+        let m = Music::chord_from_intervals(p!(C 4), &intervals, Dur::HALF);
+
+        assert_eq!(m.duration(), Dur::HALF);
+        let pitches: Vec<_> = m.perform().iter().map(|e| e.pitch).collect();
+        assert_eq!(
+            pitches,
+            [p!(C 4), p!(E 4), p!(G 4), p!(Bf 4)].map(Pitch::abs)
+        );
+    }
+
+    #[test]
+    fn two_rows_grid() {
+        let m = Music::from_grid(&[(p!(C 4), "x.x."), (p!(E 4), ".x x")], Dur::QUARTER);
+
+        let q = Dur::QUARTER;
+        assert_eq!(
+            m,
+            Music::chord(vec![
+                Music::line(vec![
+                    Music::note(q, p!(C 4)),
+                    Music::rest(q),
+                    Music::note(q, p!(C 4)),
+                    Music::rest(q),
+                ]),
+                Music::line(vec![
+                    Music::rest(q),
+                    Music::note(q, p!(E 4)),
+                    Music::note(q, p!(E 4)),
+                ]),
+            ])
+        );
+
+        // the quarter lasts 1/2 second
+        let notes: Vec<_> = m
+            .perform()
+            .iter()
+            .map(|e| (e.start_time * 2, e.pitch))
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                (Ratio::from_integer(0), p!(C 4).abs()),
+                (Ratio::from_integer(1), p!(E 4).abs()),
+                (Ratio::from_integer(2), p!(C 4).abs()),
+                (Ratio::from_integer(2), p!(E 4).abs()),
+            ]
+        );
+    }
+
+    #[test]
+    fn seventh_chord_symbols() {
+        let oc4 = Octave::OneLined;
+        assert_eq!(
+            parse_chord_symbol("Cmaj7", oc4, Dur::WHOLE).unwrap(),
+            chord(&[p!(C 4), p!(E 4), p!(G 4), p!(B 4)])
+        );
+        assert_eq!(
+            parse_chord_symbol("G7", oc4, Dur::WHOLE).unwrap(),
+            chord(&[p!(G 4), p!(B 4), p!(D 5), p!(F 5)])
+        );
+        assert_eq!(
+            parse_chord_symbol("Dm7", oc4, Dur::WHOLE).unwrap(),
+            chord(&[p!(D 4), p!(F 4), p!(A 4), p!(C 5)])
+        );
+    }
+
+    #[test]
+    fn altered_chord_symbols() {
+        let oc4 = Octave::OneLined;
+        let abs_pitches = |sym| {
+            let m = parse_chord_symbol(sym, oc4, Dur::WHOLE).unwrap();
+            m.perform()
+                .iter()
+                .map(|e| u8::from(e.pitch.get_inner()))
+                .collect::<Vec<_>>()
+        };
+        let c4 = u8::from(p!(C 4).abs().get_inner());
+
+        // F# A C
+        assert_eq!(abs_pitches("F#dim"), [6, 9, 12].map(|i| c4 + i));
+        // Bb D F Ab C Eb G
+        assert_eq!(
+            abs_pitches("Bb13"),
+            [10, 14, 17, 20, 24, 27, 31].map(|i| c4 + i)
+        );
+        // G C D F
+        assert_eq!(abs_pitches("G7sus4"), [7, 12, 14, 17].map(|i| c4 + i));
+        // B D F A
+        assert_eq!(abs_pitches("Bm7b5"), abs_pitches("Bø7"));
+    }
+
+    #[test]
+    fn invalid_chord_symbols() {
+        for sym in ["", "H7", "cmaj7", "Cmaj8", "Cfoo"] {
+            assert!(parse_chord_symbol(sym, Octave::OneLined, Dur::WHOLE).is_err());
+        }
+    }
+
+    #[test]
+    fn arrange_overlapping_sections() {
+        let intro = Music::with_dur(vec![p!(C 4), p!(E 4), p!(G 4), p!(C 5)], Dur::HALF);
+        let bass = Music::with_dur(vec![p!(C 2), p!(G 2)], Dur::WHOLE);
+        let m = Music::arrange(vec![(Dur::ZERO, intro), (Dur::WHOLE, bass)]);
+        assert_eq!(m.duration(), Dur::from(3));
+
+        let events: Vec<_> = m
+            .perform()
+            .iter()
+            .map(|e| (e.start_time, e.pitch))
+            .collect();
+        // the bar lasts 2 seconds in the default tempo
+        assert_eq!(
+            events,
+            [
+                (Ratio::from(0), p!(C 4).abs()),
+                (Ratio::from(1), p!(E 4).abs()),
+                (Ratio::from(2), p!(C 2).abs()),
+                (Ratio::from(2), p!(G 4).abs()),
+                (Ratio::from(3), p!(C 5).abs()),
+                (Ratio::from(4), p!(G 2).abs()),
+            ]
+        );
+    }
 }
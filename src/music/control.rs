@@ -1,15 +1,23 @@
 use num_rational::Ratio;
+use num_traits::One as _;
 
 use crate::{
     instruments::InstrumentName,
-    prim::{duration::DurT, interval::Interval, scale::KeySig},
+    midi::PercussionSound,
+    prim::{
+        duration::{Dur, DurT},
+        interval::{Interval, NamedInterval},
+        scale::KeySig,
+        time_sig::TimeSignature,
+    },
 };
 
 use super::{
     combinators::MapToOther,
+    iter_like::Temporal as _,
     perf::{DynPlayer, Player},
-    phrase::PhraseAttribute,
-    Music,
+    phrase::{Dynamic, PhraseAttribute, Tempo, TempoCurve},
+    Music, Primitive,
 };
 
 #[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
@@ -57,6 +65,22 @@ impl<P> Music<P> {
         self.with(Control::Transpose(delta))
     }
 
+    /// Annotate the [`Music`] to transpose all its pitches up
+    /// by the given [`NamedInterval`] while performing.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Transposition_(music)>
+    pub fn transpose_up(self, interval: NamedInterval) -> Self {
+        self.with_transpose(Interval::from(interval))
+    }
+
+    /// Annotate the [`Music`] to transpose all its pitches down
+    /// by the given [`NamedInterval`] while performing.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Transposition_(music)>
+    pub fn transpose_down(self, interval: NamedInterval) -> Self {
+        self.with_transpose(-Interval::from(interval))
+    }
+
     /// Annotate the [`Music`] to use the given [`InstrumentName`] while performing.
     pub fn with_instrument(self, name: impl Into<InstrumentName>) -> Self {
         self.with(Control::Instrument(name.into()))
@@ -92,6 +116,129 @@ impl<P> Music<P> {
     pub fn with_key_sig(self, key_signature: KeySig) -> Self {
         self.with(Control::KeySig(key_signature))
     }
+
+    /// Change the tempo of the [`Music`] to make it last exactly the `target` duration
+    /// (e.g. to fit the music into a video cue).
+    ///
+    /// The [`Music`] of zero duration could not be stretched,
+    /// as well as it could not be shrunk to zero, so it is returned unchanged.
+    pub fn stretch_to(self, target: Dur) -> Self {
+        let current = self.duration();
+        if current == Dur::ZERO || target == Dur::ZERO || current == target {
+            return self;
+        }
+        self.with_tempo(current.into_ratio::<DurT>() / target.into_ratio::<DurT>())
+    }
+
+    /// Gradually increase the volume of the notes starting
+    /// during the first `over` duration of the [`Music`]
+    /// by applying the [`Dynamic::FadeIn`].
+    ///
+    /// The volume rises from near silence up to the original one:
+    /// every note is played with the fraction of the volume
+    /// equal to the part of the fade passed by the end of the note.
+    /// The notes are never split, so the note crossing the end of the fade
+    /// is played with the original volume.
+    ///
+    /// Note that the [`DefaultPlayer`][super::perf::DefaultPlayer] ignores
+    /// the gradual dynamics, so use the [`FancyPlayer`][super::perf::FancyPlayer].
+    pub fn with_fade_in(self, over: Dur) -> Self {
+        self.with_phrase(vec![PhraseAttribute::Dyn(Dynamic::FadeIn(over))])
+    }
+
+    /// Gradually decrease the volume towards silence of the notes starting
+    /// during the last `over` duration of the [`Music`]
+    /// by applying the [`Dynamic::FadeOut`].
+    ///
+    /// Note that the [`DefaultPlayer`][super::perf::DefaultPlayer] ignores
+    /// the gradual dynamics, so use the [`FancyPlayer`][super::perf::FancyPlayer].
+    pub fn with_fade_out(self, over: Dur) -> Self {
+        self.with_phrase(vec![PhraseAttribute::Dyn(Dynamic::FadeOut(over))])
+    }
+
+    /// Gradually slow down the last part of the [musical line][Self::line]
+    /// (e.g. the final bar marked as a [phrase][Self::with_phrase] or the final chord)
+    /// by applying the [`Tempo::RitardandoCurve`] with the [`TempoCurve::Delayed`]
+    /// to end the piece naturally.
+    ///
+    /// The tail is located by its duration: the whole [`Music`] is wrapped,
+    /// but the tempo stays the same until the last part starts,
+    /// so the notes before it keep their timing.
+    /// The `amount` defines how much longer the last part becomes.
+    ///
+    /// The [`Music`] should be finite.
+    ///
+    /// Note that the [`DefaultPlayer`][super::perf::DefaultPlayer] ignores
+    /// the gradual tempo changes, so use the [`FancyPlayer`][super::perf::FancyPlayer].
+    pub fn with_final_ritardando(self, amount: Ratio<u32>) -> Self {
+        let total = self.duration();
+        let tail = self.last_line_item_duration().unwrap_or(total);
+        if tail == Dur::ZERO {
+            return self;
+        }
+
+        let tail_fraction = tail.into_ratio::<DurT>() / total.into_ratio::<DurT>();
+        self.with_phrase(vec![PhraseAttribute::Tmp(Tempo::RitardandoCurve(
+            amount,
+            TempoCurve::Delayed(Ratio::one() - tail_fraction),
+        ))])
+    }
+
+    /// The duration of the last part of the [musical line][Self::line]
+    /// the same way the line is [split into the parts][Vec::from].
+    fn last_line_item_duration(&self) -> Option<Dur> {
+        match self {
+            Self::Prim(Primitive::Rest(Dur::ZERO)) => None,
+            Self::Sequential(m1, m2) => m2
+                .last_line_item_duration()
+                .or_else(|| m1.last_line_item_duration()),
+            Self::Lazy(it) => it
+                .clone()
+                .filter_map(|m| m.last_line_item_duration())
+                .last(),
+            other => Some(other.duration()),
+        }
+    }
+}
+
+impl Music {
+    /// Prepend the percussion count-in of the given number of `bars`
+    /// to the [`Music`] to help the musicians start together
+    /// (e.g. in the exports for practicing).
+    ///
+    /// Every beat of the [`TimeSignature`] is clicked,
+    /// with the first beat of a bar accented by the higher sound.
+    /// The clicks follow the tempo set at the top level of the [`Music`].
+    pub fn count_in(self, ts: &TimeSignature, bars: usize) -> Self {
+        let mut tempo = Ratio::one();
+        let mut top = &self;
+        while let Self::Modify(control, m) = top {
+            if let Control::Tempo(t) = control {
+                tempo *= t;
+            }
+            top = m;
+        }
+
+        let beat = ts.beat_duration();
+        let bar: Vec<_> = (0..ts.beats())
+            .map(|i| {
+                let sound = if i == 0 {
+                    PercussionSound::HiWoodBlock
+                } else {
+                    PercussionSound::LowWoodBlock
+                };
+                sound.note(beat)
+            })
+            .collect();
+        let clicks = Self::line(bar.iter().cycle().take(bar.len() * bars).cloned().collect())
+            .with_instrument(InstrumentName::Percussion);
+        let clicks = if tempo == Ratio::one() {
+            clicks
+        } else {
+            clicks.with_tempo(tempo)
+        };
+        clicks + self
+    }
 }
 
 impl<T, U> MapToOther<Control<U>> for Control<T>
@@ -109,3 +256,258 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        music::{
+            perf::{Context, FancyPlayer, Performable},
+            AttrNote,
+        },
+        n, p,
+        perf::Event,
+        IntervalQuality, Pitch, Volume,
+    };
+
+    fn volumes(m: Music) -> Vec<u8> {
+        let ctx = Context::with_default_player::<FancyPlayer>();
+        Performable::<AttrNote>::perform_with_context(m, ctx)
+            .iter()
+            .map(|e| u8::from(e.volume.get_inner()))
+            .collect()
+    }
+
+    fn melody() -> Music {
+        Music::line(vec![
+            n!(C 4 / 4).into(),
+            n!(D 4 / 4).into(),
+            n!(E 4 / 4).into(),
+            n!(F 4 / 4).into(),
+        ])
+    }
+
+    fn quiet(m: Music) -> Music {
+        m.with_phrase(vec![PhraseAttribute::Dyn(Dynamic::Loudness(Volume::from(
+            40,
+        )))])
+    }
+
+    #[test]
+    fn fade_in() {
+        let vols = volumes(quiet(melody().with_fade_in(Dur::WHOLE)));
+        assert_eq!(vols, [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn fade_in_only_affects_the_beginning() {
+        let vols = volumes(quiet(melody().with_fade_in(Dur::HALF)));
+        assert_eq!(vols, [20, 40, 40, 40]);
+    }
+
+    #[test]
+    fn fade_in_keeps_the_note_crossing_its_end() {
+        let m = Music::line(vec![
+            n!(C 4 / 4).into(),
+            n!(D 4 / 2).into(),
+            n!(E 4 / 4).into(),
+        ]);
+        let m = quiet(m.with_fade_in(Dur::HALF));
+        let ctx = Context::with_default_player::<FancyPlayer>();
+        let events: Vec<_> = Performable::<AttrNote>::perform_with_context(m, ctx)
+            .iter()
+            .collect();
+
+        // the half note crossing the end of the fade is attacked only once
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1].duration, Ratio::new(1, 1));
+        let vols: Vec<_> = events
+            .iter()
+            .map(|e| u8::from(e.volume.get_inner()))
+            .collect();
+        assert_eq!(vols, [20, 40, 40]);
+    }
+
+    #[test]
+    fn fade_out() {
+        let vols = volumes(melody().with_fade_out(Dur::HALF));
+        assert_eq!(vols[..3], [127, 127, 127]);
+        assert!(vols[2] > vols[3], "{vols:?}");
+    }
+
+    fn accelerando(curve: TempoCurve) -> Vec<Event> {
+        with_tempo_phrase(Tempo::AccelerandoCurve(Ratio::new(1, 4), curve))
+    }
+
+    fn with_tempo_phrase(tempo: Tempo) -> Vec<Event> {
+        let m = melody() + melody();
+        let m = m.with_phrase(vec![PhraseAttribute::Tmp(tempo)]);
+        let ctx = Context::with_default_player::<FancyPlayer>();
+        Performable::<AttrNote>::perform_with_context(m, ctx)
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn exponential_accelerando_compresses_later_events_more() {
+        let linear = accelerando(TempoCurve::Linear);
+        let exp = accelerando(TempoCurve::Exponential);
+        assert_eq!(linear.len(), 8);
+        assert_eq!(exp.len(), 8);
+
+        // both are getting faster
+        assert!(linear.windows(2).all(|w| w[0].duration > w[1].duration));
+        assert!(exp.windows(2).all(|w| w[0].duration > w[1].duration));
+
+        // the exponential starts slower...
+        assert!(exp[0].duration > linear[0].duration);
+        assert!(exp[4].start_time > linear[4].start_time);
+        // ...but ends faster
+        assert!(exp[7].duration < linear[7].duration);
+
+        // while the whole phrase still shrinks to the same length
+        let end = |e: &Event| e.start_time + e.duration;
+        assert_eq!(end(&exp[7]), end(&linear[7]));
+    }
+
+    #[test]
+    fn tempo_change_is_linear_by_default() {
+        assert_eq!(
+            with_tempo_phrase(Tempo::Accelerando(Ratio::new(1, 4))),
+            accelerando(TempoCurve::Linear)
+        );
+        assert_eq!(
+            with_tempo_phrase(Tempo::Ritardando(Ratio::new(1, 4))),
+            with_tempo_phrase(Tempo::RitardandoCurve(Ratio::new(1, 4), TempoCurve::Linear))
+        );
+    }
+
+    fn final_ritardando(m: Music, amount: Ratio<u32>) -> (Vec<Event>, Vec<Event>) {
+        let ctx = Context::with_default_player::<FancyPlayer>();
+        let original = Performable::<AttrNote>::perform_with_context(m.clone(), ctx)
+            .iter()
+            .collect();
+        let ctx = Context::with_default_player::<FancyPlayer>();
+        let slowed =
+            Performable::<AttrNote>::perform_with_context(m.with_final_ritardando(amount), ctx)
+                .iter()
+                .collect();
+        (original, slowed)
+    }
+
+    #[test]
+    fn final_ritardando_stretches_only_the_last_bar() {
+        let m = quiet(melody()) + quiet(melody());
+        let (original, slowed) = final_ritardando(m, Ratio::new(1, 2));
+
+        assert_eq!(slowed.len(), 8);
+        assert_eq!(slowed[..4], original[..4]);
+        // the last bar starts in time, but then slows down
+        assert_eq!(slowed[4].start_time, original[4].start_time);
+        assert!(slowed[4].duration > original[4].duration);
+        assert!(slowed[5..]
+            .iter()
+            .zip(&original[5..])
+            .all(|(s, o)| s.start_time > o.start_time && s.duration > o.duration));
+        assert!(slowed[4..]
+            .windows(2)
+            .all(|w| w[0].duration < w[1].duration));
+
+        // the bar of 2 seconds becomes a half longer
+        let end = |e: &Event| e.start_time + e.duration;
+        assert_eq!(end(&slowed[7]), end(&original[7]) + Ratio::one());
+    }
+
+    #[test]
+    fn final_ritardando_of_plain_notes_stretches_the_last_note() {
+        let (original, slowed) = final_ritardando(melody() + melody(), Ratio::new(1, 2));
+
+        assert_eq!(slowed.len(), 8);
+        assert_eq!(slowed[..7], original[..7]);
+        assert_eq!(slowed[7].start_time, original[7].start_time);
+        assert_eq!(slowed[7].duration, original[7].duration * Ratio::new(3, 2));
+    }
+
+    #[test]
+    fn stretch_to_target_duration() {
+        let m = melody() + melody();
+        assert_eq!(m.duration(), Dur::from(2));
+
+        let stretched = m.clone().stretch_to(Dur::from(4));
+        assert_eq!(stretched.duration(), Dur::from(4));
+
+        let end = |m: Music| {
+            m.perform()
+                .iter()
+                .map(|e| e.start_time + e.duration)
+                .max()
+                .unwrap()
+        };
+        assert_eq!(end(stretched), end(m.clone()) * 2);
+
+        assert_eq!(m.clone().stretch_to(Dur::HALF).duration(), Dur::HALF);
+        assert_eq!(m.clone().stretch_to(Dur::ZERO), m);
+    }
+
+    #[test]
+    fn stretch_silence_of_zero_duration() {
+        let m: Music = Music::rest(Dur::ZERO);
+        assert_eq!(m.clone().stretch_to(Dur::WHOLE), m);
+    }
+
+    #[test]
+    fn count_in_precedes_the_music() {
+        let m = melody().with_instrument(crate::midi::Instrument::Violin);
+        let perf: Vec<_> = m
+            .count_in(&TimeSignature::new(3, 4), 2)
+            .perform()
+            .iter()
+            .collect();
+        assert_eq!(perf.len(), 6 + 4);
+
+        let (clicks, music): (Vec<_>, Vec<_>) = perf
+            .iter()
+            .partition(|e| e.instrument == InstrumentName::Percussion);
+        assert_eq!(clicks.len(), 6);
+        assert_eq!(music.len(), 4);
+
+        let accent = PercussionSound::HiWoodBlock.note(Dur::QUARTER).perform();
+        let accent = accent.iter().next().unwrap().pitch;
+        let accented = clicks.iter().filter(|e| e.pitch == accent).count();
+        assert_eq!(accented, 2);
+
+        // two bars of 3/4 last 1.5 whole notes (3 seconds)
+        let last_click = clicks.iter().map(|e| e.start_time).max().unwrap();
+        let first_note = music.iter().map(|e| e.start_time).min().unwrap();
+        assert!(last_click < first_note);
+        assert_eq!(first_note, Ratio::from(3));
+    }
+
+    #[test]
+    fn count_in_follows_the_tempo() {
+        let m = melody().with_tempo(2);
+        let perf = m.count_in(&TimeSignature::default(), 1).perform();
+        let first_note = perf
+            .iter()
+            .find(|e| e.instrument != InstrumentName::Percussion)
+            .unwrap();
+        // a bar of 4/4 at the doubled tempo lasts one second
+        assert_eq!(first_note.start_time, Ratio::from(1));
+    }
+
+    #[test]
+    fn transpose_up_and_down_by_named_intervals() {
+        let fifth = NamedInterval::new(IntervalQuality::Perfect, 5).unwrap();
+        let fourth = NamedInterval::new(IntervalQuality::Perfect, 4).unwrap();
+
+        let pitch = |m: Music| Pitch::from(m.perform().iter().next().unwrap().pitch);
+        let m = Music::note(Dur::QUARTER, p!(C 4));
+
+        let up = pitch(m.clone().transpose_up(fifth));
+        let down = pitch(m.transpose_down(fourth));
+        assert_eq!(up, p!(G 4));
+        assert_eq!(down, p!(G 3));
+        assert_eq!(up.class(), down.class());
+    }
+}
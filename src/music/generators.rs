@@ -0,0 +1,441 @@
+//! Algorithmic composition helpers generating
+//! the melodies and accompaniments.
+//!
+//! The random generators are deterministic:
+//! the same seed always produces the same [`Music`].
+//!
+//! See more: <https://en.wikipedia.org/wiki/Algorithmic_composition>
+use std::{collections::HashMap, iter};
+
+use crate::{
+    instruments::InstrumentName,
+    prim::{
+        duration::Dur,
+        interval::{Interval, Octave},
+        pitch::{AbsPitch, Pitch, PitchClass},
+        scale::KeySig,
+        volume::Volume,
+    },
+};
+
+use super::{Music, Primitive};
+
+/// Generate a melody of quarter notes starting from the given [`Pitch`]
+/// (snapped to the `scale`) and moving up or down
+/// by one or two diatonic degrees on every of the `steps`.
+///
+/// See more: <https://en.wikipedia.org/wiki/Random_walk>
+pub fn random_walk(start: Pitch, steps: usize, seed: u64, scale: KeySig) -> Music {
+    let mut rng = SplitMix64::new(seed);
+    let start = start.abs().snap_to_key(scale);
+
+    let pitches = (0..steps).scan(start, |current, _| {
+        let degrees = [-2, -1, 1, 2][rng.below(4)];
+        *current = current.diatonic_trans(scale, degrees);
+        Some(Pitch::from(*current))
+    });
+
+    Music::with_dur(
+        iter::once(Pitch::from(start)).chain(pitches).collect(),
+        Dur::QUARTER,
+    )
+}
+
+/// Generate a melody of quarter notes of the given `length`
+/// using the first-order Markov chain.
+///
+/// The `transition` table provides the weights of every next [`PitchClass`]
+/// given the current one. The melody stops early if the current
+/// [`PitchClass`] has no transitions.
+///
+/// See more: <https://en.wikipedia.org/wiki/Markov_chain#Music>
+pub fn markov_melody<S: std::hash::BuildHasher>(
+    transition: &HashMap<PitchClass, Vec<(PitchClass, f64)>, S>,
+    start: PitchClass,
+    octave: Octave,
+    length: usize,
+    seed: u64,
+) -> Music {
+    let mut rng = SplitMix64::new(seed);
+
+    let classes = iter::successors(Some(start), |current| {
+        let choices = transition.get(current)?;
+        let total: f64 = choices.iter().map(|(_, w)| w.max(0.0)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut threshold = rng.fraction() * total;
+        choices
+            .iter()
+            .find(|(_, w)| {
+                threshold -= w.max(0.0);
+                threshold < 0.0
+            })
+            .or_else(|| choices.last())
+            .map(|(pc, _)| *pc)
+    });
+
+    Music::with_dur(
+        classes
+            .take(length)
+            .map(|pc| Pitch::new(pc, octave))
+            .collect(),
+        Dur::QUARTER,
+    )
+}
+
+/// Generate a simple walking bass line for the progression of `chords`.
+///
+/// Every chord is accompanied by its root, third and fifth
+/// followed by the chromatic approach tone (a semitone below the root of the next chord)
+/// each lasting the given `dur`.
+///
+/// The bass is played in the [great octave][Octave::Great]
+/// regardless of the register of the chords.
+/// The lowest note of a chord is treated as its root,
+/// and the missing chord tones are replaced with the root.
+///
+/// See more: <https://en.wikipedia.org/wiki/Walking_bass>
+pub fn walking_bass(chords: &[Music], dur: Dur) -> Music {
+    let roots: Vec<_> = chords
+        .iter()
+        .map(|chord| chord_tones(chord).first().copied())
+        .collect();
+
+    let bars = chords.iter().enumerate().flat_map(|(i, chord)| {
+        let tones = chord_tones(chord);
+        let Some(&root) = tones.first() else {
+            return vec![Music::rest(dur * 4)];
+        };
+        let bass_root = Pitch::new(Pitch::from(root).class(), Octave::Great).abs();
+        let chord_tone = |n: usize| bass_root + (tones.get(n).copied().unwrap_or(root) - root);
+
+        let next_root = roots
+            .iter()
+            .skip(i + 1)
+            .find_map(|r| *r)
+            .map_or(bass_root, |r| {
+                Pitch::new(Pitch::from(r).class(), Octave::Great).abs()
+            });
+        let approach = next_root - Interval::semi_tone();
+
+        [bass_root, chord_tone(1), chord_tone(2), approach]
+            .map(|p| Music::note(dur, Pitch::from(p)))
+            .to_vec()
+    });
+    Music::line(bars.collect())
+}
+
+/// Generate the endless [Shepard tone][shepard] illusion.
+///
+/// Every instrument plays the infinite sequence of the glides
+/// moving by the given `step` (up if it is positive and down otherwise)
+/// through the given number of `octaves`.
+///
+/// Every glide fades in from the silence and fades out back to it,
+/// so its beginning and end are difficult to hear
+/// and the music seems to rise (or fall) with no end.
+/// The glides differ in their central pitch, the tempo
+/// and the pauses between them chosen by the pseudo-random generator.
+///
+/// The [`Music`] is infinite, but lazy, so any part of it
+/// could be [taken][crate::Temporal::take] and performed.
+///
+/// [shepard]: https://en.wikipedia.org/wiki/Shepard_tone
+pub fn shepard_tone(
+    step: Interval,
+    instruments: &[InstrumentName],
+    octaves: u8,
+    seed: u64,
+) -> Music<(Pitch, Volume)> {
+    let mut rng = SplitMix64::new(seed);
+    let octave_size = u32::from(u8::from(Octave::semitones_number()));
+    let step_size = u32::from(step.get_inner().unsigned_abs()).max(1);
+    let notes = u32::from(octaves) * octave_size / step_size + 1;
+
+    let voices = instruments
+        .iter()
+        .map(|instrument| {
+            let rng = SplitMix64::new(rng.next_u64());
+            let glides =
+                iter::repeat(()).scan(rng, move |rng, ()| Some(shepard_glide(rng, step, notes)));
+            Music::lazy_line(glides).with_instrument(instrument.clone())
+        })
+        .collect();
+    Music::chord(voices)
+}
+
+/// A single glide of the [`shepard_tone`] centered around the random pitch
+/// of the first line octave followed by the short pause.
+fn shepard_glide(rng: &mut SplitMix64, step: Interval, notes: u32) -> Music<(Pitch, Volume)> {
+    const DURATIONS: [Dur; 5] = [
+        Dur::SIXTEENTH,
+        Dur::DOTTED_SIXTEENTH,
+        Dur::EIGHTH,
+        Dur::DOTTED_EIGHTH,
+        Dur::QUARTER,
+    ];
+    const PAUSES: [Dur; 2] = [Dur::EIGHTH, Dur::QUARTER];
+
+    let octave_size = usize::from(u8::from(Octave::semitones_number()));
+    let center = Pitch::C(Octave::OneLined).abs()
+        + Interval::from(i8::try_from(rng.below(octave_size)).expect("The octave is small"));
+    let dur = DURATIONS[rng.below(DURATIONS.len())];
+    let pause = PAUSES[rng.below(PAUSES.len())];
+
+    let half = i8::try_from((notes - 1) / 2).unwrap_or(i8::MAX);
+    let start = center - Interval::from(step.get_inner().saturating_mul(half));
+    let max_volume = u32::from(u8::from(Volume::loudest().get_inner()));
+    let peak = notes.div_ceil(2);
+
+    let glide = iter::successors(Some(start), move |p| Some(*p + step))
+        .zip(0..notes)
+        .map(move |(pitch, i)| {
+            // the volume rises to the middle of the glide and falls symmetrically
+            let level = (i + 1).min(notes - i);
+            let volume = u8::try_from(max_volume * level / peak).unwrap_or(u8::MAX);
+            Music::note(dur, (Pitch::from(pitch), Volume::from(volume)))
+        })
+        .chain(iter::once(Music::rest(pause)));
+    Music::lazy_line(glide)
+}
+
+/// The distinct pitches of the chord from the lowest to the highest.
+fn chord_tones(chord: &Music) -> Vec<AbsPitch> {
+    let concat = |mut p1: Vec<AbsPitch>, p2| {
+        p1.extend(p2);
+        p1
+    };
+    let mut tones = chord.fold_by_ref(
+        |prim| match prim {
+            Primitive::Note(_, p) => vec![p.abs()],
+            Primitive::Rest(_) => vec![],
+        },
+        concat,
+        (vec![], concat),
+        concat,
+        |_, p| p,
+    );
+    tones.sort_unstable();
+    tones.dedup();
+    tones
+}
+
+impl<P: 'static> Music<P> {
+    /// Transform every note of the [`Music`] with the function
+    /// sharing the same pseudo-random generator, so the result
+    /// depends on the order of the notes, but not on the structure.
+    ///
+    /// The [lazy lines][Self::Lazy] get their own generator seeded from the shared one
+    /// to keep them lazy.
+    pub(super) fn map_notes_randomly<F>(self, rng: &mut SplitMix64, f: F) -> Self
+    where
+        F: Fn(&mut SplitMix64, Self) -> Self + Clone + 'static,
+    {
+        match self {
+            n @ Self::Prim(Primitive::Note(..)) => f(rng, n),
+            r @ Self::Prim(Primitive::Rest(_)) => r,
+            Self::Sequential(m1, m2) => {
+                let m1 = m1.map_notes_randomly(rng, f.clone());
+                m1 + m2.map_notes_randomly(rng, f)
+            }
+            Self::Lazy(it) => {
+                let rng = SplitMix64::new(rng.next_u64());
+                Self::lazy_line(it.scan(rng, move |rng, m| {
+                    Some(m.map_notes_randomly(rng, f.clone()))
+                }))
+            }
+            Self::Parallel(m1, m2) => {
+                let m1 = m1.map_notes_randomly(rng, f.clone());
+                m1 | m2.map_notes_randomly(rng, f)
+            }
+            Self::Modify(c, m) => m.map_notes_randomly(rng, f).with(c),
+        }
+    }
+}
+
+/// Simple and fast deterministic pseudo-random generator.
+///
+/// See more: <https://prng.di.unimi.it/splitmix64.c>
+#[derive(Debug, Clone)]
+pub(super) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(super) const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(super) const fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed number in the range `[0..n)`.
+    pub(super) fn below(&mut self, n: usize) -> usize {
+        let n = u64::try_from(n).expect("usize fits into u64");
+        usize::try_from(self.next_u64() % n).expect("less than usize")
+    }
+
+    /// Uniformly distributed number in the range `[0..1)`.
+    #[allow(clippy::cast_precision_loss)] // 53 bits fit exactly into f64 mantissa
+    pub(super) fn fraction(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{p, Performable as _};
+
+    #[test]
+    fn same_seed_same_walk() {
+        let key = KeySig::Minor(PitchClass::A);
+        let m1 = random_walk(p!(A 4), 32, 42, key);
+        let m2 = random_walk(p!(A 4), 32, 42, key);
+        assert_eq!(m1, m2);
+
+        let m3 = random_walk(p!(A 4), 32, 43, key);
+        assert_ne!(m1, m3);
+    }
+
+    #[test]
+    fn random_walk_stays_in_scale() {
+        let key = KeySig::Major(PitchClass::D);
+        // C is not in the D major, so should be snapped
+        let m = random_walk(p!(C 4), 50, 7, key);
+        let events: Vec<_> = m.perform().iter().collect();
+        assert_eq!(events.len(), 51);
+        assert!(events.iter().all(|e| key.contains(e.pitch)));
+    }
+
+    fn transitions() -> HashMap<PitchClass, Vec<(PitchClass, f64)>> {
+        use PitchClass::*;
+
+        [
+            (C, vec![(E, 1.0), (G, 2.0)]),
+            (E, vec![(G, 1.0), (C, 1.0)]),
+            (G, vec![(C, 3.0), (E, 1.0)]),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn same_seed_same_markov_melody() {
+        let table = transitions();
+        let m1 = markov_melody(&table, PitchClass::C, Octave::OneLined, 20, 1);
+        let m2 = markov_melody(&table, PitchClass::C, Octave::OneLined, 20, 1);
+        assert_eq!(m1, m2);
+
+        let pitches: Vec<_> = Vec::from(m1)
+            .into_iter()
+            .filter_map(|m| match m {
+                Music::Prim(Primitive::Note(_, p)) => Some(p),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pitches.len(), 20);
+        assert_eq!(pitches[0], p!(C 4));
+        assert!(pitches
+            .iter()
+            .all(|p| [p!(C 4), p!(E 4), p!(G 4)].contains(p)));
+    }
+
+    #[test]
+    fn markov_melody_stops_without_transitions() {
+        let table = HashMap::from([(PitchClass::C, vec![(PitchClass::D, 1.0)])]);
+        let m = markov_melody(&table, PitchClass::C, Octave::OneLined, 10, 0);
+        assert_eq!(m, Music::with_dur(vec![p!(C 4), p!(D 4)], Dur::QUARTER));
+    }
+
+    #[test]
+    fn walking_bass_over_c_major() {
+        let chord = Music::chord(vec![
+            Music::C(Octave::OneLined, Dur::WHOLE),
+            Music::E(Octave::OneLined, Dur::WHOLE),
+            Music::G(Octave::OneLined, Dur::WHOLE),
+        ]);
+        let bass = walking_bass(&[chord], Dur::QUARTER);
+        assert_eq!(
+            bass,
+            Music::with_dur(vec![p!(C 2), p!(E 2), p!(G 2), p!(B 1)], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn walking_bass_approaches_next_chord() {
+        let chord = |pitches: Vec<Pitch>| {
+            Music::chord(
+                pitches
+                    .into_iter()
+                    .map(|p| Music::note(Dur::WHOLE, p))
+                    .collect(),
+            )
+        };
+        let chords = [
+            chord(vec![p!(D 4), p!(F 4), p!(A 4)]),
+            chord(vec![p!(G 3), p!(B 3), p!(D 4)]),
+        ];
+        let bass = walking_bass(&chords, Dur::QUARTER);
+
+        let pitches: Vec<_> = bass
+            .perform()
+            .iter()
+            .map(|e| Pitch::from(e.pitch))
+            .collect();
+        assert_eq!(pitches.len(), 8);
+        assert_eq!(pitches[..4], [p!(D 2), p!(F 2), p!(A 2), p!(Fs 2)]);
+        assert_eq!(pitches[4], p!(G 2));
+    }
+
+    fn instruments() -> Vec<InstrumentName> {
+        use crate::midi::Instrument;
+
+        vec![
+            Instrument::AcousticGrandPiano.into(),
+            Instrument::Flute.into(),
+            Instrument::Cello.into(),
+        ]
+    }
+
+    #[test]
+    fn shepard_tone_prefix_performable() {
+        let m = shepard_tone(-Interval::semi_tone(), &instruments(), 2, 42);
+
+        let events: Vec<_> = m.clone().perform().iter().take(10_000).collect();
+        assert_eq!(events.len(), 10_000);
+        assert!(events
+            .windows(2)
+            .all(|w| w[0].start_time <= w[1].start_time));
+
+        let prefix: Vec<_> = m.perform().iter().take(10_000).collect();
+        assert_eq!(prefix, events);
+    }
+
+    #[test]
+    fn reversed_step_ascends() {
+        let voice = |step| {
+            let m = shepard_tone(step, &instruments()[..1], 2, 7);
+            // a single glide of 25 notes
+            let events: Vec<_> = m.perform().iter().take(25).collect();
+            events
+                .into_iter()
+                .map(|e| (e.pitch, e.volume))
+                .collect::<Vec<_>>()
+        };
+
+        let descending = voice(-Interval::semi_tone());
+        let ascending = voice(Interval::semi_tone());
+        assert!(descending.windows(2).all(|w| w[0].0 > w[1].0));
+        assert!(ascending.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let reversed: Vec<_> = descending.into_iter().rev().collect();
+        assert_eq!(ascending, reversed);
+    }
+}
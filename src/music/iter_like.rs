@@ -1,5 +1,13 @@
+use itertools::Itertools as _;
+use num_integer::Integer as _;
+use num_rational::Ratio;
+use num_traits::Zero as _;
+
 use crate::{
-    prim::duration::Dur,
+    prim::{
+        duration::{Dur, DurT},
+        time_sig::TimeSignature,
+    },
     utils::{CloneableIterator, LazyList},
 };
 
@@ -36,6 +44,15 @@ impl<P> Music<P> {
             .rfold(Self::rest(Dur::ZERO), |acc, m| acc | m)
     }
 
+    /// Alternate the items of two [musical lines][Self::line]
+    /// taking them one by one from each line: `a1, b1, a2, b2, ...`.
+    ///
+    /// The rests are alternated the same way as the notes.
+    /// When one of the lines is over, the remainder of the other one is appended.
+    pub fn interleave(a: Self, b: Self) -> Self {
+        Self::line(Vec::from(a).into_iter().interleave(Vec::from(b)).collect())
+    }
+
     /// Strip away the [`Dur::ZERO`] occurrences that could appear
     /// during composition and [transformations][super::transform].
     pub fn remove_zeros(self) -> Self {
@@ -60,6 +77,162 @@ impl<P> Music<P> {
             Self::Modify(c, m) => m.remove_zeros().with(c),
         }
     }
+
+    /// Combine the runs of adjacent [rests][Primitive::Rest] of every line
+    /// into the single rest of the total duration.
+    ///
+    /// The performance of the [`Music`] is not changed,
+    /// while its structure becomes simpler.
+    ///
+    /// The items of the [lazy line][Self::Lazy] are processed one by one,
+    /// so the rests ending one item and starting the next one are not merged.
+    pub fn merge_rests(self) -> Self {
+        match self {
+            Self::Sequential(..) => {
+                let mut merged: Vec<Self> = Vec::new();
+                for item in self.into_line_items() {
+                    match (merged.last_mut(), item.merge_rests()) {
+                        (
+                            Some(Self::Prim(Primitive::Rest(total))),
+                            Self::Prim(Primitive::Rest(d)),
+                        ) => *total = *total + d,
+                        (_, item) => merged.push(item),
+                    }
+                }
+                Self::from_line_items(merged)
+            }
+            Self::Lazy(it) => Self::lazy_line(it.map(Self::merge_rests)),
+            Self::Parallel(m1, m2) => m1.merge_rests() | m2.merge_rests(),
+            Self::Modify(c, m) => m.merge_rests().with(c),
+            prim @ Self::Prim(_) => prim,
+        }
+    }
+
+    /// Flatten the nested [sequential][Self::Sequential] structure into the items.
+    pub(super) fn into_line_items(self) -> Vec<Self> {
+        match self {
+            Self::Sequential(m1, m2) => {
+                let mut items = m1.into_line_items();
+                items.extend(m2.into_line_items());
+                items
+            }
+            other => vec![other],
+        }
+    }
+
+    /// Combine the items into the [sequential][Self::Sequential] structure,
+    /// the reverse of the [`Self::into_line_items`].
+    ///
+    /// Unlike the [`Self::line`], no trailing empty rest is added,
+    /// while no items at all produce the single empty rest.
+    pub(super) fn from_line_items<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = Self>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        items
+            .into_iter()
+            .rev()
+            .reduce(|tail, m| m + tail)
+            .unwrap_or_else(|| Self::rest(Dur::ZERO))
+    }
+
+    /// How many bars (measures) of the given [`TimeSignature`]
+    /// the [`Music`] spans. The last bar could be incomplete,
+    /// so the result is fractional.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Bar_(music)>
+    pub fn measure_count(&self, ts: &TimeSignature) -> Ratio<u32> {
+        self.duration().into_ratio() / ts.measure_duration().into_ratio()
+    }
+
+    /// The longest [`Dur`] fitting into every note and rest
+    /// of the [`Music`] a whole number of times
+    /// (the greatest common divisor of all the durations).
+    ///
+    /// It defines the resolution of the grid (e.g. in a tracker)
+    /// needed to represent the [`Music`] exactly.
+    /// The [tempo changes][Control::Tempo] are taken into account.
+    ///
+    /// The empty [`Music`] yields [`Dur::ZERO`].
+    ///
+    /// # Panics
+    /// If the [`Music`] contains an infinite [lazy line][Self::Lazy].
+    pub fn smallest_subdivision(&self) -> Dur {
+        fn gcd(a: Ratio<DurT>, b: Ratio<DurT>) -> Ratio<DurT> {
+            Ratio::new(a.numer().gcd(b.numer()), a.denom().lcm(b.denom()))
+        }
+
+        let subdivision = self.fold_by_ref(
+            |prim| match prim {
+                Primitive::Note(d, _) | Primitive::Rest(d) => d.into_ratio(),
+            },
+            gcd,
+            (Ratio::zero(), gcd),
+            gcd,
+            |ctrl, d| {
+                if let Control::Tempo(r) = ctrl {
+                    d / *r
+                } else {
+                    d
+                }
+            },
+        );
+        Dur::from(subdivision)
+    }
+
+    /// Whether no two notes of the [`Music`] ever sound simultaneously,
+    /// so it could be written as a single voice (e.g. on a single staff).
+    ///
+    /// The [`Parallel`][Self::Parallel] composition is allowed
+    /// as long as the notes of its parts do not overlap in time.
+    /// The [tempo changes][Control::Tempo] are taken into account.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Monophony>
+    ///
+    /// # Panics
+    /// If the [`Music`] contains an infinite [lazy line][Self::Lazy].
+    pub fn is_monophonic(&self) -> bool {
+        type Spans = (Ratio<DurT>, Vec<(Ratio<DurT>, Ratio<DurT>)>);
+
+        fn seq((d1, mut spans1): Spans, (d2, spans2): Spans) -> Spans {
+            spans1.extend(spans2.into_iter().map(|(s, e)| (s + d1, e + d1)));
+            (d1 + d2, spans1)
+        }
+
+        fn par((d1, mut spans1): Spans, (d2, spans2): Spans) -> Spans {
+            spans1.extend(spans2);
+            (d1.max(d2), spans1)
+        }
+
+        let (_, mut spans) = self.fold_by_ref(
+            |prim| match prim {
+                Primitive::Note(d, _) if *d > Dur::ZERO => {
+                    (d.into_ratio(), vec![(Ratio::zero(), d.into_ratio())])
+                }
+                Primitive::Note(d, _) | Primitive::Rest(d) => (d.into_ratio(), vec![]),
+            },
+            seq,
+            ((Ratio::zero(), vec![]), seq),
+            par,
+            |ctrl, (d, spans)| {
+                if let Control::Tempo(r) = ctrl {
+                    let spans = spans.into_iter().map(|(s, e)| (s / *r, e / *r)).collect();
+                    (d / *r, spans)
+                } else {
+                    (d, spans)
+                }
+            },
+        );
+
+        spans.sort_unstable();
+        spans
+            .into_iter()
+            .try_fold(Ratio::zero(), |sounding_until, (start, end)| {
+                (start >= sounding_until).then_some(end)
+            })
+            .is_some()
+    }
 }
 
 /// Entity that have a temporal duration.
@@ -157,6 +330,20 @@ impl<P> Temporal for Music<P> {
     }
 }
 
+impl<P: Clone> Music<P> {
+    /// Split the [`Music`] into the parts before and after the given time point,
+    /// so the [concatenation][Self::Sequential] of them sounds like the original.
+    ///
+    /// It combines the [`take`][Temporal::take] and the [`skip`][Temporal::skip]
+    /// into a single operation: the note sounding at the time point
+    /// is divided into two notes of the same pitch.
+    pub fn split_at(self, at: Dur) -> (Self, Self) {
+        let before = self.clone().take(at).remove_zeros();
+        let after = self.skip(at).remove_zeros();
+        (before, after)
+    }
+}
+
 impl<P> From<Music<P>> for Vec<Music<P>> {
     fn from(value: Music<P>) -> Self {
         match value {
@@ -169,3 +356,215 @@ impl<P> From<Music<P>> for Vec<Music<P>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Octave;
+
+    #[test]
+    fn line_is_monophonic() {
+        let oc4 = Octave::OneLined;
+        let m = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::rest(Dur::EIGHTH),
+            Music::E(oc4, Dur::QUARTER),
+        ]);
+        assert!(m.is_monophonic());
+        assert!(m.with_tempo(2).is_monophonic());
+        assert!(Music::<()>::rest(Dur::WHOLE).is_monophonic());
+    }
+
+    #[test]
+    fn chord_is_not_monophonic() {
+        let oc4 = Octave::OneLined;
+        let m = Music::chord(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::E(oc4, Dur::QUARTER),
+        ]);
+        assert!(!m.is_monophonic());
+
+        // the overlapping tails are not monophonic either
+        let m =
+            Music::C(oc4, Dur::HALF) | (Music::rest(Dur::QUARTER) + Music::E(oc4, Dur::QUARTER));
+        assert!(!m.is_monophonic());
+    }
+
+    #[test]
+    fn parallel_of_disjoint_lines_is_monophonic() {
+        let oc4 = Octave::OneLined;
+        let a = Music::C(oc4, Dur::QUARTER) + Music::rest(Dur::QUARTER);
+        let b = Music::rest(Dur::QUARTER) + Music::E(oc4, Dur::QUARTER);
+        assert!((a.clone() | b.clone()).is_monophonic());
+
+        // the tempo change makes the second part start earlier
+        let b = b.with_tempo(2);
+        assert!(!(a | b).is_monophonic());
+    }
+
+    #[test]
+    fn interleave_two_lines() {
+        let oc4 = Octave::OneLined;
+        let a = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::D(oc4, Dur::QUARTER),
+        ]);
+        let b = Music::line(vec![
+            Music::E(oc4, Dur::QUARTER),
+            Music::F(oc4, Dur::QUARTER),
+        ]);
+
+        assert_eq!(
+            Music::interleave(a, b),
+            Music::line(vec![
+                Music::C(oc4, Dur::QUARTER),
+                Music::E(oc4, Dur::QUARTER),
+                Music::D(oc4, Dur::QUARTER),
+                Music::F(oc4, Dur::QUARTER),
+            ])
+        );
+    }
+
+    #[test]
+    fn interleave_unequal_lines() {
+        let oc4 = Octave::OneLined;
+        let a = Music::C(oc4, Dur::QUARTER);
+        let b = Music::line(vec![
+            Music::E(oc4, Dur::QUARTER),
+            Music::rest(Dur::EIGHTH),
+            Music::G(oc4, Dur::QUARTER),
+        ]);
+
+        assert_eq!(
+            Music::interleave(a, b),
+            Music::line(vec![
+                Music::C(oc4, Dur::QUARTER),
+                Music::E(oc4, Dur::QUARTER),
+                Music::rest(Dur::EIGHTH),
+                Music::G(oc4, Dur::QUARTER),
+            ])
+        );
+    }
+
+    #[test]
+    fn split_whole_note_at_quarter() {
+        let whole = Music::C(Octave::OneLined, Dur::WHOLE);
+
+        let (before, after) = whole.clone().split_at(Dur::QUARTER);
+        assert_eq!(before, Music::C(Octave::OneLined, Dur::QUARTER));
+        assert_eq!(after, Music::C(Octave::OneLined, Dur::DOTTED_HALF));
+        // the tied notes recombine into the original one
+        assert_eq!(before.duration() + after.duration(), whole.duration());
+    }
+
+    #[test]
+    fn split_line_inside_the_note() {
+        let oc4 = Octave::OneLined;
+        let m = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::D(oc4, Dur::HALF),
+            Music::E(oc4, Dur::QUARTER),
+        ]);
+
+        let (before, after) = m.clone().split_at(Dur::HALF);
+        assert_eq!(
+            Vec::from(before.clone()),
+            [Music::C(oc4, Dur::QUARTER), Music::D(oc4, Dur::QUARTER)]
+        );
+        assert_eq!(
+            Vec::from(after.clone()),
+            [Music::D(oc4, Dur::QUARTER), Music::E(oc4, Dur::QUARTER)]
+        );
+        assert_eq!((before + after).duration(), m.duration());
+    }
+
+    #[test]
+    fn split_after_the_end() {
+        let m = Music::C(Octave::OneLined, Dur::HALF);
+
+        let (before, after) = m.clone().split_at(Dur::WHOLE);
+        assert_eq!(before, m);
+        assert_eq!(after.duration(), Dur::ZERO);
+    }
+
+    #[test]
+    fn subdivision_of_quarters_and_eighths() {
+        let oc4 = Octave::OneLined;
+        let m = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::D(oc4, Dur::EIGHTH),
+            Music::E(oc4, Dur::EIGHTH),
+            Music::rest(Dur::HALF),
+        ]);
+        assert_eq!(m.smallest_subdivision(), Dur::EIGHTH);
+
+        let triplet =
+            Music::line(vec![Music::G(oc4, Dur::QUARTER); 3]).with_tempo(Ratio::new(3, 2));
+        let with_triplet = m | triplet;
+        // both the eighth and the triplet quarter (1/6) fit into 1/24
+        assert_eq!(with_triplet.smallest_subdivision(), Dur::new(1, 24));
+    }
+
+    #[test]
+    fn subdivision_of_empty_music() {
+        assert_eq!(
+            Music::<()>::rest(Dur::ZERO).smallest_subdivision(),
+            Dur::ZERO
+        );
+    }
+
+    #[test]
+    fn measures_of_quarters() {
+        let quarter = Music::C(Octave::OneLined, Dur::QUARTER);
+        let ts = TimeSignature::default();
+
+        let bar = Music::line(vec![quarter.clone(); 4]);
+        assert_eq!(bar.measure_count(&ts), Ratio::from_integer(1));
+
+        let longer = bar + quarter;
+        assert_eq!(longer.measure_count(&ts), Ratio::new(5, 4));
+        assert_eq!(
+            longer.measure_count(&TimeSignature::new(3, 4)),
+            Ratio::new(5, 3)
+        );
+    }
+
+    #[test]
+    fn merge_consecutive_rests() {
+        let oc4 = Octave::OneLined;
+        let m = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::rest(Dur::QUARTER),
+            Music::rest(Dur::QUARTER),
+            Music::rest(Dur::QUARTER),
+            Music::D(oc4, Dur::QUARTER),
+        ]);
+        let merged = m.clone().merge_rests();
+        assert_eq!(
+            Vec::from(merged.clone()),
+            [
+                Music::C(oc4, Dur::QUARTER),
+                Music::rest(Dur::DOTTED_HALF),
+                Music::D(oc4, Dur::QUARTER),
+            ]
+        );
+        assert!(merged.performs_same_as(&m));
+    }
+
+    #[test]
+    fn rests_of_different_lazy_items_are_not_merged() {
+        let items: Vec<Music> = vec![
+            Music::rest(Dur::QUARTER),
+            Music::rest(Dur::QUARTER) + Music::rest(Dur::QUARTER),
+        ];
+        let merged = Music::lazy_line(items.into_iter()).merge_rests();
+        let Music::Lazy(it) = merged else {
+            panic!("The lazy line should stay lazy");
+        };
+        assert_eq!(
+            it.collect::<Vec<_>>(),
+            [Music::rest(Dur::QUARTER), Music::rest(Dur::HALF)]
+        );
+    }
+}
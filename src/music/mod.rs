@@ -11,6 +11,7 @@
 mod combinators;
 mod constructors;
 mod control;
+pub mod generators;
 mod iter_like;
 mod ops;
 mod ornaments;
@@ -18,6 +19,8 @@ pub mod perf;
 pub mod phrase;
 mod transform;
 
+use num_rational::Ratio;
+use num_traits::Zero as _;
 use ordered_float::OrderedFloat;
 use ux2::u4;
 
@@ -28,7 +31,7 @@ use crate::{
 
 pub use self::{
     combinators::MapToOther,
-    constructors::{rests, A440},
+    constructors::{parse_chord_symbol, rests, A440},
     control::Control,
     iter_like::Temporal,
 };
@@ -51,6 +54,33 @@ impl<P> From<(Dur, P)> for Primitive<P> {
     }
 }
 
+impl<P> Primitive<P> {
+    /// Whether the [`Primitive`] is a [note][Self::Note].
+    pub const fn is_note(&self) -> bool {
+        matches!(self, Self::Note(..))
+    }
+
+    /// Whether the [`Primitive`] is a [rest][Self::Rest].
+    pub const fn is_rest(&self) -> bool {
+        matches!(self, Self::Rest(_))
+    }
+
+    /// The duration of the [note][Self::Note] or the [rest][Self::Rest].
+    pub const fn duration(&self) -> Dur {
+        match self {
+            Self::Note(d, _) | Self::Rest(d) => *d,
+        }
+    }
+
+    /// The pitch of the [note][Self::Note] or `None` for the [rest][Self::Rest].
+    pub const fn pitch(&self) -> Option<&P> {
+        match self {
+            Self::Note(_, p) => Some(p),
+            Self::Rest(_) => None,
+        }
+    }
+}
+
 impl<P> From<(Dur, P)> for Music<P> {
     fn from(value: (Dur, P)) -> Self {
         Self::Prim(value.into())
@@ -91,11 +121,87 @@ impl<P> From<Primitive<P>> for Music<P> {
     }
 }
 
+impl<P> Music<P> {
+    /// Whether the [`Music`] is a single [note][Primitive::Note].
+    pub const fn is_note(&self) -> bool {
+        matches!(self, Self::Prim(Primitive::Note(..)))
+    }
+
+    /// The duration and the pitch of the single [note][Primitive::Note]
+    /// or `None` for any other [`Music`].
+    pub const fn as_note(&self) -> Option<(Dur, &P)> {
+        match self {
+            Self::Prim(Primitive::Note(d, p)) => Some((*d, p)),
+            _ => None,
+        }
+    }
+}
+
 impl Music {
     /// Assign [`Volume`] to every note of [`Music`].
     pub fn with_volume(self, vol: Volume) -> Music<(Pitch, Volume)> {
         self.map(move |p| (p, vol))
     }
+
+    /// The distribution of the [pitch classes][crate::PitchClass]
+    /// (enharmonically equivalent ones are collapsed, starting from the C)
+    /// weighted by the durations of the notes
+    /// and normalized to sum up to 1.
+    ///
+    /// The [tempo][Control::Tempo] and [transposition][Control::Transpose]
+    /// modifiers are taken into account.
+    /// The silent [`Music`] has all the weights equal to zero.
+    ///
+    /// Could be used as the basis for the key detection.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Pitch_class>
+    ///
+    /// # Panics
+    /// If the [`Music`] contains an infinite [lazy line][Self::Lazy].
+    pub fn pitch_class_histogram(&self) -> [Ratio<u32>; 12] {
+        let add = |mut h1: [Ratio<u32>; 12], h2: [Ratio<u32>; 12]| {
+            for (w1, w2) in h1.iter_mut().zip(h2) {
+                *w1 += w2;
+            }
+            h1
+        };
+        let histogram = self.fold_by_ref(
+            |prim| {
+                let mut histogram = [Ratio::zero(); 12];
+                if let Primitive::Note(d, p) = prim {
+                    let class = usize::try_from(p.class().distance_from_c().rem_euclid(12))
+                        .expect("Non-negative remainder");
+                    histogram[class] = d.into_ratio();
+                }
+                histogram
+            },
+            add,
+            ([Ratio::zero(); 12], add),
+            add,
+            |c, mut histogram| {
+                match c {
+                    Control::Tempo(r) if !r.is_zero() => {
+                        for w in &mut histogram {
+                            *w /= r;
+                        }
+                    }
+                    Control::Transpose(delta) => {
+                        let shift = usize::try_from(delta.get_inner().rem_euclid(12))
+                            .expect("Non-negative remainder");
+                        histogram.rotate_right(shift);
+                    }
+                    _ => {}
+                }
+                histogram
+            },
+        );
+
+        let total: Ratio<u32> = histogram.iter().sum();
+        if total.is_zero() {
+            return histogram;
+        }
+        histogram.map(|w| w / total)
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -146,3 +252,141 @@ impl From<Music<(Pitch, Volume)>> for MusicAttr {
         value.map(|(pitch, vol)| (pitch, vec![NoteAttribute::Volume(vol)]))
     }
 }
+
+impl MusicAttr {
+    /// Attach the [parameters][NoteAttribute::Params]
+    /// for the [custom instrument][crate::instruments::InstrumentName::Custom]
+    /// to every note of the [`Music`].
+    ///
+    /// The parameters override the previously attached ones.
+    pub fn with_params(self, params: Vec<f64>) -> Self {
+        let params: Vec<_> = params.into_iter().map(OrderedFloat).collect();
+        self.map_attributes(move |attrs| attrs.push(NoteAttribute::Params(params.clone())))
+    }
+
+    /// Transform the previously attached [parameters][NoteAttribute::Params]
+    /// of every note of the [`Music`].
+    pub fn map_params<F>(self, f: F) -> Self
+    where
+        F: Fn(Vec<f64>) -> Vec<f64> + Clone + 'static,
+    {
+        self.map_attributes(move |attrs| {
+            for attr in attrs {
+                if let NoteAttribute::Params(params) = attr {
+                    let values = params.iter().map(|x| x.into_inner()).collect();
+                    *params = f(values).into_iter().map(OrderedFloat).collect();
+                }
+            }
+        })
+    }
+
+    /// Modify the attributes of every note preserving
+    /// all the structure and annotations of the [`Music`].
+    fn map_attributes<F>(self, mut f: F) -> Self
+    where
+        F: FnMut(&mut Vec<NoteAttribute>) + Clone + 'static,
+    {
+        match self {
+            Self::Prim(Primitive::Note(d, (p, mut attrs))) => {
+                f(&mut attrs);
+                Self::note(d, (p, attrs))
+            }
+            Self::Prim(Primitive::Rest(_)) => self,
+            Self::Sequential(m1, m2) => m1.map_attributes(f.clone()) + m2.map_attributes(f),
+            Self::Lazy(it) => Self::lazy_line(it.map(move |m| m.map_attributes(f.clone()))),
+            Self::Parallel(m1, m2) => m1.map_attributes(f.clone()) | m2.map_attributes(f),
+            Self::Modify(c, m) => m.map_attributes(f).with(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{n, p, Interval, Performable as _};
+
+    #[test]
+    fn primitive_accessors() {
+        let note = Primitive::from(n!(C 4 / 4));
+        assert!(note.is_note());
+        assert!(!note.is_rest());
+        assert_eq!(note.duration(), Dur::QUARTER);
+        assert_eq!(note.pitch(), Some(&p!(C 4)));
+
+        let rest: Primitive<Pitch> = Primitive::Rest(Dur::HALF);
+        assert!(!rest.is_note());
+        assert!(rest.is_rest());
+        assert_eq!(rest.duration(), Dur::HALF);
+        assert_eq!(rest.pitch(), None);
+    }
+
+    #[test]
+    fn music_accessors() {
+        let note = Music::from(n!(E 4 / 8));
+        assert!(note.is_note());
+        assert_eq!(note.as_note(), Some((Dur::EIGHTH, &p!(E 4))));
+
+        let rest: Music = Music::rest(Dur::EIGHTH);
+        assert!(!rest.is_note());
+        assert_eq!(rest.as_note(), None);
+
+        let line = note.clone() + note;
+        assert!(!line.is_note());
+        assert_eq!(line.as_note(), None);
+    }
+
+    #[test]
+    fn params_flow_into_events() {
+        let m = MusicAttr::from(Music::from(n!(C 4 / 4)) + Music::rest(Dur::QUARTER))
+            .with_params(vec![0.5, 2.0]);
+        let m = m.clone() | m.map_params(|p| p.into_iter().map(|x| x * 2.0).collect());
+
+        let params: Vec<Vec<f64>> = m
+            .perform()
+            .iter()
+            .map(|e| e.params.into_iter().map(OrderedFloat::into_inner).collect())
+            .collect();
+        assert_eq!(params, [vec![0.5, 2.0], vec![1.0, 4.0]]);
+    }
+
+    #[test]
+    fn pitch_class_histogram_of_triad() {
+        let triad = Music::chord(vec![
+            n!(C 4 / 2).into(),
+            n!(E 4 / 2).into(),
+            n!(G 4 / 2).into(),
+        ]);
+        let histogram = triad.pitch_class_histogram();
+
+        let third = Ratio::new(1, 3);
+        for (class, weight) in histogram.into_iter().enumerate() {
+            if [0, 4, 7].contains(&class) {
+                assert_eq!(weight, third);
+            } else {
+                assert!(weight.is_zero());
+            }
+        }
+    }
+
+    #[test]
+    fn pitch_class_histogram_weighted_by_duration() {
+        // enharmonic B# and C are collapsed
+        let m = Music::line(vec![
+            n!(C 4 / 4).into(),
+            Music::note(Dur::QUARTER, p!(Bs 3)),
+            n!(D 4 / 2).into(),
+            Music::rest(Dur::WHOLE),
+        ]);
+        let histogram = m.pitch_class_histogram();
+        assert_eq!(histogram[0], Ratio::new(1, 2));
+        assert_eq!(histogram[2], Ratio::new(1, 2));
+
+        let transposed = m.with_transpose(Interval::tone()).pitch_class_histogram();
+        assert_eq!(transposed[2], Ratio::new(1, 2));
+        assert_eq!(transposed[4], Ratio::new(1, 2));
+
+        let silent: Music = Music::rest(Dur::WHOLE);
+        assert!(silent.pitch_class_histogram().iter().all(Ratio::is_zero));
+    }
+}
@@ -14,9 +14,17 @@ use num_traits::{CheckedSub as _, Zero as _};
 use crate::prim::{
     duration::{Dur, DurT},
     interval::Interval,
+    pitch::Pitch,
+    scale::KeySig,
 };
 
-use super::{control::Control, phrase::TrillOptions, Music, Primitive, Temporal as _};
+use super::{
+    control::Control,
+    generators::SplitMix64,
+    perf::{default_whole_note, is_melodic, ornament_notes, Duration},
+    phrase::{Ornament, PhraseAttribute, TrillOptions},
+    Music, Primitive, Temporal as _,
+};
 
 impl Music {
     /// Adds a single short transposed note before the principal one
@@ -104,11 +112,196 @@ impl Music {
     pub fn roll(&self, opts: impl Into<TrillOptions<Dur>>) -> Result<Self, String> {
         self.trill(Interval::zero(), opts)
     }
+
+    /// Rapid single alternation between the principal note
+    /// and the note above (`upper=true`) or below it.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Mordent>
+    pub fn mordent(&self, upper: bool) -> Result<Self, String> {
+        match self {
+            Self::Prim(Primitive::Note(d, p)) => {
+                let other = if upper {
+                    Interval::tone()
+                } else {
+                    -Interval::tone()
+                };
+                Ok(Self::line(vec![
+                    Self::note(*d / 8, *p),
+                    Self::note(*d / 8, p.trans(other)),
+                    Self::note(*d / 4, *p),
+                    Self::note(*d / 2, *p),
+                ]))
+            }
+            Self::Modify(c, m) => m.mordent(upper).map(|m| m.with(c.clone())),
+            _ => Err("Can only construct a mordent from a note".into()),
+        }
+    }
+
+    /// Play the notes of a chord one after another
+    /// going up (`up=true`) or down instead of striking them together.
+    ///
+    /// The arpeggio is defined as a [phrase attribute][PhraseAttribute],
+    /// so it is only applied by the [players][super::perf::FancyPlayer]
+    /// supporting it.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Arpeggio>
+    pub fn arpeggio(self, up: bool) -> Result<Self, String> {
+        let mut inner = &self;
+        while let Self::Modify(_, m) = inner {
+            inner = m;
+        }
+        if !matches!(inner, Self::Parallel(..)) {
+            return Err("Can only construct an arpeggio from a chord".into());
+        }
+
+        let ornament = if up {
+            Ornament::ArpeggioUp
+        } else {
+            Ornament::ArpeggioDown
+        };
+        Ok(self.with_phrase(vec![PhraseAttribute::Orn(ornament)]))
+    }
+
+    /// Realize the melodic [`Ornament`]s (e.g. the trills, mordents and turns)
+    /// attached as the [phrase attributes][PhraseAttribute] into the explicit notes,
+    /// the same way as the [`FancyPlayer`][super::perf::FancyPlayer] performs them.
+    ///
+    /// The expanded [`Music`] sounds the ornaments with any [player][super::perf::Player]
+    /// and is exported to MIDI with all the notes of the ornaments.
+    /// The other phrase attributes (e.g. the arpeggios or the dynamics) are kept intact.
+    ///
+    /// The [trilled notes of the given length][TrillOptions::Duration]
+    /// are measured in seconds of the default tempo
+    /// (see [`Context::with_tempo`][super::perf::Context::with_tempo]).
+    pub fn expand_ornaments(self) -> Self {
+        self.expand_ornaments_in(KeySig::default(), Interval::zero(), default_whole_note())
+    }
+
+    fn expand_ornaments_in(self, key: KeySig, transpose: Interval, whole_note: Duration) -> Self {
+        match self {
+            Self::Prim(_) => self,
+            Self::Sequential(m1, m2) => {
+                m1.expand_ornaments_in(key, transpose, whole_note)
+                    + m2.expand_ornaments_in(key, transpose, whole_note)
+            }
+            Self::Lazy(it) => {
+                Self::lazy_line(it.map(move |m| m.expand_ornaments_in(key, transpose, whole_note)))
+            }
+            Self::Parallel(m1, m2) => {
+                m1.expand_ornaments_in(key, transpose, whole_note)
+                    | m2.expand_ornaments_in(key, transpose, whole_note)
+            }
+            Self::Modify(Control::Phrase(attrs), m) => {
+                let mut m = m.expand_ornaments_in(key, transpose, whole_note);
+                let mut kept = vec![];
+                for attr in attrs {
+                    match attr {
+                        PhraseAttribute::Orn(ornament) if is_melodic(&ornament) => {
+                            m = m.realize_ornament(ornament, key, transpose, whole_note);
+                        }
+                        other => kept.push(other),
+                    }
+                }
+                if kept.is_empty() {
+                    m
+                } else {
+                    m.with_phrase(kept)
+                }
+            }
+            Self::Modify(c, m) => {
+                let (key, transpose, whole_note) = Self::in_control(&c, key, transpose, whole_note);
+                m.expand_ornaments_in(key, transpose, whole_note).with(c)
+            }
+        }
+    }
+
+    /// Replace every note with the notes of the [`Ornament`].
+    fn realize_ornament(
+        self,
+        ornament: Ornament,
+        key: KeySig,
+        transpose: Interval,
+        whole_note: Duration,
+    ) -> Self {
+        match self {
+            Self::Prim(Primitive::Note(d, p)) => {
+                // the ornament is built around the performed pitch
+                let performed = p.abs() + transpose;
+                let notes = ornament_notes(ornament, d.into_ratio() * whole_note, performed, key)
+                    .into_iter()
+                    .map(|(dur, pitch)| {
+                        let pitch = if pitch == performed {
+                            p
+                        } else {
+                            key.spell(pitch - transpose)
+                        };
+                        Self::note(Dur::from(dur / whole_note), pitch)
+                    })
+                    .collect();
+                Self::line(notes)
+            }
+            Self::Prim(Primitive::Rest(_)) => self,
+            Self::Sequential(m1, m2) => {
+                m1.realize_ornament(ornament, key, transpose, whole_note)
+                    + m2.realize_ornament(ornament, key, transpose, whole_note)
+            }
+            Self::Lazy(it) => Self::lazy_line(
+                it.map(move |m| m.realize_ornament(ornament, key, transpose, whole_note)),
+            ),
+            Self::Parallel(m1, m2) => {
+                m1.realize_ornament(ornament, key, transpose, whole_note)
+                    | m2.realize_ornament(ornament, key, transpose, whole_note)
+            }
+            Self::Modify(c, m) => {
+                let (key, transpose, whole_note) = Self::in_control(&c, key, transpose, whole_note);
+                m.realize_ornament(ornament, key, transpose, whole_note)
+                    .with(c)
+            }
+        }
+    }
+
+    /// The key, the transposition and the duration of the whole note
+    /// under the given [`Control`].
+    fn in_control(
+        control: &Control<Pitch>,
+        key: KeySig,
+        transpose: Interval,
+        whole_note: Duration,
+    ) -> (KeySig, Interval, Duration) {
+        match control {
+            Control::Tempo(r) => (key, transpose, whole_note / *r),
+            Control::Transpose(i) => (key, transpose + *i, whole_note),
+            Control::KeySig(k) => (*k, transpose, whole_note),
+            _ => (key, transpose, whole_note),
+        }
+    }
+
+    /// Decorate every note of the [`Music`] with the given [`Ornament`]
+    /// with the probability `prob` to make the performance less mechanical.
+    ///
+    /// The choice is pseudo-random but deterministic
+    /// for the given `seed`, so the result is reproducible.
+    /// The [rests][Primitive::Rest] are never ornamented.
+    pub fn ornament_randomly(self, prob: f64, seed: u64, ornament: Ornament) -> Self {
+        self.map_notes_randomly(&mut SplitMix64::new(seed), move |rng, n| {
+            if rng.fraction() < prob {
+                n.with_phrase(vec![PhraseAttribute::Orn(ornament)])
+            } else {
+                n
+            }
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::prim::{interval::Octave, pitch::Pitch};
+    use crate::{
+        music::{
+            perf::{Context, FancyPlayer, Performable},
+            AttrNote,
+        },
+        prim::{interval::Octave, pitch::PitchClass},
+    };
 
     use super::*;
 
@@ -151,4 +344,215 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn trill_performs_alternating_pitches() {
+        let oc4 = Octave::OneLined;
+        let m = Music::C(oc4, Dur::HALF)
+            .trill(Interval::tone(), TrillOptions::Count(4))
+            .unwrap();
+        let pitches: Vec<_> = m.perform().iter().map(|e| e.pitch).collect();
+
+        let (c, d) = (Pitch::C(oc4).abs(), Pitch::D(oc4).abs());
+        assert_eq!(pitches, [c, d, c, d]);
+    }
+
+    #[test]
+    fn mordent() {
+        let oc4 = Octave::OneLined;
+        let m = Music::E(oc4, Dur::WHOLE).with_tempo(2);
+
+        assert_eq!(
+            m.mordent(false).unwrap(),
+            Music::line(vec![
+                Music::E(oc4, Dur::EIGHTH),
+                Music::D(oc4, Dur::EIGHTH),
+                Music::E(oc4, Dur::QUARTER),
+                Music::E(oc4, Dur::HALF),
+            ])
+            .with_tempo(2)
+        );
+        assert!(Music::rest(Dur::WHOLE).mordent(true).is_err());
+    }
+
+    #[test]
+    fn arpeggio_up() {
+        let oc4 = Octave::OneLined;
+        let chord = Music::chord(vec![
+            Music::G(oc4, Dur::DOTTED_HALF),
+            Music::C(oc4, Dur::DOTTED_HALF),
+            Music::E(oc4, Dur::DOTTED_HALF),
+        ]);
+        let m = chord.arpeggio(true).unwrap();
+
+        let ctx = Context::with_default_player::<FancyPlayer>();
+        let events: Vec<_> = Performable::<AttrNote>::perform_with_context(m, ctx)
+            .iter()
+            .collect();
+        let pitches: Vec<_> = events.iter().map(|e| e.pitch).collect();
+        assert_eq!(
+            pitches,
+            [Pitch::C(oc4), Pitch::E(oc4), Pitch::G(oc4)].map(Pitch::abs)
+        );
+        assert!(events.windows(2).all(|w| w[0].start_time < w[1].start_time));
+
+        assert!(Music::C(oc4, Dur::HALF).arpeggio(true).is_err());
+    }
+
+    #[test]
+    fn arpeggio_of_two_notes() {
+        let oc4 = Octave::OneLined;
+        let interval = Music::chord(vec![Music::C(oc4, Dur::HALF), Music::G(oc4, Dur::HALF)]);
+
+        let perform = |up| {
+            let m = interval.clone().arpeggio(up).unwrap();
+            let ctx = Context::with_default_player::<FancyPlayer>();
+            Performable::<AttrNote>::perform_with_context(m, ctx)
+                .iter()
+                .map(|e| (e.start_time, e.pitch, e.duration))
+                .collect::<Vec<_>>()
+        };
+
+        // the half note lasts 1 second, so every note of the broken interval lasts 1/2
+        let (c, g) = (Pitch::C(oc4).abs(), Pitch::G(oc4).abs());
+        let half = Ratio::new(1, 2);
+        assert_eq!(
+            perform(true),
+            [(Ratio::from_integer(0), c, half), (half, g, half)]
+        );
+        assert_eq!(
+            perform(false),
+            [(Ratio::from_integer(0), g, half), (half, c, half)]
+        );
+    }
+
+    #[test]
+    fn expand_trill_into_notes() {
+        let oc4 = Octave::OneLined;
+        let trill = PhraseAttribute::Orn(Ornament::Trill(TrillOptions::Count(4)));
+        let m = Music::C(oc4, Dur::HALF).with_phrase(vec![trill]);
+
+        assert_eq!(
+            m.expand_ornaments(),
+            Music::line(vec![
+                Music::C(oc4, Dur::EIGHTH),
+                Music::D(oc4, Dur::EIGHTH),
+                Music::C(oc4, Dur::EIGHTH),
+                Music::D(oc4, Dur::EIGHTH),
+            ])
+        );
+    }
+
+    #[test]
+    fn expand_trill_of_given_duration() {
+        let oc4 = Octave::OneLined;
+        // the sixteenth lasts 1/8 seconds in the default tempo
+        let trill = PhraseAttribute::Orn(Ornament::Trill(Ratio::new(1, 8).into()));
+        let m = Music::E(oc4, Dur::QUARTER).with_phrase(vec![trill]);
+
+        let expanded = Vec::from(m.expand_ornaments());
+        assert_eq!(
+            expanded,
+            [
+                Music::E(oc4, Dur::SIXTEENTH),
+                Music::F(oc4, Dur::SIXTEENTH),
+                Music::E(oc4, Dur::SIXTEENTH),
+                Music::F(oc4, Dur::SIXTEENTH),
+            ]
+        );
+    }
+
+    #[test]
+    fn expanded_ornaments_perform_as_fancy_player() {
+        let oc4 = Octave::OneLined;
+        let orn = |o| vec![PhraseAttribute::Orn(o)];
+        let m = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::E(oc4, Dur::QUARTER).with_phrase(orn(Ornament::Turn)),
+            Music::G(oc4, Dur::HALF).with_phrase(orn(Ornament::Mordent)),
+            Music::B(oc4, Dur::HALF)
+                .with_phrase(orn(Ornament::InvMordent))
+                .with_transpose(Interval::from(-2))
+                .with_key_sig(KeySig::Major(PitchClass::G)),
+        ]);
+
+        let fancy = || Context::with_default_player::<FancyPlayer>();
+        let ornamented: Vec<_> = Performable::<AttrNote>::perform_with_context(m.clone(), fancy())
+            .iter()
+            .collect();
+        assert_eq!(ornamented.len(), 1 + 4 + 3 + 3);
+
+        let expanded = m.expand_ornaments();
+        let plain: Vec<_> = expanded.clone().perform().iter().collect();
+        assert_eq!(plain, ornamented);
+
+        // no more ornaments to interpret
+        let fancy_expanded: Vec<_> =
+            Performable::<AttrNote>::perform_with_context(expanded, fancy())
+                .iter()
+                .collect();
+        assert_eq!(fancy_expanded, ornamented);
+    }
+
+    #[test]
+    fn non_melodic_ornaments_are_kept() {
+        let oc4 = Octave::OneLined;
+        let chord = Music::chord(vec![Music::C(oc4, Dur::HALF), Music::E(oc4, Dur::HALF)]);
+        let arpeggio = chord.arpeggio(true).unwrap();
+        assert_eq!(arpeggio.clone().expand_ornaments(), arpeggio);
+    }
+
+    fn ornamented_count(m: &Music) -> usize {
+        m.fold_by_ref(
+            |_| 0,
+            |a, b| a + b,
+            (0, |a, b| a + b),
+            |a, b| a + b,
+            |c, a| {
+                if matches!(c, Control::Phrase(_)) {
+                    a + 1
+                } else {
+                    a
+                }
+            },
+        )
+    }
+
+    #[test]
+    fn ornament_randomly() {
+        let oc4 = Octave::OneLined;
+        let m = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::rest(Dur::QUARTER),
+            Music::D(oc4, Dur::QUARTER),
+            Music::E(oc4, Dur::QUARTER) | Music::G(oc4, Dur::QUARTER),
+            Music::F(oc4, Dur::QUARTER),
+        ]);
+
+        let none = m.clone().ornament_randomly(0.0, 42, Ornament::Mordent);
+        assert_eq!(none, m);
+
+        let all = m.clone().ornament_randomly(1.0, 42, Ornament::Mordent);
+        assert_eq!(ornamented_count(&all), 5);
+
+        let some = m.clone().ornament_randomly(0.5, 7, Ornament::Turn);
+        assert_eq!(some, m.ornament_randomly(0.5, 7, Ornament::Turn));
+    }
+
+    #[test]
+    fn ornament_randomly_lazy_line() {
+        let oc4 = Octave::OneLined;
+        let m = Music::lazy_line(iter::repeat(Music::C(oc4, Dur::EIGHTH)));
+
+        let ornamented = |seed| {
+            m.clone()
+                .ornament_randomly(0.5, seed, Ornament::Mordent)
+                .take(Dur::from(4))
+                .remove_zeros()
+        };
+        let count = ornamented_count(&ornamented(1));
+        // 32 notes in total
+        assert!(count > 0 && count < 32);
+        assert_eq!(Vec::from(ornamented(1)), Vec::from(ornamented(1)));
+    }
 }
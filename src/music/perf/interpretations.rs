@@ -2,13 +2,16 @@
 use std::iter;
 
 use intertrait::{cast_to, castable_to};
-use itertools::Itertools as _;
+use itertools::{Either, Itertools as _};
 use num_rational::Ratio;
 use num_traits::{ops::checked::CheckedSub as _, One as _, Zero as _};
 
 use crate::{
     music::{
-        phrase::{Articulation, Dynamic, Ornament, PhraseAttribute, Tempo, TrillOptions},
+        phrase::{
+            Articulation, Dynamic, Ornament, PhraseAttribute, Tempo, TempoCurve, TrillOptions,
+            Vibrato,
+        },
         Music, NoteAttribute,
     },
     prim::{
@@ -17,10 +20,10 @@ use crate::{
         scale::KeySig,
         volume::Volume,
     },
-    utils::{CloneableIterator, Measure},
+    utils::Measure,
 };
 
-use super::{player::Player, Context, Duration, Event, Performance, TimePoint};
+use super::{player::Player, Context, Duration, Event, Item, Performance, TimePoint};
 
 /// Annotate [`Event`] with attributes.
 pub trait EventAnnotator<P, A> {
@@ -85,6 +88,9 @@ fn default_event_from_note<P>(note: (Dur, Pitch), ctx: Context<'_, P>) -> Event
         volume,
         key: _ignore_key,
         depth: _ignore_depth,
+        max_depth: _ignore_max_depth,
+        release_tail: _ignore_release_tail,
+        trace: _ignore_trace,
     } = ctx;
     Event {
         start_time,
@@ -177,6 +183,19 @@ pub struct FancyPlayer {
 
 // TODO: more impls for `FancyPlayer`
 
+castable_to!(FancyPlayer => Player<(Pitch, Vec<NoteAttribute>)>);
+
+impl<P> EventAnnotator<P, NoteAttribute> for FancyPlayer {
+    fn modify_event_with_attr(
+        &self,
+        event: Event,
+        attr: &NoteAttribute,
+        ctx: &Context<'_, (P, Vec<NoteAttribute>)>,
+    ) -> Event {
+        self.inner.modify_event_with_attr(event, attr, ctx)
+    }
+}
+
 impl<A> Player<(Pitch, Vec<A>)> for FancyPlayer
 where
     Self: EventAnnotator<Pitch, A>,
@@ -203,6 +222,7 @@ where
         mut ctx: Context<'_, (Pitch, Vec<A>)>,
     ) -> (Performance, Measure<Duration>) {
         let key = ctx.key;
+        let whole_note = ctx.whole_note;
 
         let last_volume_phrase = attrs.iter().fold(None, |found, pa| match pa {
             // ignore the previous volume if found new one
@@ -230,6 +250,33 @@ where
             return (perf, dur);
         };
 
+        // the gradual change of the volume limited by the duration `over`
+        // at the start of the phrase (`sign=true`) or at its end (`sign=false`)
+        let fade = move |event: Event, over: Dur, sign: bool| {
+            let fade_len = over.into_ratio() * whole_note;
+            let dt = event.start_time - t0;
+            if sign {
+                // mirroring the fade-out, the progress is measured at the end of the note,
+                // so the first note is quiet, but still audible
+                let end = dt + event.duration;
+                if end >= fade_len {
+                    return event;
+                }
+                inflate(event, Ratio::one() - end / fade_len, false)
+            } else {
+                let Measure::Finite(dur) = dur else {
+                    return event;
+                };
+                let fade_len = fade_len.min(dur);
+                match dt.checked_sub(&(dur - fade_len)) {
+                    Some(progress) if !fade_len.is_zero() => {
+                        inflate(event, progress / fade_len, false)
+                    }
+                    _ => event,
+                }
+            }
+        };
+
         let inflate = move |event: Event, coef: Ratio<u32>, sign: bool| {
             let coef_event = match dur {
                 Measure::Finite(dur) => {
@@ -239,58 +286,61 @@ where
                 }
                 Measure::Infinite => Ratio::from_integer(0),
             };
-            let shift = if sign {
-                Ratio::one() + coef_event
-            } else {
-                // for `sign=false`, the `coef` should belong
-                // to the range `[0 (no changes)..1 (fade out to zero)]`
-                Ratio::one().checked_sub(&coef_event).unwrap_or_default()
-            };
-
-            let new_volume = Ratio::from(u32::from(event.volume.0)) * shift;
-            Event {
-                volume: Volume::from(u8::try_from(new_volume.to_integer()).unwrap_or(u8::MAX)),
-                ..event
-            }
+            inflate(event, coef_event, sign)
         };
 
-        let stretch = move |event: Event, coef: Ratio<u32>, sign: bool| {
-            let r = match dur {
-                Measure::Finite(dur) => coef / dur,
-                Measure::Infinite => Ratio::from_integer(0),
+        let stretch_time = move |t: TimePoint, coef: Ratio<u32>, curve: TempoCurve, sign: bool| {
+            let dur = match dur {
+                Measure::Finite(dur) if !dur.is_zero() => dur,
+                _ => return t,
+            };
+            let Some(dt) = t.checked_sub(&t0) else {
+                return t;
             };
 
-            let dt = event.start_time - t0;
-            let time_coef_event = dt * r;
-            let dur_coef_event = (Ratio::from(2) * dt + event.duration) * r;
-
-            let (time_shift, dur_shift) = if sign {
-                (
-                    Ratio::one() + time_coef_event,
-                    Ratio::one() + dur_coef_event,
-                )
-            } else {
-                (
+            // the time shift of the point `t` since the start of the phrase
+            // for the `curve` applied to the ratio `t / dur`
+            let shift = move |t: Duration| coef * dur * curve.apply(t / dur);
+            let position = move |t: Duration| {
+                if sign {
+                    t + shift(t)
+                } else {
                     // for `sign=false`, the `coef` should belong
                     // to the range `[0 (no changes)..1 (shrink to point)]`
-                    Ratio::one()
-                        .checked_sub(&time_coef_event)
-                        .unwrap_or_default(),
-                    // for `sign=false`, the `coef` should belong
-                    // to the range `[0 (no changes)..0.5 (shrink to point)]`
-                    Ratio::one()
-                        .checked_sub(&dur_coef_event)
-                        .unwrap_or_default(),
-                )
+                    t.checked_sub(&shift(t)).unwrap_or_default()
+                }
             };
 
+            position(dt) + t0
+        };
+
+        let stretch = move |event: Event, coef: Ratio<u32>, curve: TempoCurve, sign: bool| {
+            let start = stretch_time(event.start_time, coef, curve, sign);
+            let end = stretch_time(event.start_time + event.duration, coef, curve, sign);
             Event {
-                start_time: time_shift * dt + t0,
-                duration: dur_shift * event.duration,
+                start_time: start,
+                duration: end.checked_sub(&start).unwrap_or_default(),
                 ..event
             }
         };
 
+        let change_tempo = move |perf: Performance,
+                                 dur: Measure<Duration>,
+                                 coef: Ratio<u32>,
+                                 curve: TempoCurve,
+                                 sign: bool| {
+            let perf = perf.map_timed(
+                move |e| stretch(e, coef, curve, sign),
+                move |t| stretch_time(t, coef, curve, sign),
+            );
+            let dur = if sign {
+                dur * (Ratio::one() + coef)
+            } else {
+                dur * Ratio::one().checked_sub(&coef).unwrap_or_default()
+            };
+            (perf, dur)
+        };
+
         attrs
             .iter()
             .fold((perf, dur), move |(perf, dur), attr| match *attr {
@@ -302,43 +352,34 @@ where
                     let perf = perf.map(move |e| inflate(e, x, false));
                     (perf, dur)
                 }
-                PhraseAttribute::Tmp(Tempo::Ritardando(x)) => {
-                    let perf = perf.map(move |e| stretch(e, x, true));
-                    let dur = dur * (Ratio::one() + x);
+                PhraseAttribute::Dyn(Dynamic::FadeIn(over)) => {
+                    let perf = perf.map(move |e| fade(e, over, true));
                     (perf, dur)
                 }
-                PhraseAttribute::Tmp(Tempo::Accelerando(x)) => {
-                    let perf = perf.map(move |e| stretch(e, x, false));
-                    let dur = dur * Ratio::one().checked_sub(&x).unwrap_or_default();
+                PhraseAttribute::Dyn(Dynamic::FadeOut(over)) => {
+                    let perf = perf.map(move |e| fade(e, over, false));
                     (perf, dur)
                 }
-                PhraseAttribute::Orn(Ornament::Trill(opts)) => {
-                    // exercise 8.2.1
-                    let events = perf.iter().flat_map(move |e| trill(e, opts, key));
-                    (Performance::with_events(events), dur)
+                PhraseAttribute::Tmp(Tempo::Ritardando(x)) => {
+                    change_tempo(perf, dur, x, TempoCurve::Linear, true)
                 }
-                PhraseAttribute::Orn(Ornament::Mordent) => {
-                    // exercise 8.2.2
-                    let events = perf.iter().flat_map(move |e| mordent(e, true, false, key));
-                    (Performance::with_events(events), dur)
+                PhraseAttribute::Tmp(Tempo::RitardandoCurve(x, curve)) => {
+                    change_tempo(perf, dur, x, curve, true)
                 }
-                PhraseAttribute::Orn(Ornament::InvMordent) => {
-                    // exercise 8.2.3
-                    let events = perf.iter().flat_map(move |e| mordent(e, false, false, key));
-                    (Performance::with_events(events), dur)
+                PhraseAttribute::Tmp(Tempo::Accelerando(x)) => {
+                    change_tempo(perf, dur, x, TempoCurve::Linear, false)
                 }
-                PhraseAttribute::Orn(Ornament::DoubleMordent) => {
-                    // exercise 8.2.4
-                    let events = perf.iter().flat_map(move |e| mordent(e, true, true, key));
-                    (Performance::with_events(events), dur)
+                PhraseAttribute::Tmp(Tempo::AccelerandoCurve(x, curve)) => {
+                    change_tempo(perf, dur, x, curve, false)
                 }
-                PhraseAttribute::Orn(Ornament::DiatonicTrans(i)) => {
-                    // exercise 8.5
-                    let perf = perf.map(move |e| Event {
-                        pitch: e.pitch.diatonic_trans(key, i),
-                        ..e
+                PhraseAttribute::Orn(ornament) if is_melodic(&ornament) => {
+                    let items = perf.items().flat_map(move |item| match item {
+                        Item::Note(e, vibrato) => Either::Left(
+                            ornamented(e, ornament, key).map(move |e| Item::Note(e, vibrato)),
+                        ),
+                        mark @ Item::KeySig(..) => Either::Right(iter::once(mark)),
                     });
-                    (perf, dur)
+                    (Performance::with_items(items), dur)
                 }
                 _ => (perf, dur),
             })
@@ -405,72 +446,404 @@ where
                 }
             }
             PhraseAttribute::Orn(Ornament::ArpeggioUp) => {
-                Performance::with_events(arpeggio(perf.iter(), true).into_iter())
+                Performance::with_items(arpeggio(perf.items(), true).into_iter())
             }
             PhraseAttribute::Orn(Ornament::ArpeggioDown) => {
-                Performance::with_events(arpeggio(perf.iter(), false).into_iter())
+                Performance::with_items(arpeggio(perf.items(), false).into_iter())
+            }
+            PhraseAttribute::Orn(Ornament::Vibrato { rate, depth_cents }) => {
+                perf.with_vibrato(Vibrato { rate, depth_cents })
             }
             PhraseAttribute::Art(_) | PhraseAttribute::Orn(_) => perf,
         }
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// [`FancyPlayer`] adding the human-like timing deviations
+/// to every phrase it interprets.
+///
+/// The whole [`Music`] it plays is shaped as a single phrase,
+/// even without any explicit [phrase marks][Music::with_phrase],
+/// while every marked phrase inside it is additionally shaped on its own.
+///
+/// The tempo is gradually pushed forward towards the melodic peak
+/// (the highest note) of the phrase and broadens on the final notes
+/// (the cadence), while the phrase's notes keep their order.
+///
+/// See more: <https://en.wikipedia.org/wiki/Tempo_rubato>
+pub struct RubatoPlayer {
+    inner: FancyPlayer,
+    depth: Ratio<u32>,
+}
+
+impl Default for RubatoPlayer {
+    fn default() -> Self {
+        Self::new(Ratio::new(1, 10))
+    }
+}
+
+impl RubatoPlayer {
+    /// How many final onsets of the phrase are slowed down.
+    const CADENCE_LEN: usize = 2;
+
+    /// Create a player with the given maximum relative deviation
+    /// of the inter-onset times.
+    ///
+    /// # Panics
+    /// If the `depth` is not less than 1.
+    pub fn new(depth: Ratio<u32>) -> Self {
+        assert!(
+            depth < Ratio::one(),
+            "The rubato depth should be less than 1"
+        );
+        Self {
+            inner: FancyPlayer::default(),
+            depth,
+        }
+    }
+
+    /// Scale of every inter-onset segment, given the number of segments
+    /// and the index of the segment starting at the melodic peak.
+    fn segment_scales(self, segments: usize, peak: usize) -> Vec<Ratio<u32>> {
+        let cadence_start = segments.saturating_sub(Self::CADENCE_LEN);
+        (0..segments)
+            .map(|i| {
+                if i >= cadence_start {
+                    let progress = Ratio::new(
+                        u32::try_from(i - cadence_start + 1).unwrap_or(u32::MAX),
+                        u32::try_from(segments - cadence_start).unwrap_or(u32::MAX),
+                    );
+                    Ratio::one() + self.depth * 2 * progress
+                } else if i < peak {
+                    let progress = Ratio::new(
+                        u32::try_from(i + 1).unwrap_or(u32::MAX),
+                        u32::try_from(peak).unwrap_or(u32::MAX),
+                    );
+                    Ratio::one() - self.depth * progress
+                } else {
+                    Ratio::one()
+                }
+            })
+            .collect()
+    }
+
+    /// Warp the timing of the phrase's events starting at `t0`
+    /// and lasting for `dur`, returning the new duration.
+    fn reshape(self, perf: &Performance, t0: TimePoint, dur: Duration) -> (Performance, Duration) {
+        let items: Vec<_> = perf.items().collect();
+        let events: Vec<_> = items.iter().cloned().filter_map(Item::into_event).collect();
+        let onsets: Vec<_> = events.iter().map(|e| e.start_time).dedup().collect();
+        if onsets.len() < 2 {
+            return (Performance::with_items(items.into_iter()), dur);
+        }
+
+        let peak = events
+            .iter()
+            .rev()
+            .max_by_key(|e| e.pitch)
+            .and_then(|top| onsets.iter().position(|&t| t == top.start_time))
+            .unwrap_or_default();
+        let scales = self.segment_scales(onsets.len(), peak);
+
+        // the warped position of every onset relative to `t0`
+        let warped_onsets: Vec<_> = iter::once(onsets[0] - t0)
+            .chain(onsets.iter().tuple_windows().zip(&scales).scan(
+                onsets[0] - t0,
+                |acc, ((start, next), scale)| {
+                    *acc += (next - start) * scale;
+                    Some(*acc)
+                },
+            ))
+            .collect();
+
+        let warp = move |t: TimePoint| {
+            let i = onsets
+                .partition_point(|&onset| onset <= t)
+                .saturating_sub(1);
+            warped_onsets[i] + (t.checked_sub(&onsets[i]).unwrap_or_default()) * scales[i]
+        };
+
+        let new_dur = warp(t0 + dur);
+        let items = items.into_iter().map(move |item| match item {
+            Item::Note(e, vibrato) => {
+                let start = warp(e.start_time);
+                let end = warp(e.start_time + e.duration);
+                let e = Event {
+                    start_time: t0 + start,
+                    duration: end - start,
+                    ..e
+                };
+                Item::Note(e, vibrato)
+            }
+            Item::KeySig(t, key) => Item::KeySig(t0 + warp(t), key),
+        });
+        (
+            Performance::with_items(items.collect::<Vec<_>>().into_iter()),
+            new_dur,
+        )
+    }
+
+    /// Warp the timing of the finite part of the [`Performance`]
+    /// (see [`Self::reshape`]), leaving the infinite one as is.
+    fn reshape_finite(
+        self,
+        perf: Performance,
+        start: Measure<TimePoint>,
+        dur: Measure<Duration>,
+    ) -> (Performance, Measure<Duration>) {
+        match (start, dur) {
+            (Measure::Finite(t0), Measure::Finite(dur)) => {
+                let (perf, dur) = self.reshape(&perf, t0, dur);
+                (perf, Measure::Finite(dur))
+            }
+            _ => (perf, dur),
+        }
+    }
+}
+
+castable_to!(RubatoPlayer => Player<(Pitch, Vec<NoteAttribute>)>);
+
+impl<P> EventAnnotator<P, NoteAttribute> for RubatoPlayer {
+    fn modify_event_with_attr(
+        &self,
+        event: Event,
+        attr: &NoteAttribute,
+        ctx: &Context<'_, (P, Vec<NoteAttribute>)>,
+    ) -> Event {
+        self.inner.modify_event_with_attr(event, attr, ctx)
+    }
+}
+
+impl<A> Player<(Pitch, Vec<A>)> for RubatoPlayer
+where
+    FancyPlayer: Player<(Pitch, Vec<A>)>,
+{
+    fn name(&self) -> &'static str {
+        "Rubato"
+    }
+
+    fn play_note(
+        &self,
+        note: (Dur, &(Pitch, Vec<A>)),
+        ctx: Context<'_, (Pitch, Vec<A>)>,
+    ) -> Performance {
+        self.inner.play_note(note, ctx)
+    }
+
+    fn interpret_music(
+        &self,
+        music: &Music<(Pitch, Vec<A>)>,
+        ctx: Context<'_, (Pitch, Vec<A>)>,
+    ) -> (Performance, Measure<Duration>) {
+        let start = ctx.start_time;
+        let (perf, dur) = music.perf(ctx);
+        self.reshape_finite(perf, start, dur)
+    }
+
+    fn interpret_phrases(
+        &self,
+        music: &Music<(Pitch, Vec<A>)>,
+        attrs: &[PhraseAttribute],
+        ctx: Context<'_, (Pitch, Vec<A>)>,
+    ) -> (Performance, Measure<Duration>) {
+        let start = ctx.start_time;
+        let (perf, dur) = self.inner.interpret_phrases(music, attrs, ctx);
+        self.reshape_finite(perf, start, dur)
+    }
+
+    fn interpret_phrase(&self, perf: Performance, attr: &PhraseAttribute) -> Performance {
+        Player::<(Pitch, Vec<A>)>::interpret_phrase(&self.inner, perf, attr)
+    }
+}
+
+/// Change the volume of the [`Event`] by the given coefficient:
+/// - increase by `coef` (`1 + coef`) if `sign` is `true`;
+/// - decrease by `coef` (`1 - coef`) otherwise.
+pub(super) fn inflate(event: Event, coef: Ratio<u32>, sign: bool) -> Event {
+    let shift = if sign {
+        Ratio::one() + coef
+    } else {
+        // for `sign=false`, the `coef` should belong
+        // to the range `[0 (no changes)..1 (fade out to zero)]`
+        Ratio::one().checked_sub(&coef).unwrap_or_default()
+    };
+
+    let new_volume = Ratio::from(u32::from(event.volume.0)) * shift;
+    Event {
+        volume: Volume::from(u8::try_from(new_volume.to_integer()).unwrap_or(u8::MAX)),
+        ..event
+    }
+}
+
 impl Performance {
-    fn map<F>(self, f: F) -> Self
+    pub(super) fn map<F>(self, f: F) -> Self
     where
         F: FnMut(Event) -> Event + Clone + 'static,
     {
-        Self::with_events(self.iter().map(f))
+        self.map_timed(f, |t| t)
+    }
+
+    /// Transform every [`Event`] with the function `f`
+    /// and move the marks of the key signature changes in time
+    /// with the function `time` accordingly.
+    pub(super) fn map_timed<F, T>(self, mut f: F, time: T) -> Self
+    where
+        F: FnMut(Event) -> Event + Clone + 'static,
+        T: Fn(TimePoint) -> TimePoint + Clone + 'static,
+    {
+        Self::with_items(self.repr.map(move |item| match item {
+            Item::Note(e, vibrato) => Item::Note(f(e), vibrato),
+            Item::KeySig(t, key) => Item::KeySig(time(t), key),
+        }))
+    }
+
+    /// Make the pitch of every [`Event`] oscillate with the [`Vibrato`].
+    fn with_vibrato(self, vibrato: Vibrato) -> Self {
+        Self::with_items(self.repr.map(move |item| match item {
+            Item::Note(e, _) => Item::Note(e, Some(vibrato)),
+            mark @ Item::KeySig(..) => mark,
+        }))
     }
 }
 
-fn trill(
-    event: Event,
-    opts: TrillOptions<Ratio<u32>>,
+/// Whether the [`Ornament`] is a melodic figure decorating every single note
+/// and could be [realized][ornament_notes] as a sequence of notes.
+pub(in crate::music) const fn is_melodic(ornament: &Ornament) -> bool {
+    matches!(
+        ornament,
+        Ornament::Trill(_)
+            | Ornament::Mordent
+            | Ornament::InvMordent
+            | Ornament::DoubleMordent
+            | Ornament::Turn
+            | Ornament::DiatonicTrans(_)
+    )
+}
+
+/// Realize the [melodic][is_melodic] [`Ornament`] decorating
+/// the single note of the given duration and pitch in the given key
+/// as the sequence of notes (durations and pitches) played one after another.
+///
+/// The other ornaments leave the note as is.
+pub(in crate::music) fn ornament_notes(
+    ornament: Ornament,
+    duration: Duration,
+    pitch: AbsPitch,
     key: KeySig,
-) -> impl Iterator<Item = Event> + Clone {
-    let main_pitch = event.pitch;
-    let mut trill_pitch = main_pitch.diatonic_trans(key, 1);
-    if trill_pitch == main_pitch {
+) -> Vec<(Duration, AbsPitch)> {
+    match ornament {
+        // exercise 8.2.1
+        Ornament::Trill(opts) => alternate(
+            pitch,
+            neighbour(pitch, key, true),
+            trill_durations(duration, opts),
+        ),
+        // exercise 8.2.2
+        Ornament::Mordent => alternate(
+            pitch,
+            neighbour(pitch, key, true),
+            mordent_durations(duration, false),
+        ),
+        // exercise 8.2.3
+        Ornament::InvMordent => alternate(
+            pitch,
+            neighbour(pitch, key, false),
+            mordent_durations(duration, false),
+        ),
+        // exercise 8.2.4
+        Ornament::DoubleMordent => alternate(
+            pitch,
+            neighbour(pitch, key, true),
+            mordent_durations(duration, true),
+        ),
+        Ornament::Turn => {
+            let quarter = duration / 4;
+            vec![
+                (quarter, neighbour(pitch, key, true)),
+                (quarter, pitch),
+                (quarter, neighbour(pitch, key, false)),
+                (quarter, pitch),
+            ]
+        }
+        // exercise 8.5
+        Ornament::DiatonicTrans(i) => vec![(duration, pitch.diatonic_trans(key, i))],
+        _ => vec![(duration, pitch)],
+    }
+}
+
+/// The adjacent note of the scale above (`upper=true`) or below the given one.
+fn neighbour(pitch: AbsPitch, key: KeySig, upper: bool) -> AbsPitch {
+    let step = if upper { 1 } else { -1 };
+    let mut aux = pitch.diatonic_trans(key, step);
+    if aux == pitch {
         // pitch is out of defined key
-        trill_pitch = main_pitch.diatonic_trans(key, 2);
+        aux = pitch.diatonic_trans(key, 2 * step);
     }
-    assert!(trill_pitch > main_pitch);
+    assert!(if upper { aux > pitch } else { aux < pitch });
+    aux
+}
 
-    let d = event.duration;
-    let dur_seq: Box<dyn CloneableIterator<Item = Duration>> = match opts {
+fn trill_durations(d: Duration, opts: TrillOptions<Ratio<u32>>) -> Vec<Duration> {
+    match opts {
         TrillOptions::Duration(single) => {
             let n = (d / single).to_integer();
             let last_dur = d
                 .checked_sub(&(Ratio::from(n) * single))
                 .expect("Parts total duration should not be bigger than the whole");
 
-            Box::new(
-                iter::repeat(single)
-                    .take(n as usize)
-                    .chain((!last_dur.is_zero()).then_some(last_dur)),
-            )
+            iter::repeat(single)
+                .take(n as usize)
+                .chain((!last_dur.is_zero()).then_some(last_dur))
+                .collect()
         }
         TrillOptions::Count(n) => {
             let single = d / Ratio::from(u32::from(n));
-            Box::new(iter::repeat(single).take(usize::from(n)))
+            iter::repeat(single).take(usize::from(n)).collect()
         }
-    };
+    }
+}
 
-    alternate_pitch(event, trill_pitch, dur_seq)
+fn mordent_durations(d: Duration, double: bool) -> Vec<Duration> {
+    let mordent = d / 8;
+    if double {
+        iter::repeat(mordent)
+            .take(4)
+            .chain(Some(d * Ratio::new(1, 2)))
+            .collect()
+    } else {
+        iter::repeat(mordent)
+            .take(2)
+            .chain(Some(d * Ratio::new(3, 4)))
+            .collect()
+    }
 }
 
-fn alternate_pitch(
-    event: Event,
+fn alternate(
+    principal: AbsPitch,
     auxiliary: AbsPitch,
-    durations: impl Iterator<Item = Duration> + Clone,
-) -> impl Iterator<Item = Event> + Clone {
-    let principal = event.pitch;
+    durations: Vec<Duration>,
+) -> Vec<(Duration, AbsPitch)> {
     durations
+        .into_iter()
         .enumerate()
-        .scan(TimePoint::zero(), move |start, (i, duration)| {
+        .map(|(i, duration)| {
             // odd are alternate
             let pitch = if i % 2 == 1 { auxiliary } else { principal };
+            (duration, pitch)
+        })
+        .collect()
+}
+
+/// Replace the [`Event`] with the sequence of [`Event`]s realizing the [`Ornament`].
+fn ornamented(
+    event: Event,
+    ornament: Ornament,
+    key: KeySig,
+) -> impl Iterator<Item = Event> + Clone {
+    ornament_notes(ornament, event.duration, event.pitch, key)
+        .into_iter()
+        .scan(event.start_time, move |start, (duration, pitch)| {
             let prev_start = *start;
             *start += duration;
             Some(Event {
@@ -482,59 +855,31 @@ fn alternate_pitch(
         })
 }
 
-fn mordent(
-    event: Event,
-    upper: bool,
-    double: bool,
-    key: KeySig,
-) -> impl Iterator<Item = Event> + Clone {
-    let main_pitch = event.pitch;
-    let aux_pitch = if upper {
-        let mut pitch = main_pitch.diatonic_trans(key, 1);
-        if pitch == main_pitch {
-            // pitch is out of defined key
-            pitch = main_pitch.diatonic_trans(key, 2);
-        }
-        assert!(pitch > main_pitch);
-        pitch
-    } else {
-        let mut pitch = main_pitch.diatonic_trans(key, -1);
-        if pitch == main_pitch {
-            // pitch is out of defined key
-            pitch = main_pitch.diatonic_trans(key, -2);
-        }
-        assert!(pitch < main_pitch);
-        pitch
-    };
-
-    let d = event.duration;
-    let mordent = d / 8;
-    let dur_seq: Box<dyn CloneableIterator<Item = Duration>> = if double {
-        Box::new(
-            iter::repeat(mordent)
-                .take(4)
-                .chain(Some(d * Ratio::new(1, 2))),
-        )
-    } else {
-        Box::new(
-            iter::repeat(mordent)
-                .take(2)
-                .chain(Some(d * Ratio::new(3, 4))),
-        )
-    };
-    alternate_pitch(event, aux_pitch, dur_seq)
-}
-
-fn arpeggio(events: impl Iterator<Item = Event>, up: bool) -> Vec<Event> {
-    let chord_groups = events.group_by(|e| (e.start_time, e.duration));
+fn arpeggio(items: impl Iterator<Item = Item>, up: bool) -> Vec<Item> {
+    // every mark forms a group on its own
+    let chord_groups = items.group_by(|item| match item {
+        Item::Note(e, _) => Some((e.start_time, e.duration)),
+        Item::KeySig(..) => None,
+    });
     chord_groups
         .into_iter()
-        .flat_map(|(_, chord)| arpeggio_chord(chord.collect(), up))
+        .flat_map(|(_, group)| {
+            let (chord, marks): (Vec<_>, Vec<_>) = group.partition_map(|item| match item {
+                Item::Note(e, vibrato) => Either::Left((e, vibrato)),
+                mark @ Item::KeySig(..) => Either::Right(mark),
+            });
+            marks
+                .into_iter()
+                .chain(arpeggio_chord(chord, up).map(|(e, vibrato)| Item::Note(e, vibrato)))
+        })
         .collect()
 }
 
-fn arpeggio_chord(mut events: Vec<Event>, up: bool) -> Box<dyn Iterator<Item = Event>> {
-    let (s, d) = if let Some(first) = events.first() {
+fn arpeggio_chord(
+    mut events: Vec<(Event, Option<Vibrato>)>,
+    up: bool,
+) -> Box<dyn Iterator<Item = (Event, Option<Vibrato>)>> {
+    let (s, d) = if let Some((first, _)) = events.first() {
         (first.start_time, first.duration)
     } else {
         return Box::new(iter::empty());
@@ -542,12 +887,12 @@ fn arpeggio_chord(mut events: Vec<Event>, up: bool) -> Box<dyn Iterator<Item = E
 
     assert!(events
         .iter()
-        .all(|e| (e.start_time == s) && (e.duration == d)));
+        .all(|(e, _)| (e.start_time == s) && (e.duration == d)));
 
     if up {
-        events.sort_by_key(|e| e.pitch);
+        events.sort_by_key(|(e, _)| e.pitch);
     } else {
-        events.sort_by_key(|e| std::cmp::Reverse(e.pitch));
+        events.sort_by_key(|(e, _)| std::cmp::Reverse(e.pitch));
     }
 
     let size = u32::try_from(events.len()).expect("len is not low enough");
@@ -556,11 +901,20 @@ fn arpeggio_chord(mut events: Vec<Event>, up: bool) -> Box<dyn Iterator<Item = E
             if d.numer() % size == 0 {
                 // could split into equal intervals
                 let short_dur = d / size;
-                Box::new(events.into_iter().enumerate().map(move |(i, e)| Event {
-                    start_time: s + short_dur * u32::try_from(i).expect("i is not low enough"),
-                    duration: short_dur,
-                    ..e
-                }))
+                Box::new(
+                    events
+                        .into_iter()
+                        .enumerate()
+                        .map(move |(i, (e, vibrato))| {
+                            let e = Event {
+                                start_time: s + short_dur
+                                    * u32::try_from(i).expect("i is not low enough"),
+                                duration: short_dur,
+                                ..e
+                            };
+                            (e, vibrato)
+                        }),
+                )
             } else {
                 // split into 1/4 or 1/8 intervals, with the last note longer
                 let short_dur = if size <= 4 {
@@ -571,30 +925,45 @@ fn arpeggio_chord(mut events: Vec<Event>, up: bool) -> Box<dyn Iterator<Item = E
                 };
 
                 let equal_dur_notes = size - 1;
-                Box::new(events.into_iter().enumerate().map(move |(i, e)| {
-                    // the last is longer
-                    let i = u32::try_from(i).expect("i is not low enough");
-                    let duration = if i == equal_dur_notes {
-                        d - (short_dur * equal_dur_notes)
-                    } else {
-                        short_dur
-                    };
-
-                    Event {
-                        start_time: s + short_dur * i,
-                        duration,
-                        ..e
-                    }
-                }))
+                Box::new(
+                    events
+                        .into_iter()
+                        .enumerate()
+                        .map(move |(i, (e, vibrato))| {
+                            // the last is longer
+                            let i = u32::try_from(i).expect("i is not low enough");
+                            let duration = if i == equal_dur_notes {
+                                d - (short_dur * equal_dur_notes)
+                            } else {
+                                short_dur
+                            };
+
+                            let e = Event {
+                                start_time: s + short_dur * i,
+                                duration,
+                                ..e
+                            };
+                            (e, vibrato)
+                        }),
+                )
             }
         }
-        4 | 8 => {
+        2 | 4 | 8 => {
             let short_dur = d / size;
-            Box::new(events.into_iter().enumerate().map(move |(i, e)| Event {
-                start_time: s + short_dur * u32::try_from(i).expect("i is not low enough"),
-                duration: short_dur,
-                ..e
-            }))
+            Box::new(
+                events
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(i, (e, vibrato))| {
+                        let e = Event {
+                            start_time: s + short_dur
+                                * u32::try_from(i).expect("i is not low enough"),
+                            duration: short_dur,
+                            ..e
+                        };
+                        (e, vibrato)
+                    }),
+            )
         }
         _ => Box::new(events.into_iter()),
     }
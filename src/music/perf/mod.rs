@@ -1,27 +1,43 @@
 //! Defines abstract [`Performance`] which
 //! is a time-ordered sequence of musical [`Event`]s.
-use std::{borrow::Cow, iter, ops::Deref};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    fmt::Write as _,
+    iter,
+    ops::Deref,
+};
 
-use itertools::Itertools as _;
-use log::{debug, info};
+use itertools::{Either, Itertools as _};
+use log::{debug, error, info};
 use num_rational::Ratio;
+use num_traits::{CheckedSub as _, One as _, Zero as _};
 use ordered_float::OrderedFloat;
 
 use crate::{
     instruments::InstrumentName,
     midi::Instrument,
-    music::{AttrNote, MusicAttr},
-    prim::{duration::Dur, interval::Interval, pitch::AbsPitch, scale::KeySig, volume::Volume},
+    music::{AttrNote, MusicAttr, NoteAttribute},
+    prim::{
+        duration::Dur,
+        interval::Interval,
+        pitch::{AbsPitch, Pitch},
+        scale::KeySig,
+        time_sig::TimeSignature,
+        volume::Volume,
+    },
     utils::{to_static, CloneableIterator, LazyList, Measure},
 };
 
-use super::{control::Control, Music, Primitive};
+use super::{control::Control, phrase::Vibrato, Music, Primitive};
 
 pub use self::{
-    interpretations::{DefaultPlayer, EventAnnotator, FancyPlayer},
+    interpretations::{DefaultPlayer, EventAnnotator, FancyPlayer, RubatoPlayer},
     player::{DynPlayer, Player},
 };
 
+pub(super) use self::interpretations::{is_melodic, ornament_notes};
+
 mod interpretations;
 mod player;
 
@@ -29,7 +45,8 @@ mod player;
 /// [`Performance`] is a time-ordered sequence
 /// of musical [`events`][Event].
 pub struct Performance {
-    repr: LazyList<Event>,
+    repr: LazyList<Item>,
+    end_time: Option<TimePoint>,
 }
 
 impl Performance {
@@ -37,22 +54,497 @@ impl Performance {
     pub fn with_events<I>(events: I) -> Self
     where
         I: CloneableIterator<Item = Event> + 'static,
+    {
+        Self::with_items(LazyList(Box::new(events)).map(|e| Item::Note(e, None)))
+    }
+
+    pub(crate) fn with_items<I>(items: I) -> Self
+    where
+        I: CloneableIterator<Item = Item> + 'static,
     {
         Self {
-            repr: LazyList(Box::new(events)),
+            repr: LazyList(Box::new(items)),
+            end_time: None,
         }
     }
 
     /// Iterate over the [`Event`]s of the [`Performance`].
     pub fn iter(&self) -> LazyList<Event> {
+        LazyList(Box::new(self.repr.clone().filter_map(Item::into_event)))
+    }
+
+    /// Iterate over the [`Event`]s of the [`Performance`]
+    /// along with their [`Vibrato`]s and the marks of the key signature changes.
+    pub(crate) fn items(&self) -> LazyList<Item> {
         self.repr.clone()
     }
 
+    /// Mark the start of the [`Performance`] with its [key signature][KeySig]
+    /// and its end with the key to `restore` (if any).
+    fn in_key(
+        self,
+        key: KeySig,
+        start: Measure<TimePoint>,
+        restore: Option<(TimePoint, KeySig)>,
+    ) -> Self {
+        let Measure::Finite(start) = start else {
+            return self;
+        };
+        let restore = restore.map(|(end, outer_key)| Item::KeySig(end, outer_key));
+        Self::with_items(
+            iter::once(Item::KeySig(start, key))
+                .chain(self.repr)
+                .chain(restore),
+        )
+    }
+
+    /// Mark the explicit end of the [`Performance`] in seconds,
+    /// so the exported piece lasts exactly the given [`Duration`]
+    /// even if the last [`Event`] ends earlier (or there are no [`Event`]s at all).
+    ///
+    /// The marker is dropped by the transformations
+    /// producing the new [`Performance`], so set it right before the export.
+    pub fn with_total_duration(self, d: Duration) -> Self {
+        Self {
+            end_time: Some(d),
+            ..self
+        }
+    }
+
+    /// The explicit end of the [`Performance`]
+    /// set by the [`Self::with_total_duration`].
+    pub const fn end_time(&self) -> Option<TimePoint> {
+        self.end_time
+    }
+
     /// Checks whether the given performance is infinite
     /// by calling [`Iterator::size_hint`].
     pub fn is_probably_infinite(&self) -> bool {
         is_probably_infinite(&self.repr)
     }
+
+    /// Concatenate two [`Performance`]s overlapping
+    /// the end of the first one with the start of the second one.
+    ///
+    /// During the `overlap` the volumes of the `a`'s [`Event`]s
+    /// gradually fade out to zero, while the volumes
+    /// of the `b`'s [`Event`]s gradually fade in.
+    ///
+    /// The first [`Performance`] should be finite.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Fade_(audio_engineering)#Crossfading>
+    pub fn crossfade(a: Self, b: Self, overlap: Duration) -> Self {
+        let end_of_a = a
+            .iter()
+            .map(|e| e.start_time + e.duration)
+            .max()
+            .unwrap_or_default();
+        let fade_start = end_of_a.checked_sub(&overlap).unwrap_or_default();
+        let overlap = end_of_a - fade_start;
+
+        // how far is the given time point from the beginning of the overlap
+        let fade_progress = move |t: TimePoint| {
+            if overlap.is_zero() {
+                Ratio::one()
+            } else {
+                (t.checked_sub(&fade_start).unwrap_or_default() / overlap).min(Ratio::one())
+            }
+        };
+
+        let a = a.map(move |e| {
+            if e.start_time < fade_start {
+                e
+            } else {
+                let coef = fade_progress(e.start_time);
+                interpretations::inflate(e, coef, false)
+            }
+        });
+
+        let b = b.map_timed(
+            move |e| {
+                let e = Event {
+                    start_time: e.start_time + fade_start,
+                    ..e
+                };
+                let coef = Ratio::one() - fade_progress(e.start_time);
+                interpretations::inflate(e, coef, false)
+            },
+            move |t| t + fade_start,
+        );
+
+        Self::with_items(
+            a.items()
+                .merge_by(b.items(), |x, y| x.start_time() <= y.start_time()),
+        )
+    }
+
+    /// Shift all the [`Event`]s in time,
+    /// so the [`Performance`] starts exactly at zero.
+    ///
+    /// Could be useful for the excerpts of the [`Performance`]
+    /// (e.g. to export them into MIDI without the leading silence).
+    pub fn rebase(self) -> Self {
+        let Some(t0) = self.iter().next().map(|e| e.start_time) else {
+            return self;
+        };
+
+        let shift = move |t: TimePoint| t.checked_sub(&t0).unwrap_or_default();
+        self.map_timed(
+            move |e| Event {
+                start_time: shift(e.start_time),
+                ..e
+            },
+            shift,
+        )
+    }
+
+    /// Conform the [`Performance`] rendered with one tempo to another tempo
+    /// by rescaling the start times and durations of all the [`Event`]s.
+    ///
+    /// The tempo is defined by the duration of the whole note
+    /// (see [`Context::with_tempo`]).
+    ///
+    /// # Panics
+    /// If the `from_whole_note` is zero.
+    pub fn retime(self, from_whole_note: Duration, to_whole_note: Duration) -> Self {
+        assert!(
+            !from_whole_note.is_zero(),
+            "The duration of the whole note should be positive"
+        );
+        let ratio = to_whole_note / from_whole_note;
+        self.map_timed(
+            move |e| Event {
+                start_time: e.start_time * ratio,
+                duration: e.duration * ratio,
+                ..e
+            },
+            move |t| t * ratio,
+        )
+    }
+
+    /// Emulate the strumming of a guitar by delaying the starts
+    /// of the simultaneous [`Event`]s (e.g. the notes of a chord)
+    /// by increasing amounts of `spread` one after another:
+    /// from the lowest to the highest pitch if `up` is `true`,
+    /// and in the reverse order otherwise.
+    ///
+    /// Unlike the arpeggio, the durations of the notes stay the same,
+    /// so the `spread` should be small enough to keep the chord together.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Strum>
+    pub fn strum(self, spread: Duration, up: bool) -> Self {
+        let mut items = self.items().peekable();
+        let chords = iter::from_fn(move || {
+            let first = match items.next()? {
+                Item::Note(e, vibrato) => (e, vibrato),
+                mark @ Item::KeySig(..) => return Some(Either::Left(mark)),
+            };
+            let start_time = first.0.start_time;
+            let mut chord = vec![first];
+            while let Some(Item::Note(e, vibrato)) =
+                items.next_if(|item| matches!(item, Item::Note(e, _) if e.start_time == start_time))
+            {
+                chord.push((e, vibrato));
+            }
+            Some(Either::Right(chord))
+        });
+
+        Self::with_items(chords.flat_map(move |chord| {
+            let mut chord = match chord {
+                Either::Left(mark) => return Either::Left(iter::once(mark)),
+                Either::Right(chord) => chord,
+            };
+            if up {
+                chord.sort_by_key(|(e, _)| e.pitch);
+            } else {
+                chord.sort_by_key(|(e, _)| std::cmp::Reverse(e.pitch));
+            }
+            Either::Right(chord.into_iter().zip(0..).map(move |((e, vibrato), i)| {
+                let e = Event {
+                    start_time: e.start_time + spread * i,
+                    ..e
+                };
+                Item::Note(e, vibrato)
+            }))
+        }))
+    }
+
+    /// Connect the consecutive [`Event`]s of the same instrument
+    /// by extending each of them up to the start of the next one,
+    /// so the notes are played smoothly without the gaps.
+    ///
+    /// The `overlap` defines which fraction of the next [`Event`]'s duration
+    /// the extended note rings into it (zero means the notes just touch).
+    /// The [`Event`]s are never shortened,
+    /// and the last [`Event`] of every instrument is left as is.
+    ///
+    /// In contrast to the [`Legato`][super::phrase::Articulation::Legato]
+    /// articulation scaling the durations by the fixed factor,
+    /// the resulting durations depend on the distances between the notes.
+    ///
+    /// The [`Event`] is produced only after the next [`Event`]
+    /// of the same instrument (or the end of the [`Performance`]) is reached,
+    /// so the infinite [`Performance`] produces nothing after the last note
+    /// of any instrument which stops playing.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Legato>
+    pub fn connect_legato(self, overlap: Ratio<u32>) -> Self {
+        let mut items = self.items();
+        // the events waiting for their durations to be resolved (in the original order)
+        // along with the flag of the resolution
+        let mut queue: VecDeque<(Item, bool)> = VecDeque::new();
+        let mut popped = 0_usize;
+        // the start time and the indices in the queue (counting the popped ones)
+        // of the unresolved events of every instrument
+        let mut unresolved: BTreeMap<InstrumentName, (TimePoint, Vec<usize>)> = BTreeMap::new();
+
+        Self::with_items(iter::from_fn(move || loop {
+            if queue.front().is_some_and(|(_, resolved)| *resolved) {
+                popped += 1;
+                return queue.pop_front().map(|(item, _)| item);
+            }
+
+            let Some(next) = items.next() else {
+                // nothing to connect the remaining events with
+                return queue.pop_front().map(|(item, _)| item);
+            };
+            let (next, vibrato) = match next {
+                Item::Note(e, vibrato) => (e, vibrato),
+                mark @ Item::KeySig(..) => {
+                    // the marks wait only for the preceding events
+                    queue.push_back((mark, true));
+                    continue;
+                }
+            };
+
+            let index = popped + queue.len();
+            let (start, indices) = unresolved
+                .entry(next.instrument.clone())
+                .or_insert_with(|| (next.start_time, vec![]));
+            if *start != next.start_time {
+                let distance = next.start_time - *start;
+                for i in indices.drain(..) {
+                    let (item, resolved) = &mut queue[i - popped];
+                    if let Item::Note(e, _) = item {
+                        let connected = distance + overlap * next.duration;
+                        e.duration = e.duration.max(connected);
+                    }
+                    *resolved = true;
+                }
+                *start = next.start_time;
+            }
+            indices.push(index);
+            queue.push_back((Item::Note(next, vibrato), false));
+        }))
+    }
+
+    /// Scale the volume of every [`Event`] by the gain
+    /// defined for the moment of its start by the function `f`,
+    /// e.g. to make the arbitrary swells or the tremolo.
+    ///
+    /// The gain less than one makes the [`Event`] quieter
+    /// and the gain greater than one makes it louder
+    /// (but no louder than the [loudest][Volume::loudest] possible volume).
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Automation_(audio)>
+    pub fn automate_volume<F>(self, f: F) -> Self
+    where
+        F: Fn(TimePoint) -> Ratio<u32> + Clone + 'static,
+    {
+        self.map(move |e| {
+            let gain = f(e.start_time);
+            if gain >= Ratio::one() {
+                interpretations::inflate(e, gain - Ratio::one(), true)
+            } else {
+                interpretations::inflate(e, Ratio::one() - gain, false)
+            }
+        })
+    }
+
+    /// Scale the volumes of all the [`Event`]s of the finite [`Performance`]
+    /// so the loudest of them becomes exactly `target`,
+    /// preserving the ratios between the volumes.
+    ///
+    /// The silent [`Performance`] is returned unchanged.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Audio_normalization>
+    pub fn normalize_volume(self, target: Volume) -> Self {
+        let items: Vec<_> = self.items().collect();
+        let peak = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Note(e, _) => Some(u32::from(u8::from(e.volume.get_inner()))),
+                Item::KeySig(..) => None,
+            })
+            .max()
+            .unwrap_or_default();
+        let perf = Self::with_items(items.into_iter());
+        if peak == 0 {
+            return perf;
+        }
+
+        let gain = Ratio::new(u32::from(u8::from(target.get_inner())), peak);
+        perf.map(move |e| {
+            let volume = (Ratio::from(u32::from(u8::from(e.volume.get_inner()))) * gain).round();
+            Event {
+                volume: Volume::from(u8::try_from(volume.to_integer()).unwrap_or(u8::MAX)),
+                ..e
+            }
+        })
+    }
+
+    /// Remove the duplicated [`Event`]s, i.e. the ones starting at the same time
+    /// with the same pitch, instrument and duration, keeping only the first of them.
+    ///
+    /// Useful after layering the [parallel][Music::Parallel] voices
+    /// which share some notes, so that the MIDI note is not started twice.
+    pub fn dedup_simultaneous(self) -> Self {
+        let mut current_start = None;
+        let mut seen = Vec::new();
+        Self::with_items(self.items().filter(move |item| {
+            let Item::Note(e, _) = item else {
+                return true;
+            };
+            if current_start != Some(e.start_time) {
+                current_start = Some(e.start_time);
+                seen.clear();
+            }
+
+            let key = (e.pitch, e.instrument.clone(), e.duration);
+            if seen.contains(&key) {
+                false
+            } else {
+                seen.push(key);
+                true
+            }
+        }))
+    }
+
+    /// Transpose the pitches of all the [`Event`]s by the number of `degrees`
+    /// of the given diatonic [scale][KeySig] without re-performing the [`Music`]
+    /// (see [`Music::transpose_diatonic`]).
+    ///
+    /// The [percussion][InstrumentName::Percussion] [`Event`]s are kept intact,
+    /// since their pitches denote the specific sounds.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Transposition_(music)#Diatonic>
+    pub fn transpose_diatonic(self, key: KeySig, degrees: i8) -> Self {
+        self.map(move |e| {
+            if e.instrument == InstrumentName::Percussion {
+                return e;
+            }
+            Event {
+                pitch: e.pitch.diatonic_trans(key, degrees),
+                ..e
+            }
+        })
+    }
+
+    /// Describe the finite [`Performance`] as a text (one [`Event`] per line)
+    /// for debugging purposes.
+    ///
+    /// Every line has the form `t=<start> +<duration> <pitch> vel=<volume> instr=<instrument>`,
+    /// where the time is in seconds and the pitch is [spelled with sharps][Pitch::from].
+    pub fn to_text(&self) -> String {
+        self.iter().fold(String::new(), |mut text, e| {
+            let _ = writeln!(
+                text,
+                "t={} +{} {} vel={} instr={}",
+                e.start_time,
+                e.duration,
+                Pitch::from(e.pitch),
+                e.volume.get_inner(),
+                e.instrument,
+            );
+            text
+        })
+    }
+
+    /// Guess the duration of the beat of the finite [`Performance`]
+    /// (e.g. the imported one) as the most common time interval
+    /// between the consecutive onsets of its [`Event`]s.
+    ///
+    /// If several intervals are equally common, the shortest one is chosen.
+    /// The [`Performance`] with less than two distinct onsets has no beat.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Beat_(music)>
+    pub fn estimate_beat(&self) -> Option<Duration> {
+        let mut counts: BTreeMap<Duration, usize> = BTreeMap::new();
+        for (start, next) in self.iter().map(|e| e.start_time).dedup().tuple_windows() {
+            *counts.entry(next - start).or_default() += 1;
+        }
+        // `max_by_key` returns the last of the maximums,
+        // so iterate backwards to prefer the shortest interval
+        counts
+            .into_iter()
+            .rev()
+            .max_by_key(|&(_, count)| count)
+            .map(|(interval, _)| interval)
+    }
+
+    /// Split the finite [`Performance`] into the bars (measures)
+    /// of the given [`TimeSignature`] performed in the given tempo
+    /// (the duration of the whole note, see [`Context::with_tempo`]).
+    ///
+    /// Every [`Event`] belongs to the bar where it starts,
+    /// even if it lasts past the bar line.
+    /// The bars without any [`Event`]s started are left empty.
+    pub fn group_by_bar(&self, ts: &TimeSignature, whole_note: Duration) -> Vec<Vec<Event>> {
+        let bar_len = ts.measure_duration().into_ratio() * whole_note;
+        assert!(!bar_len.is_zero(), "The bar should have positive duration");
+
+        let mut bars: Vec<Vec<Event>> = vec![];
+        for e in self {
+            let index = usize::try_from((e.start_time / bar_len).to_integer())
+                .expect("Bar index fits into usize");
+            if bars.len() <= index {
+                bars.resize_with(index + 1, Vec::new);
+            }
+            bars[index].push(e);
+        }
+        bars
+    }
+
+    /// Whether multiple [`Event`]s (e.g. the notes of a chord)
+    /// start exactly at the given time point.
+    ///
+    /// Only the [`Event`]s up to the time point are inspected,
+    /// so it could be used for the infinite [`Performance`] as well.
+    pub fn is_simultaneous_at(&self, t: TimePoint) -> bool {
+        self.iter()
+            .take_while(|e| e.start_time <= t)
+            .filter(|e| e.start_time == t)
+            .nth(1)
+            .is_some()
+    }
+
+    /// The maximum number of [`Event`]s sounding at the same time
+    /// in the finite [`Performance`].
+    ///
+    /// The note ending exactly when another one starts
+    /// is not considered as sounding simultaneously with it.
+    pub fn max_polyphony(&self) -> usize {
+        let mut bounds: Vec<_> = self
+            .iter()
+            .filter(|e| !e.duration.is_zero())
+            .flat_map(|e| [(e.start_time, true), (e.start_time + e.duration, false)])
+            .collect();
+        // `false` (note-off) goes before `true` (note-on) for the same time
+        bounds.sort_unstable();
+
+        bounds
+            .into_iter()
+            .scan(0_usize, |sounding, (_, is_on)| {
+                if is_on {
+                    *sounding += 1;
+                } else {
+                    *sounding -= 1;
+                }
+                Some(*sounding)
+            })
+            .max()
+            .unwrap_or_default()
+    }
 }
 
 fn is_probably_infinite<T>(it: &impl Iterator<Item = T>) -> bool {
@@ -74,7 +566,7 @@ impl IntoIterator for Performance {
     type IntoIter = LazyList<Event>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.repr
+        self.iter()
     }
 }
 
@@ -99,9 +591,151 @@ where
     }
 
     fn perform_with_context(self, ctx: Context<'_, AttrNote>) -> Performance {
-        let (perf, dur) = MusicAttr::from(self).perf(ctx);
+        let start = ctx.start_time;
+        let key = ctx.key;
+        let (perf, dur) = ctx
+            .player
+            .clone()
+            .interpret_music(&MusicAttr::from(self), ctx);
         info!("Produced a performance of {:?} seconds long", dur);
-        perf
+        perf.in_key(key, start, None)
+    }
+}
+
+impl<P: 'static> Music<P>
+where
+    DefaultPlayer: Player<P>,
+{
+    /// Create a [`Performance`] using the default [`Context`]
+    /// without converting the [`Music`] into the [`MusicAttr`] first
+    /// (as the [`Performable`] does), so the [`DefaultPlayer`]
+    /// plays the notes directly, e.g. the [`Music<(Pitch, Volume)>`][Music].
+    ///
+    /// Could be used to avoid needless allocation of
+    /// the empty lists of [`NoteAttribute`]s for every note.
+    pub fn perform_raw(self) -> Performance {
+        self.perform_raw_with_context(Context::with_default_player::<DefaultPlayer>())
+    }
+
+    /// Create a [`Performance`] using the custom [`Context`]
+    /// without converting the [`Music`] into the [`MusicAttr`] first
+    /// (see [`Self::perform_raw`]).
+    pub fn perform_raw_with_context(self, ctx: Context<'_, P>) -> Performance {
+        let start = ctx.start_time;
+        let key = ctx.key;
+        let (perf, dur) = ctx.player.clone().interpret_music(&self, ctx);
+        info!("Produced a performance of {dur:?} seconds long");
+        perf.in_key(key, start, None)
+    }
+}
+
+impl<P: Clone> Music<P>
+where
+    MusicAttr: From<Self>,
+{
+    /// The maximum number of notes sounding at the same time.
+    ///
+    /// Could be used to check whether the [`Music`]
+    /// is playable on a monophonic instrument.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Polyphony_and_monophony_in_instruments>
+    pub fn max_polyphony(&self) -> usize {
+        self.clone().perform().max_polyphony()
+    }
+
+    /// Compare the [`Music`] values up to musical equivalence,
+    /// i.e. by their finite [`Performance`]s instead of the structure.
+    ///
+    /// E.g. `C4 + (D4 + E4)` and `(C4 + D4) + E4` are structurally different,
+    /// but they perform the same.
+    pub fn performs_same_as(&self, other: &Self) -> bool {
+        self.clone()
+            .perform()
+            .iter()
+            .eq(other.clone().perform().iter())
+    }
+}
+
+impl MusicAttr {
+    /// Approximate the finite [`Performance`] performed in the given tempo
+    /// (the duration of the whole note, see [`Context::with_tempo`])
+    /// with the score, i.e. do the reverse of the [performing][Performable::perform].
+    ///
+    /// The start times and durations of the [`Event`]s are quantized to the `grid`
+    /// (e.g. [sixteenth][Dur::SIXTEENTH]) and the [`Event`]s starting
+    /// at the same time are grouped into [chords][Music::chord].
+    /// Every instrument produces a [line][Music::line] of such chords
+    /// separated by the [rests][Music::rest] if needed.
+    ///
+    /// The conversion is lossy:
+    /// - the notes of a chord overlapping the next chord are cut short;
+    /// - the [vibrato][super::phrase::Ornament::Vibrato] is dropped;
+    /// - the [volume][NoteAttribute::Volume] and the [parameters][NoteAttribute::Params]
+    ///   are kept as the note attributes instead of the phrase annotations.
+    ///
+    /// # Panics
+    /// If the `whole_note` or the `grid` is zero.
+    pub fn from_performance(perf: &Performance, whole_note: Duration, grid: Dur) -> Self {
+        assert!(
+            !whole_note.is_zero(),
+            "The duration of the whole note should be positive"
+        );
+        assert!(grid > Dur::ZERO, "The quantization grid should be positive");
+
+        let grid_time = grid.into_ratio() * whole_note;
+        let to_units = |t: Duration| (t / grid_time).round().to_integer();
+
+        let mut by_instrument: BTreeMap<InstrumentName, BTreeMap<u32, Vec<Event>>> =
+            BTreeMap::new();
+        for e in perf {
+            by_instrument
+                .entry(e.instrument.clone())
+                .or_default()
+                .entry(to_units(e.start_time))
+                .or_default()
+                .push(e);
+        }
+
+        let voices = by_instrument
+            .into_iter()
+            .map(|(instrument, chords)| {
+                let mut line = vec![];
+                let mut now = 0;
+                let mut chords = chords.into_iter().peekable();
+                while let Some((start, mut events)) = chords.next() {
+                    if start > now {
+                        line.push(Self::rest(grid * (start - now)));
+                    }
+                    events.sort_by_key(|e| e.pitch);
+
+                    let next_start = chords.peek().map(|(next, _)| *next);
+                    let durations: Vec<_> = events
+                        .iter()
+                        .map(|e| {
+                            let units = to_units(e.duration).max(1);
+                            next_start.map_or(units, |next| units.min(next - start))
+                        })
+                        .collect();
+                    let longest = durations.iter().copied().max().unwrap_or_default();
+
+                    let notes = events
+                        .into_iter()
+                        .zip(durations)
+                        .map(|(e, units)| {
+                            let mut attrs = vec![NoteAttribute::Volume(e.volume)];
+                            if !e.params.is_empty() {
+                                attrs.push(NoteAttribute::Params(e.params));
+                            }
+                            Self::note(grid * units, (Pitch::from(e.pitch), attrs))
+                        })
+                        .collect();
+                    line.push(Self::chord(notes));
+                    now = start + longest;
+                }
+                Self::line(line).with_instrument(instrument)
+            })
+            .collect();
+        Self::chord(voices)
     }
 }
 
@@ -111,6 +745,16 @@ impl<P: 'static> Music<P> {
             depth: ctx.depth + 1,
             ..ctx
         };
+        if ctx.depth > ctx.max_depth {
+            error!(
+                "Skipping the performance of the too deeply nested Music (depth {} exceeds the limit {})",
+                ctx.depth, ctx.max_depth
+            );
+            return (Performance::with_events(iter::empty()), Measure::default());
+        }
+        if ctx.trace {
+            self.trace(&ctx);
+        }
         match self {
             Self::Prim(Primitive::Note(d, p)) => {
                 let dur = d.into_ratio() * ctx.whole_note;
@@ -130,6 +774,29 @@ impl<P: 'static> Music<P> {
         }
     }
 
+    fn trace(&self, ctx: &Context<'_, P>) {
+        debug!(target: TRACE_LOG_TARGET, "{}", self.trace_line(ctx));
+    }
+
+    /// The kind of the [`Music`] node along with its start time,
+    /// indented by the current nesting depth.
+    fn trace_line(&self, ctx: &Context<'_, P>) -> String {
+        let kind = match self {
+            Self::Prim(Primitive::Note(d, _)) => format!("Note {d:?}"),
+            Self::Prim(Primitive::Rest(d)) => format!("Rest {d:?}"),
+            Self::Sequential(..) => "Sequential".into(),
+            Self::Lazy(_) => "Lazy".into(),
+            Self::Parallel(..) => "Parallel".into(),
+            Self::Modify(..) => "Modify".into(),
+        };
+        format!(
+            "{:indent$}{kind} at {:?}",
+            "",
+            ctx.start_time,
+            indent = ctx.depth,
+        )
+    }
+
     fn perf_seq_pair(
         m1: &Self,
         m2: &Self,
@@ -142,6 +809,9 @@ impl<P: 'static> Music<P> {
                 start_time: ctx.start_time + d,
                 ..ctx
             };
+            if ctx.release_tail > Dur::ZERO {
+                p1 = Self::ring_into_rest(p1, m2.leading_rest(), &ctx);
+            }
             let (p2, d2) = m2.perf(ctx);
             debug!("The duration of sum's RHS: {d2:?}");
             p1.repr.extend(p2.repr);
@@ -160,44 +830,97 @@ impl<P: 'static> Music<P> {
     ) -> (Performance, Measure<Duration>) {
         let is_infinite = is_probably_infinite(&it);
         let size_hint = it.size_hint();
+        // look ahead only if required, since it makes every item to be generated twice
+        let next_rests = if ctx.release_tail > Dur::ZERO {
+            Either::Left(
+                it.clone()
+                    .skip(1)
+                    .map(|m| m.leading_rest())
+                    .chain(iter::once(Dur::ZERO)),
+            )
+        } else {
+            Either::Right(iter::repeat(Dur::ZERO))
+        };
 
-        let events_with_max_dur = it.enumerate()
-            .scan((ctx, Measure::default()), |(ctx, total_dur), (i, m)| {
+        let events_with_max_dur = it.zip(next_rests).enumerate()
+            .scan((ctx, Measure::default()), |(ctx, total_dur), (i, (m, next_rest))| {
                 if ctx.start_time == Measure::Infinite {
                     info!("Ignoring the performance of the rest of Music::Lazy, because the last item is infinite");
                     return None;
                 }
-                let (p, d) = m.perf(ctx.clone());
+                let (mut p, d) = m.perf(ctx.clone());
                 debug!("The duration of Lazy item #{i}: {d:?}");
                 debug!("Ctx start time #{i}: {:?}. Depth={}", ctx.start_time, ctx.depth);
                 ctx.start_time = ctx.start_time + d;
+                p = Self::ring_into_rest(p, next_rest, ctx);
                 *total_dur = *total_dur + d;
                 Some((p.repr, *total_dur))
             });
 
         if is_infinite {
             debug!("The Music::Lazy has infinite items");
-            let perf = Performance::with_events(events_with_max_dur.flat_map(|(e, _)| e));
+            let perf = Performance::with_items(events_with_max_dur.flat_map(|(e, _)| e));
             (perf, Measure::Infinite)
         } else {
             debug!("The Music::Lazy has finite items: {:?}", size_hint);
             // TODO: calculate the duration more intelligently (maybe some `Measure::Lazy`)
             let d = Measure::max_in_iter(events_with_max_dur.clone().map(|(_, d)| d));
-            let perf = Performance::with_events(events_with_max_dur.flat_map(|(e, _)| e));
+            let perf = Performance::with_items(events_with_max_dur.flat_map(|(e, _)| e));
             (perf, d.unwrap_or_default())
         }
     }
 
+    /// Extend the [`Event`]s ending exactly at the current start time of the [`Context`]
+    /// into the following rest (see [`Context::with_release_tail`]).
+    fn ring_into_rest(perf: Performance, next_rest: Dur, ctx: &Context<'_, P>) -> Performance {
+        let Measure::Finite(end) = ctx.start_time else {
+            return perf;
+        };
+        let tail = ctx.release_tail.min(next_rest);
+        if tail == Dur::ZERO {
+            return perf;
+        }
+
+        let tail = tail.into_ratio() * ctx.whole_note;
+        perf.map(move |e| {
+            if e.start_time + e.duration == end {
+                Event {
+                    duration: e.duration + tail,
+                    ..e
+                }
+            } else {
+                e
+            }
+        })
+    }
+
+    /// The total duration of the rests the [`Music`] starts with.
+    fn leading_rest(&self) -> Dur {
+        match self {
+            Self::Prim(Primitive::Rest(d)) => *d,
+            Self::Sequential(m1, m2) => match &**m1 {
+                Self::Prim(Primitive::Rest(d)) => *d + m2.leading_rest(),
+                m1 => m1.leading_rest(),
+            },
+            // the tempo change makes the rest last differently
+            Self::Modify(Control::Tempo(_), _)
+            | Self::Prim(Primitive::Note(..))
+            | Self::Lazy(_)
+            | Self::Parallel(..) => Dur::ZERO,
+            Self::Modify(_, m) => m.leading_rest(),
+        }
+    }
+
     fn perf_par(m1: &Self, m2: &Self, ctx: Context<'_, P>) -> (Performance, Measure<Duration>) {
         let (p1, d1) = m1.perf(ctx.clone());
         debug!("The duration of parallel's LHS: {d1:?}");
         let (p2, d2) = m2.perf(ctx);
         debug!("The duration of parallel's RHS: {d2:?}");
         (
-            Performance::with_events(
-                p1.iter()
+            Performance::with_items(
+                p1.items()
                     // use simple `.merge()` for perfectly commutative `Self::Parallel`
-                    .merge_by(p2.iter(), |x, y| x.start_time < y.start_time),
+                    .merge_by(p2.items(), Item::is_played_before),
             ),
             d1.max(d2),
         )
@@ -226,12 +949,21 @@ impl<P: 'static> Music<P> {
             }
             Control::Player(p) => {
                 info!("Overwriting player during `perform`: {}", p.name());
-                Context {
+                let ctx = Context {
                     player: Cow::Borrowed(p),
                     ..ctx
-                }
+                };
+                return p.interpret_music(self, ctx);
+            }
+            Control::KeySig(ks) => {
+                let (start, outer_key) = (ctx.start_time, ctx.key);
+                let (perf, dur) = self.perf(Context { key: *ks, ..ctx });
+                let restore = match start + dur {
+                    Measure::Finite(end) => Some((end, outer_key)),
+                    Measure::Infinite => None,
+                };
+                return (perf.in_key(*ks, start, restore), dur);
             }
-            Control::KeySig(ks) => Context { key: *ks, ..ctx },
         };
         self.perf(ctx)
     }
@@ -270,6 +1002,58 @@ pub struct Event {
     pub params: Vec<OrderedFloat<f64>>,
 }
 
+impl Event {
+    /// The order of simultaneous [`Event`]s while merging the parallel [`Performance`]s:
+    /// - the earlier event goes first;
+    /// - for the same start time, the lower pitch goes first;
+    /// - for the same pitch, the instruments are compared;
+    /// - if all of the above are the same, the left event goes first.
+    fn is_played_before(&self, other: &Self) -> bool {
+        (self.start_time, self.pitch, &self.instrument)
+            <= (other.start_time, other.pitch, &other.instrument)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// The element of the time-ordered stream the [`Performance`] consists of.
+pub(crate) enum Item {
+    /// The playing of the note along with the oscillation of its pitch.
+    ///
+    /// The [`Vibrato`] is not a part of the [`Event`],
+    /// so the transformations of the [`Event`]s keep it intact.
+    Note(Event, Option<Vibrato>),
+    /// The [key signature][KeySig] of the following [`Event`]s,
+    /// e.g. to annotate the MIDI file.
+    KeySig(TimePoint, KeySig),
+}
+
+impl Item {
+    pub(crate) const fn start_time(&self) -> TimePoint {
+        match self {
+            Self::Note(e, _) => e.start_time,
+            Self::KeySig(t, _) => *t,
+        }
+    }
+
+    pub(crate) fn into_event(self) -> Option<Event> {
+        match self {
+            Self::Note(e, _) => Some(e),
+            Self::KeySig(..) => None,
+        }
+    }
+
+    /// The order of simultaneous [`Item`]s while merging the parallel [`Performance`]s:
+    /// the marks go before the [`Event`]s starting at the same time,
+    /// the [`Event`]s are ordered by [`Event::is_played_before`].
+    fn is_played_before(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Note(e1, _), Self::Note(e2, _)) => e1.is_played_before(e2),
+            (Self::KeySig(t, _), other) => *t <= other.start_time(),
+            (Self::Note(e, _), Self::KeySig(t, _)) => e.start_time < *t,
+        }
+    }
+}
+
 /// Point on the time line to identify start of the event. Measured in seconds.
 pub type TimePoint = Ratio<u32>;
 
@@ -288,6 +1072,9 @@ pub struct Context<'p, P: 'static> {
     volume: Volume,
     key: KeySig,
     depth: usize,
+    max_depth: usize,
+    release_tail: Dur,
+    trace: bool,
 }
 
 // Manual `impl Clone` to overcome the lack of strict deriving mechanism
@@ -306,6 +1093,9 @@ impl<P: 'static> Clone for Context<'_, P> {
             volume,
             key,
             depth,
+            max_depth,
+            release_tail,
+            trace,
         } = self;
         Self {
             start_time: *start_time,
@@ -316,6 +1106,9 @@ impl<P: 'static> Clone for Context<'_, P> {
             volume: *volume,
             key: *key,
             depth: *depth,
+            max_depth: *max_depth,
+            release_tail: *release_tail,
+            trace: *trace,
         }
     }
 }
@@ -331,6 +1124,9 @@ impl<P: 'static> Context<'_, P> {
             volume,
             key,
             depth,
+            max_depth,
+            release_tail,
+            trace,
         } = self;
         let player = to_static(player);
         Context {
@@ -342,10 +1138,17 @@ impl<P: 'static> Context<'_, P> {
             volume,
             key,
             depth,
+            max_depth,
+            release_tail,
+            trace,
         }
     }
 }
 
+/// The [target](https://docs.rs/log/latest/log/#targets) of the log records
+/// produced by the [traced][Context::with_trace] performance.
+pub const TRACE_LOG_TARGET: &str = "musik::perf::trace";
+
 /// Defines a tempo of X beats per minute
 /// using the size of a single beat for reference
 /// (common value for a beat is [quarter note][Dur::QUARTER]).
@@ -366,7 +1169,18 @@ pub fn metro(setting: u32, note_dur: Dur) -> Duration {
     Ratio::from_integer(60) / (Ratio::from_integer(setting) * note_dur.into_ratio())
 }
 
+/// The duration of the whole note in the default tempo of 120 bpm.
+pub(super) fn default_whole_note() -> Duration {
+    metro(120, Dur::QUARTER)
+}
+
 impl<'p, P: 'static> Context<'p, P> {
+    /// The default limit of nesting for the [`Music`] being performed
+    /// (no limit at all).
+    ///
+    /// See [`Context::with_max_depth`] for details.
+    pub const DEFAULT_MAX_DEPTH: usize = usize::MAX;
+
     /// Defines the default [`Context`] with the given [`Player`].
     ///
     /// All the other fields could be changed using
@@ -379,11 +1193,14 @@ impl<'p, P: 'static> Context<'p, P> {
             start_time: Measure::default(),
             player,
             instrument: Instrument::AcousticGrandPiano.into(),
-            whole_note: metro(120, Dur::QUARTER),
+            whole_note: default_whole_note(),
             transpose_interval: Interval::default(),
             volume: Volume::loudest(),
             key: KeySig::default(),
             depth: 0,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            release_tail: Dur::ZERO,
+            trace: false,
         }
     }
 
@@ -446,6 +1263,43 @@ impl<'p, P: 'static> Context<'p, P> {
         Self { key, ..self }
     }
 
+    /// Changes the maximum nesting level of the [`Music`]
+    /// which is allowed to be performed.
+    ///
+    /// Every [`Music::Sequential`], [`Music::Parallel`] or [`Music::Modify`]
+    /// adds a level of nesting, so the deeply recursive generative
+    /// [`Music`] could overflow the stack during performance.
+    /// The parts nested deeper than the limit are skipped
+    /// (with the error logged), so the resulting [`Performance`]
+    /// will be partial.
+    ///
+    /// The limit is not set by default, because every note
+    /// of a long [line][Music::line] is nested one level deeper
+    /// than the previous one, so any reasonable limit
+    /// would cut off the valid long melodies.
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Let the sound of a note ring into the following [rest][Music::rest]
+    /// for at most the given duration (e.g. to imitate the natural reverberation).
+    ///
+    /// The notes followed by another note are not affected.
+    pub fn with_release_tail(self, release_tail: Dur) -> Self {
+        Self {
+            release_tail,
+            ..self
+        }
+    }
+
+    /// Log every nested part of the [`Music`] being performed
+    /// (with the `debug` level and the [`TRACE_LOG_TARGET`] target)
+    /// indented by its nesting depth, so the performance of the deeply
+    /// recursive [`Music`] could be traced in a readable way.
+    pub fn with_trace(self, trace: bool) -> Self {
+        Self { trace, ..self }
+    }
+
     /// Current start time of the [`Context`] in seconds since
     /// the start of the whole performance.
     pub fn start_time(&self) -> TimePoint {
@@ -485,6 +1339,12 @@ impl<'p, P: 'static> Context<'p, P> {
     pub const fn key(&self) -> KeySig {
         self.key
     }
+
+    /// Current nesting level of the [`Music`] being performed
+    /// (see [`Context::with_max_depth`]).
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
 }
 
 #[cfg(test)]
@@ -493,7 +1353,7 @@ mod tests {
 
     use super::*;
 
-    use crate::{n, Octave, Pitch};
+    use crate::{n, p, Octave, Pitch, PitchClass, Temporal as _};
 
     #[test]
     fn john_cage() {
@@ -501,8 +1361,8 @@ mod tests {
         // will last exactly 4'33"
         let m: Music = Music::lazy_line([Dur::from(136), Dur::HALF].into_iter().map(Music::rest));
 
-        let mut perf = m.perform();
-        assert!(perf.repr.next().is_none());
+        let perf = m.perform();
+        assert!(perf.iter().next().is_none());
     }
 
     #[test]
@@ -634,4 +1494,793 @@ mod tests {
         dbg!(&perf_eager);
         assert_eq!(perf_lazy, perf_eager);
     }
+
+    #[test]
+    fn crossfade() {
+        let a: Music = Music::with_dur(vec![p!(C 4); 4], Dur::QUARTER);
+        let b: Music = Music::with_dur(vec![p!(G 4); 4], Dur::QUARTER);
+
+        // the tempo is 2 seconds per whole note,
+        // so every performance lasts exactly 2 seconds
+        let overlap = Ratio::from_integer(1);
+        let perf: Vec<_> = Performance::crossfade(a.perform(), b.perform(), overlap)
+            .iter()
+            .collect();
+        assert_eq!(perf.len(), 8);
+
+        let (a_events, b_events): (Vec<_>, Vec<_>) =
+            perf.into_iter().partition(|e| e.pitch == p!(C 4).abs());
+
+        let a_volumes: Vec<_> = a_events.iter().map(|e| e.volume).collect();
+        assert_eq!(a_volumes, [127, 127, 127, 63].map(Volume::from).to_vec());
+
+        let b_start: Vec<_> = b_events.iter().map(|e| e.start_time).collect();
+        assert_eq!(b_start, [2, 3, 4, 5].map(|t| Ratio::new(t, 2)).to_vec());
+        let b_volumes: Vec<_> = b_events.iter().map(|e| e.volume).collect();
+        assert_eq!(b_volumes, [0, 63, 127, 127].map(Volume::from).to_vec());
+    }
+
+    #[test]
+    fn crossfade_is_progressive() {
+        let a: Music = Music::with_dur(vec![p!(C 4); 8], Dur::EIGHTH);
+        let b: Music = Music::with_dur(vec![p!(G 4); 8], Dur::EIGHTH);
+
+        let overlap = Ratio::from_integer(1);
+        let perf = Performance::crossfade(a.perform(), b.perform(), overlap);
+        let (a_events, b_events): (Vec<_>, Vec<_>) =
+            perf.iter().partition(|e| e.pitch == p!(C 4).abs());
+
+        let fading_out: Vec<_> = a_events
+            .iter()
+            .filter(|e| e.start_time >= Ratio::from_integer(1))
+            .map(|e| e.volume)
+            .collect();
+        assert_eq!(fading_out.len(), 4);
+        assert!(fading_out.windows(2).all(|w| w[0] > w[1]));
+
+        let fading_in: Vec<_> = b_events
+            .iter()
+            .filter(|e| e.start_time < Ratio::from_integer(2))
+            .map(|e| e.volume)
+            .collect();
+        assert_eq!(fading_in.len(), 4);
+        assert!(fading_in.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn rebase_sliced_performance() {
+        let m: Music = Music::with_dur(vec![p!(C 4), p!(D 4), p!(E 4), p!(F 4)], Dur::EIGHTH);
+        let perf = m.perform();
+        // drop the first two events
+        let sliced = Performance::with_events(perf.iter().skip(2));
+        assert_eq!(
+            sliced.iter().next().map(|e| e.start_time),
+            Some(Ratio::new(1, 2))
+        );
+
+        let rebased: Vec<_> = sliced.rebase().iter().collect();
+        let start_times: Vec<_> = rebased.iter().map(|e| e.start_time).collect();
+        assert_eq!(start_times, [Ratio::from(0), Ratio::new(1, 4)]);
+
+        let pitches: Vec<_> = rebased.iter().map(|e| e.pitch).collect();
+        assert_eq!(pitches, [p!(E 4).abs(), p!(F 4).abs()]);
+    }
+
+    #[test]
+    fn rebase_empty_performance() {
+        let perf = Performance::with_events(iter::empty());
+        assert!(perf.rebase().iter().next().is_none());
+    }
+
+    #[test]
+    fn parallel_simultaneous_notes_have_fixed_order() {
+        let low = Music::from(n!(C 4 / 4));
+        let high = Music::from(n!(G 4 / 4));
+
+        for m in [low.clone() | high.clone(), high | low] {
+            let pitches: Vec<_> = m.perform().iter().map(|e| e.pitch).collect();
+            assert_eq!(pitches, [p!(C 4).abs(), p!(G 4).abs()]);
+        }
+    }
+
+    #[test]
+    fn parallel_simultaneous_notes_ordered_by_instrument() {
+        let piano = Music::from(n!(C 4 / 4)).with_instrument(Instrument::AcousticGrandPiano);
+        let violin = Music::from(n!(C 4 / 4)).with_instrument(Instrument::Violin);
+
+        for m in [piano.clone() | violin.clone(), violin | piano] {
+            let instruments: Vec<_> = m.perform().iter().map(|e| e.instrument).collect();
+            assert_eq!(
+                instruments,
+                [
+                    Instrument::AcousticGrandPiano.into(),
+                    InstrumentName::from(Instrument::Violin)
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn polyphony_of_melody() {
+        let m = Music::line(vec![
+            n!(C 4 / 4).into(),
+            n!(D 4 / 4).into(),
+            n!(E 4 / 2).into(),
+        ]);
+        assert_eq!(m.max_polyphony(), 1);
+    }
+
+    #[test]
+    fn polyphony_of_chord() {
+        let m = Music::chord(vec![
+            n!(C 4 / 4).into(),
+            n!(E 4 / 4).into(),
+            n!(G 4 / 4).into(),
+        ]);
+        assert_eq!(m.max_polyphony(), 3);
+    }
+
+    #[test]
+    fn polyphony_of_overlapping_voices() {
+        let melody = Music::line(vec![n!(C 4 / 2).into(), n!(D 4 / 2).into()]);
+        let bass = Music::rest(Dur::QUARTER) + Music::from(n!(C 3 / 2));
+        assert_eq!((melody | bass).max_polyphony(), 2);
+        assert_eq!(Music::<Pitch>::rest(Dur::WHOLE).max_polyphony(), 0);
+    }
+
+    #[test]
+    fn retime_to_faster_tempo() {
+        let m = Music::line(vec![
+            n!(C 4 / 4).into(),
+            n!(D 4 / 2).into(),
+            n!(E 4 / 4).into(),
+        ]);
+        let perf = m.perform();
+        let retimed: Vec<_> = perf
+            .clone()
+            .retime(Duration::from_integer(2), Duration::from_integer(1))
+            .iter()
+            .collect();
+
+        let original: Vec<_> = perf.iter().collect();
+        assert_eq!(retimed.len(), original.len());
+        for (orig, new) in original.into_iter().zip(retimed) {
+            assert_eq!(new.start_time, orig.start_time / 2);
+            assert_eq!(new.duration, orig.duration / 2);
+            assert_eq!(new.pitch, orig.pitch);
+        }
+    }
+
+    #[test]
+    fn chord_is_simultaneous() {
+        let chord = Music::chord(vec![
+            n!(C 4 / 4).into(),
+            n!(E 4 / 4).into(),
+            n!(G 4 / 4).into(),
+        ]);
+        let perf = (Music::from(n!(B 3 / 4)) + chord).perform();
+
+        assert!(!perf.is_simultaneous_at(TimePoint::from_integer(0)));
+        assert!(perf.is_simultaneous_at(TimePoint::new(1, 2)));
+        assert!(!perf.is_simultaneous_at(TimePoint::new(1, 4)));
+    }
+
+    #[test]
+    fn regrouped_sequences_perform_the_same() {
+        let (c, d, e) = (
+            Music::from(n!(C 4 / 4)),
+            Music::from(n!(D 4 / 4)),
+            Music::from(n!(E 4 / 4)),
+        );
+        let m1 = c.clone() + (d.clone() + e.clone());
+        let m2 = (c.clone() + d.clone()) + e.clone();
+        assert_ne!(m1, m2);
+        assert!(m1.performs_same_as(&m2));
+
+        let chord1 = c.clone() | (d.clone() | e.clone());
+        let chord2 = (e.clone() | c.clone()) | d.clone();
+        assert!(chord1.performs_same_as(&chord2));
+
+        let different = c + e + d;
+        assert!(!m1.performs_same_as(&different));
+        assert!(!m1.performs_same_as(&chord1));
+    }
+
+    #[test]
+    fn too_deep_nesting_gives_empty_performance() {
+        let m = (0..50).fold(Music::from(n!(C 4 / 4)), |m, _| {
+            m.with_transpose(Interval::semi_tone())
+        });
+        let ctx = Context::with_default_player::<DefaultPlayer>().with_max_depth(10);
+        assert!(m.clone().perform_with_context(ctx).iter().next().is_none());
+
+        let ctx = Context::with_default_player::<DefaultPlayer>().with_max_depth(100);
+        let events: Vec<_> = m.perform_with_context(ctx).iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].pitch, p!(D 8).abs());
+    }
+
+    #[test]
+    fn too_long_line_gives_partial_performance() {
+        let m: Music = Music::line(vec![Music::from(n!(C 4 / 8)); 20]);
+        let ctx = Context::with_default_player::<DefaultPlayer>().with_max_depth(10);
+        let events: Vec<_> = m.perform_with_context(ctx).iter().collect();
+
+        // every next note of the line is one level deeper than the previous one
+        assert_eq!(events.len(), 9);
+        assert_eq!(events[8].start_time, TimePoint::from_integer(2));
+    }
+
+    #[test]
+    fn long_line_is_not_limited_by_default() {
+        // the deeply nested line requires more stack than the test thread has by default
+        let count = std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(|| {
+                let m: Music = Music::line(vec![Music::from(n!(C 4 / 16)); 1500]);
+                m.perform().iter().count()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(count, 1500);
+    }
+
+    #[test]
+    fn strum_chord() {
+        let chord: Music = Music::chord(vec![
+            n!(E 4 / 2).into(),
+            n!(C 4 / 2).into(),
+            n!(G 4 / 2).into(),
+        ]);
+        let spread = Duration::new(1, 50);
+
+        let events: Vec<_> = chord.clone().perform().strum(spread, true).iter().collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events.iter().map(|e| e.pitch).collect::<Vec<_>>(),
+            [p!(C 4).abs(), p!(E 4).abs(), p!(G 4).abs()]
+        );
+        assert_eq!(
+            events.iter().map(|e| e.start_time).collect::<Vec<_>>(),
+            [TimePoint::zero(), spread, spread * 2]
+        );
+        assert!(events.iter().all(|e| e.duration == Duration::one()));
+
+        let events: Vec<_> = chord.perform().strum(spread, false).iter().collect();
+        assert_eq!(events[0].pitch, p!(G 4).abs());
+        assert_eq!(events[2].pitch, p!(C 4).abs());
+        assert_eq!(events[2].start_time, spread * 2);
+    }
+
+    #[test]
+    fn connect_legato_within_instrument() {
+        let melody: Music = Music::line(vec![
+            n!(C 4 / 8).into(),
+            Music::rest(Dur::EIGHTH),
+            n!(D 4 / 8).into(),
+            Music::rest(Dur::EIGHTH),
+            n!(E 4 / 4).into(),
+        ]);
+        let bass: Music = Music::line(vec![n!(C 3 / 8).into(), Music::rest(Dur::DOTTED_HALF)])
+            .with_instrument(Instrument::AcousticBass);
+        let m = melody | bass;
+
+        let events: Vec<_> = m
+            .clone()
+            .perform()
+            .connect_legato(Ratio::zero())
+            .iter()
+            .collect();
+        let original: Vec<_> = m.clone().perform().iter().collect();
+        assert_eq!(events.len(), original.len());
+
+        let durations = |instrument: InstrumentName, events: &[Event]| -> Vec<_> {
+            events
+                .iter()
+                .filter(|e| e.instrument == instrument)
+                .map(|e| e.duration)
+                .collect()
+        };
+
+        let piano: InstrumentName = Instrument::AcousticGrandPiano.into();
+        // the quarter lasts 1/2 second
+        assert_eq!(
+            durations(piano.clone(), &events),
+            [Ratio::new(1, 2), Ratio::new(1, 2), Ratio::new(1, 2)]
+        );
+        let bass: InstrumentName = Instrument::AcousticBass.into();
+        assert_eq!(durations(bass.clone(), &events), durations(bass, &original));
+
+        // ring into the next note for a half of its duration
+        let events: Vec<_> = m
+            .perform()
+            .connect_legato(Ratio::new(1, 2))
+            .iter()
+            .collect();
+        assert_eq!(
+            durations(piano, &events),
+            [Ratio::new(5, 8), Ratio::new(3, 4), Ratio::new(1, 2)]
+        );
+    }
+
+    #[test]
+    fn connect_legato_of_infinite_performance() {
+        let m: Music = Music::lazy_line(iter::repeat_with(|| Music::from(n!(C 4 / 8))));
+        let events: Vec<_> = m
+            .perform()
+            .connect_legato(Ratio::new(1, 2))
+            .iter()
+            .take(3)
+            .collect();
+        // the eighth lasts 1/4 second
+        assert_eq!(
+            events.iter().map(|e| e.duration).collect::<Vec<_>>(),
+            [Ratio::new(3, 8); 3]
+        );
+    }
+
+    #[test]
+    fn connect_legato_of_chords() {
+        let chord = || Music::chord(vec![n!(C 4 / 8).into(), n!(E 4 / 8).into()]);
+        let m: Music = chord() + Music::rest(Dur::EIGHTH) + chord();
+        let events: Vec<_> = m.perform().connect_legato(Ratio::zero()).iter().collect();
+        assert_eq!(
+            events.iter().map(|e| e.duration).collect::<Vec<_>>(),
+            [
+                Ratio::new(1, 2),
+                Ratio::new(1, 2),
+                Ratio::new(1, 4),
+                Ratio::new(1, 4)
+            ]
+        );
+    }
+
+    fn transposed_pitches(pitches: &[Pitch], key: KeySig, degrees: i8) -> Vec<Pitch> {
+        let m = Music::with_dur(pitches.to_vec(), Dur::QUARTER);
+        m.perform()
+            .transpose_diatonic(key, degrees)
+            .iter()
+            .map(|e| Pitch::from(e.pitch))
+            .collect()
+    }
+
+    #[test]
+    fn transpose_performance_diatonic() {
+        let c_major = KeySig::Major(PitchClass::C);
+        let g_major = KeySig::Major(PitchClass::G);
+
+        assert_eq!(
+            transposed_pitches(&[p!(C 4), p!(D 4), p!(E 4)], c_major, 2),
+            [p!(E 4), p!(F 4), p!(G 4)]
+        );
+        assert_eq!(
+            transposed_pitches(&[p!(C 4), p!(D 4), p!(E 4)], g_major, 2),
+            [p!(E 4), p!(Fs 4), p!(G 4)]
+        );
+        // the D# is not from the C-Major scale
+        assert_eq!(
+            transposed_pitches(&[p!(C 4), p!(Ds 4), p!(E 4)], c_major, 2),
+            [p!(E 4), p!(F 4), p!(G 4)]
+        );
+        assert_eq!(
+            transposed_pitches(&[p!(C 4), p!(D 4), p!(A 4)], c_major, 3),
+            [p!(F 4), p!(G 4), p!(D 5)]
+        );
+        assert_eq!(
+            transposed_pitches(&[p!(C 4), p!(D 4), p!(A 4)], c_major, 10),
+            [p!(F 5), p!(G 5), p!(D 6)]
+        );
+        assert_eq!(
+            transposed_pitches(&[p!(C 4), p!(Ds 4), p!(A 4)], c_major, -19),
+            [p!(E 1), p!(F 1), p!(C 2)]
+        );
+    }
+
+    #[test]
+    fn transpose_performance_diatonic_keeps_percussion() {
+        let m: Music = Music::with_dur(vec![p!(C 3), p!(D 3)], Dur::QUARTER)
+            .with_instrument(InstrumentName::Percussion);
+        let before: Vec<_> = m.clone().perform().iter().collect();
+        let after: Vec<_> = m
+            .perform()
+            .transpose_diatonic(KeySig::Major(PitchClass::C), 2)
+            .iter()
+            .collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn volume_automation_peaks() {
+        let notes = Music::with_dur(vec![p!(C 4); 8], Dur::EIGHTH);
+        let m: Music<(Pitch, Volume)> = notes.map(|p| (p, Volume::from(100)));
+
+        // the triangle wave with the period of 1 second
+        // reaching the full volume in the middle of the period
+        let tremolo = |t: TimePoint| {
+            let phase = t.fract();
+            let half = Ratio::new(1, 2);
+            let distance = if phase > half {
+                phase - half
+            } else {
+                half - phase
+            };
+            Ratio::one() - distance
+        };
+
+        let volumes: Vec<_> = m
+            .perform()
+            .automate_volume(tremolo)
+            .iter()
+            .map(|e| u8::from(e.volume.get_inner()))
+            .collect();
+        assert_eq!(volumes, [50, 75, 100, 75, 50, 75, 100, 75]);
+    }
+
+    #[test]
+    fn volume_automation_clamped() {
+        let m = Music::with_dur(vec![p!(C 4), p!(D 4)], Dur::QUARTER);
+        let events: Vec<_> = m
+            .perform()
+            .automate_volume(|_| Ratio::from_integer(3))
+            .iter()
+            .collect();
+        assert!(events.iter().all(|e| e.volume == Volume::loudest()));
+    }
+
+    fn score_and_performance() -> (Music, Performance) {
+        let melody = Music::line(vec![
+            n!(C 4 / 4).into(),
+            Music::rest(Dur::EIGHTH),
+            Music::chord(vec![n!(E 4 / 8).into(), n!(G 4 / 8).into()]),
+            n!(C 5 / 2).into(),
+        ]);
+        let bass = Music::line(vec![n!(C 3 / 2).into(), n!(G 2 / 2).into()])
+            .with_instrument(Instrument::AcousticBass);
+        let m = melody | bass;
+        let perf = m.clone().perform();
+        (m, perf)
+    }
+
+    #[test]
+    fn score_from_performance_round_trip() {
+        let (m, perf) = score_and_performance();
+
+        let score = MusicAttr::from_performance(&perf, metro(120, Dur::QUARTER), Dur::SIXTEENTH);
+        assert_eq!(score.duration(), m.duration());
+        assert!(score.perform().iter().eq(perf.iter()));
+    }
+
+    #[test]
+    fn score_from_performance_quantizes_timing() {
+        let (_, perf) = score_and_performance();
+        // play everything a bit late and a bit shorter
+        let sloppy = perf.clone().map(|e| Event {
+            start_time: e.start_time + Ratio::new(1, 50),
+            duration: e.duration - Ratio::new(1, 50),
+            ..e
+        });
+
+        let score = MusicAttr::from_performance(&sloppy, metro(120, Dur::QUARTER), Dur::EIGHTH);
+        assert!(score.perform().iter().eq(perf.iter()));
+    }
+
+    #[test]
+    fn overlapping_chord_is_cut_short() {
+        // the G starts while the C still sounds
+        let upper: Music = Music::line(vec![n!(E 4 / 4).into(), n!(G 4 / 4).into()]);
+        let m = Music::from(n!(C 4 / 2)) | upper;
+        let perf = m.perform();
+
+        let score = MusicAttr::from_performance(&perf, metro(120, Dur::QUARTER), Dur::QUARTER);
+        let durations: Vec<_> = score.perform().iter().map(|e| e.duration).collect();
+        assert_eq!(
+            durations,
+            [Ratio::new(1, 2), Ratio::new(1, 2), Ratio::new(1, 2)]
+        );
+    }
+
+    #[test]
+    fn group_events_by_bar() {
+        let first: Music = Music::line(vec![
+            n!(C 4 / 2).into(),
+            n!(D 4 / 4).into(),
+            // crosses the bar line
+            n!(E 4 / 2).into(),
+        ]);
+        let second = Music::line(vec![n!(F 4 / 4).into(), n!(G 4 / 4).into()]);
+        let m = first + second;
+
+        let whole_note = metro(120, Dur::QUARTER);
+        let bars = m
+            .perform()
+            .group_by_bar(&TimeSignature::default(), whole_note);
+        assert_eq!(bars.len(), 2);
+
+        let pitches: Vec<Vec<_>> = bars
+            .iter()
+            .map(|bar| bar.iter().map(|e| e.pitch).collect())
+            .collect();
+        assert_eq!(
+            pitches,
+            [
+                vec![p!(C 4).abs(), p!(D 4).abs(), p!(E 4).abs()],
+                vec![p!(F 4).abs(), p!(G 4).abs()],
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_bars_are_kept() {
+        let m: Music = Music::from(n!(C 4 / 4)) + Music::rest(Dur::from(2)) + n!(D 4 / 4).into();
+        let bars = m
+            .perform()
+            .group_by_bar(&TimeSignature::new(3, 4), metro(120, Dur::QUARTER));
+        assert_eq!(bars.iter().map(Vec::len).collect::<Vec<_>>(), [1, 0, 0, 1]);
+    }
+
+    fn rubato_onsets(m: Music) -> Vec<TimePoint> {
+        let ctx = Context::with_default_player::<RubatoPlayer>();
+        Performable::<AttrNote>::perform_with_context(m, ctx)
+            .iter()
+            .map(|e| e.start_time)
+            .collect()
+    }
+
+    #[test]
+    fn rubato_makes_even_line_uneven() {
+        let notes = [
+            n!(C 4 / 8),
+            n!(D 4 / 8),
+            n!(E 4 / 8),
+            n!(F 4 / 8),
+            n!(G 4 / 8),
+            n!(F 4 / 8),
+            n!(E 4 / 8),
+            n!(D 4 / 8),
+        ];
+        let m = Music::line(notes.into_iter().map(Music::from).collect());
+        let onsets = rubato_onsets(m);
+        assert_eq!(onsets.len(), 8);
+
+        let inter_onsets: Vec<_> = onsets.windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(inter_onsets.iter().any(|&ioi| ioi != inter_onsets[0]));
+        // accelerating into the peak
+        assert!(inter_onsets[..4].windows(2).all(|w| w[0] > w[1]));
+        // broadening at the cadence
+        assert!(inter_onsets[6] > inter_onsets[5]);
+        assert!(inter_onsets[6] > inter_onsets[0]);
+    }
+
+    #[test]
+    fn rubato_shapes_the_annotated_part() {
+        let line = || {
+            Music::line(vec![
+                n!(C 4 / 4).into(),
+                n!(G 4 / 4).into(),
+                n!(E 4 / 4).into(),
+                n!(C 4 / 4).into(),
+            ])
+        };
+        let even: Vec<_> = line().perform().iter().map(|e| e.start_time).collect();
+        assert_eq!(rubato_onsets(line()), {
+            let ctx = Context::with_default_player::<DefaultPlayer>();
+            let m = MusicAttr::from(line()).with_player(RubatoPlayer::default());
+            Performable::<AttrNote>::perform_with_context(m, ctx)
+                .iter()
+                .map(|e| e.start_time)
+                .collect::<Vec<_>>()
+        });
+        assert_ne!(rubato_onsets(line()), even);
+    }
+
+    #[test]
+    fn rubato_shifts_the_next_phrase() {
+        let phrase = Music::line(vec![
+            n!(C 4 / 4).into(),
+            n!(G 4 / 4).into(),
+            n!(C 4 / 4).into(),
+        ])
+        .with_phrase(vec![]);
+        let m = phrase + Music::from(n!(C 5 / 4));
+
+        let onsets = rubato_onsets(m);
+        assert_eq!(onsets.len(), 4);
+        // the slowed cadence delays the note after the phrase
+        assert!(onsets[3] > Ratio::new(3, 2));
+        assert!(onsets.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn normalize_volume_to_target() {
+        let m: Music<(Pitch, Volume)> = Music::line(vec![
+            Music::note(Dur::QUARTER, (p!(C 4), Volume::from(20))),
+            Music::note(Dur::QUARTER, (p!(E 4), Volume::from(40))),
+            Music::note(Dur::QUARTER, (p!(G 4), Volume::from(30))),
+        ]);
+        let volumes: Vec<_> = m
+            .perform()
+            .normalize_volume(Volume::from(100))
+            .iter()
+            .map(|e| u8::from(e.volume.get_inner()))
+            .collect();
+        assert_eq!(volumes, [50, 100, 75]);
+    }
+
+    #[test]
+    fn normalize_silence_is_noop() {
+        let m: Music<(Pitch, Volume)> = Music::note(Dur::QUARTER, (p!(C 4), Volume::softest()));
+        let perf = m.perform().normalize_volume(Volume::loudest());
+        assert_eq!(perf.iter().next().unwrap().volume, Volume::softest());
+    }
+
+    #[test]
+    fn performance_as_text() {
+        let m: Music = Music::line(vec![n!(C 4 / 4).into(), n!(Fs 3 / 8).into()])
+            | Music::from(n!(E 4 / 2)).with_instrument(InstrumentName::Percussion);
+        let text = m.perform().to_text();
+        assert_eq!(
+            text,
+            "t=0 +1/2 C4 vel=127 instr=AcousticGrandPiano\n\
+             t=0 +1 E4 vel=127 instr=Percussion\n\
+             t=1/2 +1/4 F#3 vel=127 instr=AcousticGrandPiano\n"
+        );
+    }
+
+    #[test]
+    fn overlaid_voice_is_deduplicated() {
+        let voice: Music = Music::line(vec![
+            n!(C 4 / 4).into(),
+            n!(E 4 / 4).into(),
+            n!(G 4 / 2).into(),
+        ]);
+        let layered = voice.clone() | voice.clone();
+        assert_eq!(layered.clone().perform().iter().count(), 6);
+
+        let deduped: Vec<_> = layered.perform().dedup_simultaneous().iter().collect();
+        let single: Vec<_> = voice.perform().iter().collect();
+        assert_eq!(deduped, single);
+    }
+
+    #[test]
+    fn dedup_keeps_different_simultaneous_notes() {
+        let m: Music = Music::from(n!(C 4 / 4))
+            | Music::from(n!(C 4 / 2))
+            | Music::from(n!(C 4 / 4)).with_instrument(InstrumentName::Percussion)
+            | Music::from(n!(C 4 / 4));
+        let events: Vec<_> = m.perform().dedup_simultaneous().iter().collect();
+        assert_eq!(events.len(), 3);
+    }
+
+    #[test]
+    fn release_tail_rings_into_rest() {
+        let m: Music = Music::line(vec![
+            n!(C 4 / 4).into(),
+            Music::rest(Dur::QUARTER),
+            n!(D 4 / 4).into(),
+            n!(E 4 / 4).into(),
+            Music::rest(Dur::SIXTEENTH),
+        ]);
+        let ctx = Context::with_default_player::<DefaultPlayer>().with_release_tail(Dur::EIGHTH);
+
+        let durations: Vec<_> = m
+            .clone()
+            .perform_with_context(ctx.clone())
+            .iter()
+            .map(|e| e.duration)
+            .collect();
+        // the tail is limited by the rest's duration
+        assert_eq!(
+            durations,
+            [Ratio::new(3, 4), Ratio::new(1, 2), Ratio::new(5, 8)]
+        );
+
+        let lazy = Music::lazy_line(Vec::from(m).into_iter());
+        let lazy_durations: Vec<_> = lazy
+            .perform_with_context(ctx)
+            .iter()
+            .map(|e| e.duration)
+            .collect();
+        assert_eq!(lazy_durations, durations);
+    }
+
+    #[test]
+    fn no_release_tail_by_default() {
+        let m: Music = Music::from(n!(C 4 / 4)) + Music::rest(Dur::QUARTER);
+        let event = m.perform().iter().next().unwrap();
+        assert_eq!(event.duration, Ratio::new(1, 2));
+    }
+
+    #[test]
+    fn lazy_items_are_not_generated_a_third_time_without_release_tail() {
+        use std::{cell::Cell, rc::Rc};
+
+        let generated = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&generated);
+        let m: Music = Music::lazy_line((0..4).map(move |_| {
+            counter.set(counter.get() + 1);
+            Music::from(n!(C 4 / 4))
+        }));
+
+        assert_eq!(m.perform().iter().count(), 4);
+        // one pass to perform the items and one more to find out the total duration,
+        // while looking ahead for the rests is skipped without the release tail
+        assert_eq!(generated.get(), 2 * 4);
+    }
+
+    #[test]
+    fn trace_is_indented_by_depth() {
+        let _ = env_logger::try_init();
+
+        let inner: Music = Music::from(n!(D 4 / 4)) + Music::from(n!(E 4 / 4));
+        let m = Music::from(n!(C 4 / 4)) + inner.clone();
+        let ctx = Context::with_default_player::<DefaultPlayer>().with_trace(true);
+        assert_eq!(m.clone().perform_with_context(ctx).iter().count(), 3);
+
+        let ctx = Context::with_default_player::<DefaultPlayer>();
+        let start = format!("{:?}", ctx.start_time);
+        assert_eq!(m.trace_line(&ctx), format!("Sequential at {start}"));
+        let nested = Context { depth: 2, ..ctx };
+        assert_eq!(
+            inner.trace_line(&nested),
+            format!("  Sequential at {start}")
+        );
+        let note: Music = n!(E 4 / 4).into();
+        assert_eq!(
+            note.trace_line(&nested),
+            format!("  Note {:?} at {start}", Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn raw_performance_is_the_same() {
+        let m: Music<(Pitch, Volume)> = Music::line(vec![
+            Music::note(Dur::QUARTER, (p!(C 4), Volume::from(30))),
+            Music::note(Dur::EIGHTH, (p!(E 4), Volume::from(90))),
+            Music::rest(Dur::EIGHTH),
+        ])
+        .with_transpose(Interval::from(2))
+        .with_tempo(2)
+        .with_instrument(Instrument::Flute);
+        let raw: Vec<_> = m.clone().perform_raw().iter().collect();
+        assert_eq!(raw.len(), 2);
+        assert_eq!(raw, m.perform().iter().collect::<Vec<_>>());
+
+        let m: Music = Music::from(n!(C 4 / 4)) | Music::from(n!(G 4 / 2));
+        let ctx = Context::with_default_player::<DefaultPlayer>().with_volume(Volume::from(50));
+        assert_eq!(
+            m.clone()
+                .perform_raw_with_context(ctx)
+                .iter()
+                .collect::<Vec<_>>(),
+            m.perform_with_context(
+                Context::with_default_player::<DefaultPlayer>().with_volume(Volume::from(50))
+            )
+            .iter()
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn estimate_beat_of_even_quarters() {
+        let m: Music = Music::line(vec![
+            n!(C 4 / 4).into(),
+            n!(D 4 / 4).into(),
+            n!(E 4 / 4).into(),
+            n!(F 4 / 4).into(),
+        ]);
+        // the quarter note lasts half a second in the default tempo
+        assert_eq!(m.perform().estimate_beat(), Some(Ratio::new(1, 2)));
+
+        let swung: Music = Music::line(vec![
+            n!(C 4 / 4).into(),
+            n!(D 4 / 8).into(),
+            n!(E 4 / 8).into(),
+            n!(F 4 / 4).into(),
+        ]);
+        assert_eq!(swung.perform().estimate_beat(), Some(Ratio::new(1, 4)));
+
+        let single: Music = Music::from(n!(C 4 / 4)) | Music::from(n!(E 4 / 4));
+        assert_eq!(single.perform().estimate_beat(), None);
+    }
 }
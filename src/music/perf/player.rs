@@ -29,6 +29,21 @@ pub trait Player<P>: DynClone + CastFrom {
     /// Play individual notes.
     fn play_note(&self, note: (Dur, &P), ctx: Context<'_, P>) -> Performance;
 
+    /// Playing the whole [`Music`] the [`Player`] is responsible for,
+    /// i.e. the entire performed piece or the part
+    /// [annotated][Music::with_player] with this [`Player`].
+    ///
+    /// The default implementation simply performs the [`Music`]
+    /// part by part, but the [`Player`] could shape the whole [`Performance`],
+    /// treating it as a single unmarked phrase.
+    fn interpret_music(
+        &self,
+        music: &Music<P>,
+        ctx: Context<'_, P>,
+    ) -> (Performance, Measure<Duration>) {
+        music.perf(ctx)
+    }
+
     /// Playing [`Music`] phrases
     /// taking into account [`PhraseAttribute`]-s and [`Context`].
     fn interpret_phrases(
@@ -71,6 +86,68 @@ impl<P> DynPlayer<P> {
     }
 }
 
+impl<P: 'static> DynPlayer<P> {
+    /// Create a [`Player`] which plays individual notes
+    /// the same way as the `note_from` player does,
+    /// but interprets the phrases the way of the `phrase_from` player.
+    pub fn combine(note_from: Self, phrase_from: Self) -> Self {
+        Self::from_player(CombinedPlayer {
+            note_from,
+            phrase_from,
+        })
+    }
+}
+
+#[derive(Debug)]
+/// The [`Player`] delegating its responsibilities to two other [`Player`]s.
+///
+/// See [`DynPlayer::combine`].
+struct CombinedPlayer<P: 'static> {
+    note_from: DynPlayer<P>,
+    phrase_from: DynPlayer<P>,
+}
+
+// Manual `impl Clone` to avoid the unnecessary `P: Clone` bound
+impl<P> Clone for CombinedPlayer<P> {
+    fn clone(&self) -> Self {
+        Self {
+            note_from: self.note_from.clone(),
+            phrase_from: self.phrase_from.clone(),
+        }
+    }
+}
+
+impl<P: 'static> Player<P> for CombinedPlayer<P> {
+    fn name(&self) -> &'static str {
+        "Combined"
+    }
+
+    fn play_note(&self, note: (Dur, &P), ctx: Context<'_, P>) -> Performance {
+        self.note_from.play_note(note, ctx)
+    }
+
+    fn interpret_music(
+        &self,
+        music: &Music<P>,
+        ctx: Context<'_, P>,
+    ) -> (Performance, Measure<Duration>) {
+        self.phrase_from.interpret_music(music, ctx)
+    }
+
+    fn interpret_phrases(
+        &self,
+        music: &Music<P>,
+        attrs: &[PhraseAttribute],
+        ctx: Context<'_, P>,
+    ) -> (Performance, Measure<Duration>) {
+        self.phrase_from.interpret_phrases(music, attrs, ctx)
+    }
+
+    fn interpret_phrase(&self, perf: Performance, attr: &PhraseAttribute) -> Performance {
+        self.phrase_from.interpret_phrase(perf, attr)
+    }
+}
+
 impl<P> Deref for DynPlayer<P> {
     type Target = Box<dyn Player<P>>;
 
@@ -143,10 +220,15 @@ impl<T: 'static, U: 'static> MapToOther<DynPlayer<U>> for DynPlayer<T> {
 mod tests {
     use super::*;
 
+    use std::borrow::Cow;
+
+    use num_rational::Ratio;
+    use num_traits::One as _;
+
     use crate::{
-        music::{Control, MusicAttr},
+        music::{phrase::Dynamic, AttrNote, Control, MusicAttr},
         n, p,
-        perf::DefaultPlayer,
+        perf::{DefaultPlayer, Event, FancyPlayer, Performable},
         Octave, Pitch, PitchClass, Temporal as _, Volume,
     };
 
@@ -214,4 +296,52 @@ mod tests {
             matches!(m, Music::Modify(Control::Player(ref pl), _) if pl.name() == "Default (Pitch with attributes)")
         );
     }
+
+    #[derive(Debug, Clone, Default)]
+    struct StaccatoPlayer;
+
+    impl Player<AttrNote> for StaccatoPlayer {
+        fn name(&self) -> &'static str {
+            "Staccato"
+        }
+
+        fn play_note(&self, note: (Dur, &AttrNote), ctx: Context<'_, AttrNote>) -> Performance {
+            DefaultPlayer::default()
+                .play_note(note, ctx)
+                .map(|e| Event {
+                    duration: e.duration / 2,
+                    ..e
+                })
+        }
+
+        fn interpret_phrase(&self, perf: Performance, _attr: &PhraseAttribute) -> Performance {
+            perf
+        }
+    }
+
+    #[test]
+    fn combined_player_notes_and_phrases() {
+        let m = Music::with_dur(vec![p!(C 4), p!(D 4), p!(E 4), p!(F 4)], Dur::QUARTER)
+            .with_phrase(vec![
+                PhraseAttribute::Dyn(Dynamic::Loudness(Volume::from(40))),
+                PhraseAttribute::Dyn(Dynamic::Crescendo(Ratio::one())),
+            ]);
+        let player = DynPlayer::combine(
+            DynPlayer::from_player(StaccatoPlayer),
+            DynPlayer::from_player(FancyPlayer::default()),
+        );
+        assert_eq!(player.name(), "Combined");
+
+        let ctx = Context::with_player(Cow::Owned(player));
+        let events: Vec<_> = Performable::<AttrNote>::perform_with_context(m, ctx)
+            .iter()
+            .collect();
+        assert_eq!(events.len(), 4);
+
+        // the staccato notes
+        assert!(events.iter().all(|e| e.duration == Duration::new(1, 4)));
+        // the fancy crescendo
+        assert_eq!(events[0].volume, Volume::from(40));
+        assert!(events.windows(2).all(|w| w[0].volume < w[1].volume));
+    }
 }
@@ -6,8 +6,9 @@
 use enum_iterator::Sequence;
 use enum_map::Enum;
 use num_rational::Ratio;
+use num_traits::{One as _, Zero as _};
 
-use crate::prim::volume::Volume;
+use crate::prim::{duration::Dur, volume::Volume};
 
 type Rational = Ratio<u32>;
 
@@ -44,6 +45,13 @@ pub enum Dynamic {
     Crescendo(Rational),
     /// Gradually decreasing volume.
     Diminuendo(Rational),
+    /// The [`Self::Crescendo`] from near silence up to the original volume
+    /// only during the given duration at the start of the phrase,
+    /// the following notes are played with the original volume.
+    FadeIn(Dur),
+    /// The [`Self::Diminuendo`] down to silence
+    /// only during the given duration at the end of the phrase.
+    FadeOut(Dur),
     /// Choose from one of the standard Volume presets.
     StdLoudness(StdLoudness),
     /// Explicitly specify [`Volume`].
@@ -105,6 +113,55 @@ pub enum Tempo {
     Accelerando(Rational),
     /// Slowing down gradually, opposite of [`Self::Accelerando`].
     Ritardando(Rational),
+    /// The [`Self::Accelerando`] following the given [`TempoCurve`]
+    /// instead of the [linear][TempoCurve::Linear] one.
+    AccelerandoCurve(Rational, TempoCurve),
+    /// The [`Self::Ritardando`] following the given [`TempoCurve`]
+    /// instead of the [linear][TempoCurve::Linear] one.
+    RitardandoCurve(Rational, TempoCurve),
+}
+
+#[derive(Debug, Default, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+/// The shape of the gradual [tempo change][Tempo]
+/// over the course of the phrase.
+pub enum TempoCurve {
+    /// The tempo changes evenly from the start to the end of the phrase.
+    #[default]
+    Linear,
+    /// The tempo changes slowly at the start of the phrase
+    /// and more and more rapidly towards its end.
+    ///
+    /// The curve is approximated with the quadratic function
+    /// to keep the calculations precise.
+    Exponential,
+    /// The tempo stays the same until the given fraction of the phrase passes
+    /// and then changes evenly (as the [`Self::Linear`] does) towards its end,
+    /// e.g. to slow down only the final bars.
+    ///
+    /// The amount of the tempo change relates to the delayed part of the phrase only.
+    Delayed(Rational),
+}
+
+impl TempoCurve {
+    /// Map the relative position inside the phrase
+    /// (from `0` at the start to `1` at the end)
+    /// into the time shift accumulated up to this point
+    /// relative to the whole duration of the phrase
+    /// (to be scaled by the amount of the tempo change).
+    pub(crate) fn apply(self, x: Rational) -> Rational {
+        match self {
+            Self::Linear => x * x,
+            Self::Exponential => x * x * x,
+            Self::Delayed(start) => {
+                if x <= start {
+                    return Rational::zero();
+                }
+                // the linear curve after the `start` as if the phrase started there
+                let dx = x - start;
+                dx * dx / (Rational::one() - start)
+            }
+        }
+    }
 }
 
 #[allow(missing_docs)]
@@ -162,6 +219,30 @@ pub enum Ornament {
     // Instruction(String),
     Head(NoteHead),
     DiatonicTrans(i8),
+    /// Regular oscillation of the pitch.
+    ///
+    /// In MIDI it is realized with the pitch bend messages
+    /// affecting the whole channel, i.e. all the notes
+    /// of the same instrument sounding at the same time.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Vibrato>
+    Vibrato {
+        /// How many oscillations per second.
+        rate: Ratio<u32>,
+        /// The maximum deviation from the main pitch
+        /// in [cents](https://en.wikipedia.org/wiki/Cent_(music)).
+        depth_cents: u32,
+    },
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+/// The parameters of the [`Vibrato`][Ornament::Vibrato]
+/// attached to the single performed note.
+pub struct Vibrato {
+    /// How many oscillations per second.
+    pub rate: Ratio<u32>,
+    /// The maximum deviation from the main pitch in cents.
+    pub depth_cents: u32,
 }
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
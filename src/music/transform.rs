@@ -5,9 +5,24 @@
 //! - <https://en.wikipedia.org/wiki/Transformation_(music)>
 //! - <https://en.wikipedia.org/wiki/Permutation_(music)>
 
-use crate::prim::{duration::Dur, interval::Interval, pitch::AbsPitch};
+use std::iter;
 
-use super::{Music, Primitive, Temporal as _};
+use enum_map::Enum as _;
+use itertools::Itertools as _;
+use num_rational::Ratio;
+use num_traits::One as _;
+
+use crate::prim::{
+    duration::{Dur, DurT},
+    interval::{Interval, Octave},
+    pitch::{AbsPitch, ErrorPitchClipping, Pitch},
+    scale::KeySig,
+    volume::Volume,
+};
+
+use super::{
+    control::Control, generators::SplitMix64, phrase::StdLoudness, Music, Primitive, Temporal as _,
+};
 
 impl Music {
     /// In contrast to the annotation of the [`Music`] with [`Transpose`][`Self::with_transpose`]
@@ -23,6 +38,76 @@ impl Music {
         self.map(move |pitch| pitch.trans(delta))
     }
 
+    /// Transpose every note of the [`Music`] like [`Self::trans`] does,
+    /// but instead of silently clipping the pitches
+    /// jumping out of the valid range, report the [`ErrorPitchClipping`]
+    /// for the first such note.
+    ///
+    /// # Panics
+    /// If the [`Music`] contains an infinite [lazy line][Self::Lazy].
+    pub fn try_trans(self, delta: Interval) -> Result<Self, ErrorPitchClipping> {
+        self.fold_by_ref(
+            |prim| match prim {
+                Primitive::Note(_, pitch) => pitch
+                    .abs_checked()
+                    .and_then(|abs| abs.checked_add(delta))
+                    .map(|_| ()),
+                Primitive::Rest(_) => Ok(()),
+            },
+            Result::and,
+            (Ok(()), Result::and),
+            Result::and,
+            |_, res| res,
+        )?;
+        Ok(self.trans(delta))
+    }
+
+    /// Find the notes which pitches jump out of the valid range
+    /// of the [`AbsPitch`] when performed (taking into account
+    /// the [transposition][Self::with_transpose] annotations),
+    /// so they would be silently clipped.
+    ///
+    /// Every note is reported by its index in the order of appearance
+    /// in the [`Music`] (the left parts of the [sequential][Self::Sequential]
+    /// and [parallel][Self::Parallel] compositions go first).
+    ///
+    /// # Panics
+    /// If the [`Music`] contains an infinite [lazy line][Self::Lazy].
+    pub fn find_clipped(&self) -> Vec<(usize, ErrorPitchClipping)> {
+        let concat = |mut p1: Vec<_>, p2| {
+            p1.extend(p2);
+            p1
+        };
+        let notes = self.fold_by_ref(
+            |prim| match prim {
+                Primitive::Note(_, pitch) => vec![(pitch.abs_checked(), Interval::zero())],
+                Primitive::Rest(_) => vec![],
+            },
+            concat,
+            (vec![], concat),
+            concat,
+            |control, notes| {
+                if let Control::Transpose(delta) = control {
+                    notes
+                        .into_iter()
+                        .map(|(abs, transpose)| (abs, transpose + *delta))
+                        .collect()
+                } else {
+                    notes
+                }
+            },
+        );
+        notes
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, (abs, transpose))| {
+                abs.and_then(|abs| abs.checked_add(transpose))
+                    .err()
+                    .map(|err| (i, err))
+            })
+            .collect()
+    }
+
     /// Get the inverted [musical line][Self::line]
     /// where all the pitch intervals _from the first note_
     /// replaced with their simple arithmetic inverses (-).
@@ -54,6 +139,443 @@ impl Music {
         }
     }
 
+    /// Get the inverted [`Music`] where every pitch
+    /// is mirrored around the given `axis` pitch:
+    /// the pitches above the axis become the same distance below it
+    /// and vice versa.
+    ///
+    /// In contrast to [`Self::invert`], the structure of the [`Music`]
+    /// is preserved, and the pitches that jump out of the valid range
+    /// are clipped to the lowest or the highest [`AbsPitch`].
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Inversion_(music)#Melodies>
+    pub fn invert_around(self, axis: Pitch) -> Self {
+        let axis = axis.abs();
+        self.map(move |pitch| Pitch::from(axis + (axis - pitch.abs())))
+    }
+
+    /// The melodic contour of the [musical line][Self::line]:
+    /// the [`Interval`]s between every pair of the consecutive notes.
+    ///
+    /// The [rests][Primitive::Rest] and the non-primitive parts
+    /// of the line (e.g. chords) are skipped.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Contour_(music)>
+    pub fn contour(&self) -> Vec<Interval> {
+        Vec::from(self.clone())
+            .iter()
+            .filter_map(|m| m.as_note().map(|(_, p)| p.abs()))
+            .tuple_windows()
+            .map(|(p1, p2)| p2 - p1)
+            .collect()
+    }
+
+    /// For every note of the [musical line][Self::line]
+    /// get its [degree][KeySig::scale_degree] in the given scale
+    /// or `None` for the chromatic note, e.g. to make the Nashville Number System chart.
+    ///
+    /// The [rests][Primitive::Rest] and the non-primitive parts
+    /// of the line (e.g. chords) are skipped.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Nashville_Number_System>
+    pub fn scale_degrees(&self, key: KeySig) -> Vec<Option<u8>> {
+        Vec::from(self.clone())
+            .iter()
+            .filter_map(|m| m.as_note().map(|(_, p)| key.scale_degree(p.abs())))
+            .collect()
+    }
+
+    /// Restore the [musical line][Self::line] of the notes of the same [`Dur`]
+    /// starting from the `start` pitch and moving by the given `intervals`.
+    ///
+    /// It is the inverse of the [`Self::contour`].
+    pub fn from_contour(start: Pitch, intervals: &[Interval], dur: Dur) -> Self {
+        let pitches = iter::once(start).chain(intervals.iter().scan(start, |p, delta| {
+            *p = p.trans(*delta);
+            Some(*p)
+        }));
+        Self::with_dur(pitches.collect(), dur)
+    }
+
+    /// In contrast to the [`Self::trans`] which transposes by the fixed [`Interval`],
+    /// this function moves every note by the given number
+    /// of the scale degrees in the [key][KeySig]
+    /// (e.g. 'up a third in the key' means `degrees=2`).
+    ///
+    /// The resulting pitches are spelled according to the key.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Transposition_(music)#Diatonic>
+    pub fn transpose_diatonic(self, key: KeySig, degrees: i8) -> Self {
+        self.map(move |pitch| key.spell(pitch.abs().diatonic_trans(key, degrees)))
+    }
+
+    /// Add the second voice moving in parallel
+    /// with the [`Music`] at the fixed [`Interval`].
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Parallel_harmony>
+    pub fn harmonize(self, interval: Interval) -> Self {
+        self.clone() | self.trans(interval)
+    }
+
+    /// Add the second voice moving in parallel with the [`Music`]
+    /// by the given number of the scale degrees in the [key][KeySig]
+    /// (see [`Self::transpose_diatonic`]), e.g. in thirds (`degrees=2`)
+    /// or sixths (`degrees=5`) which are major or minor depending on the degree.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Parallel_harmony>
+    pub fn harmonize_diatonic(self, key: KeySig, degrees: i8) -> Self {
+        self.clone() | self.transpose_diatonic(key, degrees)
+    }
+
+    /// Force every pitch of the [`Music`] to belong to the given [scale][KeySig]
+    /// by replacing it with the closest pitch of the scale
+    /// (the lower one is chosen if there are two equally close pitches).
+    ///
+    /// Could be useful to tame the randomly generated melodies.
+    pub fn snap_to_scale(self, key: KeySig) -> Self {
+        self.map(move |pitch| key.spell(pitch.abs().snap_to_key(key)))
+    }
+
+    /// Move every out-of-key note of the [`Music`] to the nearest pitch
+    /// of the given [scale][KeySig], making the [`Music`] diatonic.
+    ///
+    /// In contrast to the [`Self::snap_to_scale`], the choice between two equally close
+    /// pitches of the scale depends on the neighbouring notes of the melodic line,
+    /// so the chromatic tones are resolved stepwise:
+    /// - the pitch repeating the previous or the next note is avoided,
+    ///   so the passing tone `E F# G` becomes `E F G`
+    ///   and the neighbour tone `C C# C` becomes `C D C`;
+    /// - otherwise, the pitch closer to the next note is chosen.
+    ///
+    /// The notes of the [lazy][Self::lazy_line] lines are resolved
+    /// without looking at their neighbours.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Nonchord_tone>
+    pub fn conform_to_key(self, key: KeySig) -> Self {
+        match self {
+            Self::Prim(Primitive::Note(d, p)) => Self::note(d, conform_pitch(p, [None, None], key)),
+            Self::Sequential(..) => {
+                let items = self.into_line_items();
+                let pitches: Vec<_> = items
+                    .iter()
+                    .map(|m| match m {
+                        Self::Prim(Primitive::Note(_, p)) => Some(p.abs()),
+                        _ => None,
+                    })
+                    .collect();
+                let neighbour = |range: &mut dyn Iterator<Item = usize>| {
+                    range
+                        .map(|j| (&items[j], pitches[j]))
+                        .find(|(m, _)| !matches!(m, Self::Prim(Primitive::Rest(_))))
+                        .and_then(|(_, p)| p)
+                };
+
+                Self::from_line_items(items.iter().enumerate().map(|(i, m)| match m {
+                    Self::Prim(Primitive::Note(d, p)) => {
+                        let prev = neighbour(&mut (0..i).rev());
+                        let next = neighbour(&mut (i + 1..items.len()));
+                        Self::note(*d, conform_pitch(*p, [prev, next], key))
+                    }
+                    other => other.clone().conform_to_key(key),
+                }))
+            }
+            Self::Parallel(m1, m2) => m1.conform_to_key(key) | m2.conform_to_key(key),
+            Self::Lazy(it) => Self::lazy_line(it.map(move |m| m.conform_to_key(key))),
+            Self::Modify(control, m) => m.conform_to_key(key).with(control),
+            rest @ Self::Prim(Primitive::Rest(_)) => rest,
+        }
+    }
+
+    /// Shift the whole [`Music`] by the same number of octaves
+    /// to make it fit into the range of an instrument `[low..=high]`.
+    ///
+    /// In contrast to shifting every single note separately,
+    /// the melodic contour stays intact.
+    /// If the [`Music`] cannot fit into the range completely,
+    /// the shift with the fewest out-of-range notes is chosen
+    /// (preferring the smallest shift and then the lower one).
+    pub fn fit_to_range(self, low: Pitch, high: Pitch) -> Self {
+        let (low, high) = (low.abs(), high.abs());
+        let concat = |mut p1: Vec<Pitch>, p2| {
+            p1.extend(p2);
+            p1
+        };
+        let pitches = self.fold_by_ref(
+            |prim| match prim {
+                Primitive::Note(_, p) => vec![*p],
+                Primitive::Rest(_) => vec![],
+            },
+            concat,
+            (vec![], concat),
+            concat,
+            |_, p| p,
+        );
+
+        let max_shift = i8::try_from(Octave::LENGTH).expect("Octaves number is small");
+        let best_shift = (-max_shift..=max_shift)
+            .filter_map(|k| {
+                let out_of_range = pitches
+                    .iter()
+                    .map(|p| shift_octave(*p, k).map(Pitch::abs_checked))
+                    .try_fold(0_usize, |count, p| {
+                        let p = p?.ok()?;
+                        Some(count + usize::from(p < low || p > high))
+                    })?;
+                Some((k, out_of_range))
+            })
+            .min_by_key(|(k, out_of_range)| (*out_of_range, k.unsigned_abs()))
+            .map(|(k, _)| k);
+
+        match best_shift {
+            Some(k) if k != 0 => self
+                .map(move |p| shift_octave(p, k).expect("The shift was checked for all pitches")),
+            _ => self,
+        }
+    }
+
+    /// Get rid of the [`Transpose`][Control::Transpose] and [`Tempo`][Control::Tempo]
+    /// annotations by applying them directly to the pitches and durations of the notes.
+    ///
+    /// The other [controls][Control] (e.g. [`Instrument`][Control::Instrument])
+    /// could not be expressed at the level of a single note, so they stay in place.
+    ///
+    /// The result is performed the same way as the original [`Music`],
+    /// but could be analyzed without running the performance.
+    pub fn flatten_controls(self) -> Self {
+        self.flatten_controls_with(Interval::zero(), Ratio::one())
+    }
+
+    fn flatten_controls_with(self, delta: Interval, tempo: Ratio<DurT>) -> Self {
+        match self {
+            Self::Prim(Primitive::Note(d, p)) => Self::note(d / tempo, p.trans(delta)),
+            Self::Prim(Primitive::Rest(d)) => Self::rest(d / tempo),
+            Self::Sequential(m1, m2) => {
+                m1.flatten_controls_with(delta, tempo) + m2.flatten_controls_with(delta, tempo)
+            }
+            Self::Lazy(it) => {
+                Self::lazy_line(it.map(move |m| m.flatten_controls_with(delta, tempo)))
+            }
+            Self::Parallel(m1, m2) => {
+                m1.flatten_controls_with(delta, tempo) | m2.flatten_controls_with(delta, tempo)
+            }
+            Self::Modify(Control::Transpose(i), m) => m.flatten_controls_with(delta + i, tempo),
+            Self::Modify(Control::Tempo(r), m) => m.flatten_controls_with(delta, tempo * r),
+            Self::Modify(c, m) => m.flatten_controls_with(delta, tempo).with(c),
+        }
+    }
+
+    /// Move every note of the [`Music`] by the random number of octaves
+    /// in the range `-spread..=spread` keeping its [pitch class][crate::PitchClass]
+    /// to make the texture more varied.
+    ///
+    /// The choice is pseudo-random but deterministic for the given `seed`.
+    /// The shift which moves the note out of the valid range
+    /// is reduced to the largest possible one in the same direction.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Register_(music)>
+    pub fn octave_jumble(self, seed: u64, spread: u8) -> Self {
+        let max_shift = i8::try_from(Octave::LENGTH).expect("Octaves number is small");
+        let spread = i8::try_from(spread).unwrap_or(i8::MAX).min(max_shift);
+        let choices = usize::from(spread.unsigned_abs()) * 2 + 1;
+
+        self.map_notes_randomly(&mut SplitMix64::new(seed), move |rng, n| {
+            let Self::Prim(Primitive::Note(d, pitch)) = n else {
+                return n;
+            };
+            let shift = i8::try_from(rng.below(choices)).expect("The shift is small") - spread;
+            let shifted = (0..=shift.abs())
+                .rev()
+                .filter_map(|k| shift_octave(pitch, k * shift.signum()))
+                .find(|p| p.abs_checked().is_ok())
+                .unwrap_or(pitch);
+            Self::note(d, shifted)
+        })
+    }
+
+    /// Loosen the strict timing of the [`Music`] right in the score,
+    /// so that it sounds (and looks when exported) less mechanical.
+    ///
+    /// Every note is either delayed or released earlier
+    /// by the random amount up to `spread` (but no more than a half of the note),
+    /// with the tiny [rest][Self::rest] filling the gap.
+    /// The onsets of the following notes are not shifted,
+    /// so the total duration of the [`Music`] stays the same.
+    ///
+    /// The choice is pseudo-random but deterministic for the given `seed`.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Humanize>
+    pub fn humanize(self, seed: u64, spread: Dur) -> Self {
+        const STEPS: DurT = 8;
+
+        self.map_notes_randomly(&mut SplitMix64::new(seed), move |rng, n| {
+            let Self::Prim(Primitive::Note(d, pitch)) = n else {
+                return n;
+            };
+            let step = DurT::try_from(rng.below(STEPS as usize + 1)).expect("The step is small");
+            let late = rng.below(2) == 0;
+            let shift = spread.min(d.halve()) * Ratio::new(step, STEPS);
+            if shift == Dur::ZERO {
+                return n;
+            }
+
+            let note = Self::note(d - shift, pitch);
+            if late {
+                Self::rest(shift) + note
+            } else {
+                note + Self::rest(shift)
+            }
+        })
+    }
+
+    /// Overlay the [`Music`] with its delayed and progressively quieter copies
+    /// to produce a simple echo effect.
+    ///
+    /// The `k`-th repeat is delayed by `delay * k`
+    /// and its volume is scaled by `decay^k`.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Echo>
+    #[allow(clippy::similar_names)]
+    pub fn echo(self, delay: Dur, repeats: usize, decay: Ratio<u8>) -> Music<(Pitch, Volume)> {
+        let decay = Ratio::new(u32::from(*decay.numer()), u32::from(*decay.denom()));
+        let original = self.clone().with_volume(Volume::loudest());
+
+        (1..=repeats)
+            .scan(Volume::loudest(), |volume, k| {
+                let scaled = decay * u32::from(u8::from(volume.get_inner()));
+                *volume = Volume::from(u8::try_from(scaled.to_integer()).unwrap_or(u8::MAX));
+                let k = DurT::try_from(k).expect("Too many repeats");
+                Some(self.clone().with_volume(*volume).with_delay(delay * k))
+            })
+            .fold(original, |acc, m| acc | m)
+    }
+
+    /// Assign every note the [`Volume`] of the piecewise-linear envelope
+    /// defined by the breakpoints `(onset, volume)` at the onset of the note
+    /// (see [`Self::map_with_onset`]).
+    ///
+    /// The notes before the first breakpoint or after the last one
+    /// get the volume of that breakpoint.
+    /// Without any breakpoints all the notes are the [loudest][Volume::loudest].
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Envelope_(music)>
+    pub fn envelope(self, points: &[(Dur, Volume)]) -> Music<(Pitch, Volume)> {
+        let mut points: Vec<_> = points
+            .iter()
+            .map(|&(at, vol)| (at.into_ratio::<u32>(), u32::from(u8::from(vol.get_inner()))))
+            .collect();
+        points.sort_by_key(|&(at, _)| at);
+
+        self.map_with_onset(move |onset, pitch| {
+            let onset = onset.into_ratio();
+            let after = points.partition_point(|&(at, _)| at <= onset);
+            let volume = match (after.checked_sub(1).map(|i| points[i]), points.get(after)) {
+                (Some((_, vol)), None) | (None, Some(&(_, vol))) => Ratio::from(vol),
+                (Some((start, from)), Some(&(end, to))) => {
+                    let progress = (onset - start) / (end - start);
+                    // interpolate in both directions without the negative values
+                    Ratio::from(from) * (Ratio::one() - progress) + Ratio::from(to) * progress
+                }
+                (None, None) => Ratio::from(u32::from(u8::from(Volume::loudest().get_inner()))),
+            };
+            let volume = u8::try_from(volume.round().to_integer()).unwrap_or(u8::MAX);
+            (*pitch, Volume::from(volume))
+        })
+    }
+
+    /// Emphasize the notes of the [musical line][Self::line]
+    /// by cycling the `pattern` of accents over them (like the step sequencers do):
+    /// the accented notes are played [fortissimo][StdLoudness::Fortissimo]
+    /// and the other ones are played [mezzo-forte][StdLoudness::MezzoForte].
+    ///
+    /// The rests of the line are not counted,
+    /// while the non-primitive parts of the line (e.g. chords)
+    /// are counted as a single note.
+    ///
+    /// The [`Music`] should be finite: the infinite [lazy line][Self::Lazy]
+    /// is collected eagerly, so the accenting never returns.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Accent_(music)>
+    ///
+    /// # Panics
+    /// If the `pattern` is empty.
+    pub fn accent_pattern(self, pattern: &[bool]) -> Music<(Pitch, Volume)> {
+        assert!(
+            !pattern.is_empty(),
+            "The accent pattern should not be empty"
+        );
+
+        let accented = StdLoudness::Fortissimo.get_volume();
+        let normal = StdLoudness::MezzoForte.get_volume();
+        let mut accents = pattern.iter().cycle();
+        let line = Vec::from(self)
+            .into_iter()
+            .map(|m| {
+                let volume = match m {
+                    Self::Prim(Primitive::Rest(_)) => normal,
+                    _ if accents.next() == Some(&true) => accented,
+                    _ => normal,
+                };
+                m.with_volume(volume)
+            })
+            .collect();
+        Music::line(line)
+    }
+
+    /// Separate the polyphonic [`Music`] into the voices
+    /// by walking its top-level [parallel][Self::Parallel] structure.
+    ///
+    /// The nested parallels are flattened, so `(a | b) | c` gives three voices.
+    /// The [controls][Control] wrapping the parallel parts are copied
+    /// into every voice, so [playing them together][Self::chord]
+    /// sounds the same as the original [`Music`].
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Part_(music)>
+    pub fn split_voices(&self) -> Vec<Self> {
+        match self {
+            Self::Parallel(m1, m2) => {
+                let mut voices = m1.split_voices();
+                voices.extend(m2.split_voices());
+                voices
+            }
+            Self::Modify(c, m) => m
+                .split_voices()
+                .into_iter()
+                .map(|voice| voice.with(c.clone()))
+                .collect(),
+            Self::Prim(_) | Self::Sequential(_, _) | Self::Lazy(_) => vec![self.clone()],
+        }
+    }
+
+    /// Reverse the order of the pitches in the [musical line][Self::line]
+    /// while keeping the rhythm: the durations of the notes
+    /// and the positions of the rests stay the same.
+    ///
+    /// In contrast to the [`Self::retrograde`], only the pitches are reversed.
+    ///
+    /// Returns `None` if the [`Music`] is not a line of [primitives][Self::Prim].
+    pub fn retrograde_pitches(self) -> Option<Self> {
+        let prims = Vec::from(self)
+            .into_iter()
+            .map(|m| match m {
+                Self::Prim(p) => Some(p),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut pitches = prims.iter().rev().filter_map(Primitive::pitch);
+        let line = prims
+            .iter()
+            .map(|p| match p {
+                Primitive::Note(d, _) => Self::note(
+                    *d,
+                    *pitches.next().expect("The number of notes is the same"),
+                ),
+                Primitive::Rest(d) => Self::rest(*d),
+            })
+            .collect();
+        Some(Self::line(line))
+    }
+
     /// [Playing the reversed version][Self::retrograde]
     /// of the [inverted][Self::invert] [musical line][Self::line].
     ///
@@ -92,6 +614,43 @@ impl<P> Music<P> {
         Self::lazy_line(Vec::from(self).into_iter().rev())
     }
 
+    /// Playing the reversed version of a [musical line][Self::line]
+    /// which could be [lazy][Self::lazy_line] (e.g. a long but finite generated stream).
+    ///
+    /// In contrast to the [`Self::retrograde`], no more than `max` items
+    /// of the line are buffered, so the error is returned for the longer
+    /// (or even infinite) lines instead of consuming all the memory.
+    pub fn retrograde_bounded(self, max: usize) -> Result<Self, String>
+    where
+        P: Clone,
+    {
+        let mut items = Vec::new();
+        self.collect_line_items(max, &mut items)?;
+        Ok(Self::lazy_line(items.into_iter().rev()))
+    }
+
+    fn collect_line_items(self, max: usize, items: &mut Vec<Self>) -> Result<(), String> {
+        match self {
+            Self::Prim(Primitive::Rest(Dur::ZERO)) => Ok(()),
+            Self::Sequential(m1, m2) => {
+                m1.collect_line_items(max, items)?;
+                m2.collect_line_items(max, items)
+            }
+            Self::Lazy(it) => it
+                .into_iter()
+                .try_for_each(|m| m.collect_line_items(max, items)),
+            other => {
+                if items.len() >= max {
+                    return Err(format!(
+                        "The line is too long to reverse: it has more than {max} items"
+                    ));
+                }
+                items.push(other);
+                Ok(())
+            }
+        }
+    }
+
     /// Play the [`Music`] backwards.
     ///
     /// In contrast with the [`Self::retrograde`]
@@ -119,6 +678,302 @@ impl<P> Music<P> {
             |c, m| m.with(c),
         )
     }
+
+    /// Pad every [parallel][Self::Parallel] voice with the trailing rest
+    /// so that all the voices have the same duration
+    /// (the same way as [`Self::reverse`] aligns the voices).
+    ///
+    /// The nested parallels are balanced as well.
+    pub fn balance(self) -> Self {
+        match self {
+            Self::Parallel(m1, m2) => {
+                let (m1, m2) = (m1.balance(), m2.balance());
+                let longest = m1.duration().max(m2.duration());
+                m1.pad_to(longest) | m2.pad_to(longest)
+            }
+            Self::Modify(c, m) => m.balance().with(c),
+            Self::Prim(_) | Self::Sequential(_, _) | Self::Lazy(_) => self,
+        }
+    }
+
+    /// Append the trailing rest to the [`Music`]
+    /// to make it last exactly the `target` duration.
+    ///
+    /// The [`Music`] already lasting for the `target` duration or longer
+    /// is returned as is.
+    pub fn pad_to(self, target: Dur) -> Self {
+        let dur = self.duration();
+        if dur < target {
+            self + Self::rest(target - dur)
+        } else {
+            self
+        }
+    }
+
+    /// Thin out the [musical line][Self::line] by keeping only
+    /// the 1st, the `(n+1)`-th, the `(2n+1)`-th, etc. notes
+    /// and replacing all the other notes with the rests of equal duration,
+    /// so the timing of the kept notes is preserved.
+    ///
+    /// The rests of the line are not counted,
+    /// while the non-primitive parts of the line (e.g. chords)
+    /// are counted as a single note.
+    ///
+    /// # Panics
+    /// If `n` is zero.
+    pub fn keep_every_nth(self, n: usize) -> Self {
+        assert!(n > 0, "The step to keep the notes should be positive");
+
+        let line = Vec::from(self)
+            .into_iter()
+            .scan(0, |notes_seen, m| {
+                let m = match m {
+                    rest @ Self::Prim(Primitive::Rest(_)) => rest,
+                    m => {
+                        let i = *notes_seen;
+                        *notes_seen += 1;
+                        if i % n == 0 {
+                            m
+                        } else {
+                            Self::rest(m.duration())
+                        }
+                    }
+                };
+                Some(m)
+            })
+            .collect();
+        Self::line(line)
+    }
+
+    /// Remove the silence at the end of the [`Music`]
+    /// represented by the trailing [rests][Primitive::Rest]
+    /// (or the parts consisting of the rests only) of the [lines][Self::line].
+    ///
+    /// The rests inside the [`Music`] are preserved.
+    /// The [parallel][Self::Parallel] parts are trimmed independently,
+    /// so the result lasts until the end of the last note.
+    pub fn trim_trailing_rests(self) -> Self {
+        match self {
+            Self::Prim(Primitive::Rest(_)) => Self::rest(Dur::ZERO),
+            n @ Self::Prim(Primitive::Note(..)) => n,
+            line @ (Self::Sequential(..) | Self::Lazy(_)) => {
+                let mut line = Vec::from(line);
+                while let Some(last) = line.pop() {
+                    if !last.is_silent() {
+                        line.push(last.trim_trailing_rests());
+                        break;
+                    }
+                }
+                Self::line(line)
+            }
+            Self::Parallel(m1, m2) => m1.trim_trailing_rests() | m2.trim_trailing_rests(),
+            Self::Modify(c, m) => m.trim_trailing_rests().with(c),
+        }
+    }
+
+    /// Let the final notes ring out longer
+    /// by lengthening the last simultaneous group of notes
+    /// (e.g. the final chord) by the `extra` duration.
+    ///
+    /// In contrast with the [fermata][super::phrase::Articulation::Fermata]
+    /// which prolongs the notes proportionally, the `extra` duration is absolute.
+    /// The trailing [rests][Primitive::Rest] are not affected
+    /// and the [parallel][Self::Parallel] voices ending earlier are preserved.
+    ///
+    /// The [`Music`] should be finite: the infinite [lazy line][Self::Lazy]
+    /// has no final notes, so looking for them never returns.
+    pub fn sustain_final(self, extra: Dur) -> Self {
+        match self {
+            Self::Prim(Primitive::Note(d, p)) => Self::note(d + extra, p),
+            rest @ Self::Prim(Primitive::Rest(_)) => rest,
+            line @ (Self::Sequential(..) | Self::Lazy(_)) => {
+                let mut line = Vec::from(line);
+                if let Some(last) = line.iter_mut().rfind(|m| !m.is_silent()) {
+                    *last = std::mem::replace(last, Self::rest(Dur::ZERO)).sustain_final(extra);
+                }
+                Self::line(line)
+            }
+            Self::Parallel(m1, m2) => {
+                let (end1, end2) = (m1.sounding_end(), m2.sounding_end());
+                let sustain = |m: Self, end: Option<Dur>| {
+                    if end == end1.max(end2) {
+                        m.sustain_final(extra)
+                    } else {
+                        m
+                    }
+                };
+                sustain(*m1, end1) | sustain(*m2, end2)
+            }
+            Self::Modify(Control::Tempo(r), m) => {
+                m.sustain_final(extra * r).with(Control::Tempo(r))
+            }
+            Self::Modify(c, m) => m.sustain_final(extra).with(c),
+        }
+    }
+
+    /// Round the duration of every note and rest to the nearest multiple
+    /// of the `grid` (e.g. [sixteenth][Dur::SIXTEENTH]) to clean up
+    /// the imported or generated rhythms.
+    ///
+    /// The notes and rests never disappear: a duration shorter than
+    /// a half of the `grid` is rounded up to a single `grid` unit.
+    /// The zero-length [rests][Primitive::Rest] used to terminate
+    /// the [lines][Self::line] are kept as is.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Quantization_(music)>
+    ///
+    /// # Panics
+    /// If the `grid` is zero.
+    pub fn quantize_durations(self, grid: Dur) -> Self {
+        assert!(grid > Dur::ZERO, "The quantization grid should be positive");
+
+        let quantize = move |dur: Dur| {
+            if dur == Dur::ZERO {
+                return dur;
+            }
+            let units = (dur.into_ratio::<DurT>() / grid.into_ratio())
+                .round()
+                .to_integer();
+            grid * units.max(1)
+        };
+
+        match self {
+            Self::Prim(Primitive::Note(d, p)) => Self::note(quantize(d), p),
+            Self::Prim(Primitive::Rest(d)) => Self::rest(quantize(d)),
+            Self::Sequential(m1, m2) => m1.quantize_durations(grid) + m2.quantize_durations(grid),
+            Self::Lazy(it) => Self::lazy_line(it.map(move |m| m.quantize_durations(grid))),
+            Self::Parallel(m1, m2) => m1.quantize_durations(grid) | m2.quantize_durations(grid),
+            Self::Modify(c, m) => m.quantize_durations(grid).with(c),
+        }
+    }
+
+    /// The time the last note of the [`Music`] stops sounding
+    /// (i.e. the [duration][Temporal::duration] without the trailing rests)
+    /// or `None` if the [`Music`] has no notes at all.
+    fn sounding_end(&self) -> Option<Dur> {
+        let seq = |(d1, end1): (Dur, Option<Dur>), (d2, end2): (Dur, Option<Dur>)| {
+            (d1 + d2, end2.map(|end| d1 + end).or(end1))
+        };
+        let (_, end) = self.fold_by_ref(
+            |prim| match prim {
+                Primitive::Note(d, _) => (*d, Some(*d)),
+                Primitive::Rest(d) => (*d, None),
+            },
+            seq,
+            ((Dur::ZERO, None), seq),
+            |(d1, end1), (d2, end2)| (d1.max(d2), end1.max(end2)),
+            |ctrl, (d, end)| {
+                if let Control::Tempo(r) = ctrl {
+                    (d / *r, end.map(|end| end / *r))
+                } else {
+                    (d, end)
+                }
+            },
+        );
+        end
+    }
+
+    fn is_silent(&self) -> bool {
+        self.fold_by_ref(
+            |prim| matches!(prim, Primitive::Rest(_)),
+            |s1, s2| s1 && s2,
+            (true, |s1, s2| s1 && s2),
+            |s1, s2| s1 && s2,
+            |_, s| s,
+        )
+    }
+}
+
+impl<P: Ord> Music<P> {
+    /// Bring the [`Music`] to the canonical form,
+    /// so the musically identical trees built in a different way
+    /// become equal and could be compared with `assert_eq!`:
+    /// - the [sequential][Self::Sequential] chains are right-associated;
+    /// - the adjacent [rests][Primitive::Rest] are merged into a single one
+    ///   and the zero-length rests are removed;
+    /// - the [parallel][Self::Parallel] voices are ordered by their first pitch
+    ///   and then by their whole sequences of notes and rests
+    ///   (the voices differing only by the [controls][Self::Modify] keep their order).
+    ///
+    /// The [`Music`] should be finite.
+    pub fn canonical(self) -> Self {
+        match self {
+            n @ Self::Prim(_) => n,
+            line @ (Self::Sequential(..) | Self::Lazy(_)) => {
+                let mut items: Vec<Self> = Vec::new();
+                for m in Vec::from(line).into_iter().map(Self::canonical) {
+                    match (items.last_mut(), m) {
+                        (_, Self::Prim(Primitive::Rest(Dur::ZERO))) => {}
+                        (
+                            Some(Self::Prim(Primitive::Rest(d1))),
+                            Self::Prim(Primitive::Rest(d2)),
+                        ) => *d1 = *d1 + d2,
+                        (_, m) => items.push(m),
+                    }
+                }
+                Self::from_line_items(items)
+            }
+            par @ Self::Parallel(..) => {
+                let mut voices: Vec<_> = par
+                    .into_voices()
+                    .into_iter()
+                    .map(Self::canonical)
+                    .filter(|m| !matches!(m, Self::Prim(Primitive::Rest(Dur::ZERO))))
+                    .collect();
+                // the voices without any pitch go last
+                voices.sort_by(|m1, m2| {
+                    let first_pitches = match (m1.first_pitch(), m2.first_pitch()) {
+                        (Some(p1), Some(p2)) => p1.cmp(p2),
+                        (p1, p2) => p1.is_none().cmp(&p2.is_none()),
+                    };
+                    first_pitches.then_with(|| m1.primitives().cmp(&m2.primitives()))
+                });
+                voices
+                    .into_iter()
+                    .rev()
+                    .reduce(|acc, m| m | acc)
+                    .unwrap_or_else(|| Self::rest(Dur::ZERO))
+            }
+            Self::Modify(c, m) => m.canonical().with(c),
+        }
+    }
+
+    fn into_voices(self) -> Vec<Self> {
+        match self {
+            Self::Parallel(m1, m2) => {
+                let mut voices = m1.into_voices();
+                voices.extend(m2.into_voices());
+                voices
+            }
+            other => vec![other],
+        }
+    }
+
+    fn first_pitch(&self) -> Option<&P> {
+        match self {
+            Self::Prim(Primitive::Note(_, p)) => Some(p),
+            Self::Prim(Primitive::Rest(_)) | Self::Lazy(_) => None,
+            Self::Sequential(m1, m2) | Self::Parallel(m1, m2) => {
+                m1.first_pitch().or_else(|| m2.first_pitch())
+            }
+            Self::Modify(_, m) => m.first_pitch(),
+        }
+    }
+
+    /// All the notes and rests of the [`Music`] in the order of the tree traversal,
+    /// ignoring the [lazy lines][Self::Lazy].
+    fn primitives(&self) -> Vec<&Primitive<P>> {
+        match self {
+            Self::Prim(p) => vec![p],
+            Self::Lazy(_) => vec![],
+            Self::Sequential(m1, m2) | Self::Parallel(m1, m2) => {
+                let mut prims = m1.primitives();
+                prims.extend(m2.primitives());
+                prims
+            }
+            Self::Modify(_, m) => m.primitives(),
+        }
+    }
 }
 
 impl<P: Clone> Music<P> {
@@ -126,6 +981,861 @@ impl<P: Clone> Music<P> {
     ///
     /// Also could be used in the form `Music * n`.
     pub fn times(&self, n: usize) -> Self {
-        Self::lazy_line(std::iter::repeat(self.clone()).take(n))
+        Self::lazy_line(iter::repeat(self.clone()).take(n))
+    }
+}
+
+fn shift_octave(pitch: Pitch, octaves: i8) -> Option<Pitch> {
+    let index = i8::try_from(pitch.octave().into_usize()).ok()? + octaves;
+    let index = usize::try_from(index).ok()?;
+    (index < Octave::LENGTH).then(|| Pitch::new(pitch.class(), Octave::from_usize(index)))
+}
+
+/// Choose the closest pitch of the scale for the [`Pitch`]
+/// considering its `[previous, next]` neighbours (see [`Music::conform_to_key`]).
+fn conform_pitch(pitch: Pitch, neighbours: [Option<AbsPitch>; 2], key: KeySig) -> Pitch {
+    let abs = pitch.abs();
+    if key.contains(abs) {
+        return pitch;
+    }
+
+    let [prev, next] = neighbours;
+    let distance = |a: AbsPitch, b: AbsPitch| a.get_u8().abs_diff(b.get_u8());
+    let octave = i8::try_from(u8::from(Octave::semitones_number())).expect("12 is low enough");
+    let lower = (1..=octave)
+        .map(|d| abs - Interval::from(d))
+        .find(|p| key.contains(*p));
+    let upper = (1..=octave)
+        .map(|d| abs + Interval::from(d))
+        .find(|p| key.contains(*p));
+
+    [lower, upper]
+        .into_iter()
+        .flatten()
+        .min_by_key(|&candidate| {
+            let repeats = Some(candidate) == prev || Some(candidate) == next;
+            let to_target = next.or(prev).map(|t| distance(candidate, t));
+            (distance(candidate, abs), repeats, to_target)
+        })
+        .map_or(pitch, |p| key.spell(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{p, Octave, Performable as _, PitchClass};
+
+    #[test]
+    fn invert_around_the_tonic() {
+        let m = Music::with_dur(vec![p!(C 4), p!(E 4), p!(G 4)], Dur::QUARTER);
+        let inverted = m.invert_around(p!(C 4));
+
+        assert_eq!(
+            inverted,
+            Music::with_dur(vec![p!(C 4), p!(Gs 3), p!(F 3)], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn invert_around_is_symmetric() {
+        let axis = p!(C 4);
+        let m = Music::with_dur(vec![p!(C 4), p!(E 4), p!(G 4)], Dur::QUARTER);
+        let inverted: Vec<_> = Vec::from(m.clone().invert_around(axis));
+
+        for (orig, inv) in Vec::from(m).into_iter().zip(inverted) {
+            match (orig, inv) {
+                (Music::Prim(Primitive::Note(_, p1)), Music::Prim(Primitive::Note(_, p2))) => {
+                    assert_eq!(p1.abs() - axis.abs(), axis.abs() - p2.abs());
+                }
+                _ => panic!("Notes expected"),
+            }
+        }
+    }
+
+    #[test]
+    fn invert_around_clips_out_of_range() {
+        let m = Music::with_dur(vec![p!(C 4), p!(C 9)], Dur::QUARTER);
+        let inverted = m.invert_around(p!(C 1));
+
+        assert_eq!(
+            inverted,
+            Music::with_dur(vec![Pitch::C(Octave::OctoContra); 2], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn echo_adds_delayed_quieter_voices() {
+        let m = Music::note(Dur::QUARTER, p!(C 4));
+        let echoed = m.echo(Dur::EIGHTH, 3, Ratio::new(1, 2));
+
+        let events: Vec<_> = echoed.perform().iter().collect();
+        assert_eq!(events.len(), 4);
+
+        let start_times: Vec<_> = events.iter().map(|e| e.start_time).collect();
+        // the eighth note lasts for 1/4 of a second
+        assert_eq!(start_times, [0, 1, 2, 3].map(|x| Ratio::new(x, 4)).to_vec());
+
+        let volumes: Vec<_> = events.iter().map(|e| e.volume).collect();
+        assert_eq!(volumes, [127, 63, 31, 15].map(Volume::from).to_vec());
+    }
+
+    #[test]
+    fn octave_jumble_keeps_pitch_classes() {
+        let scale: Vec<_> = p!(C 4).major_scale().collect();
+        let m = Music::with_dur(scale.clone(), Dur::EIGHTH);
+
+        let jumbled = m.clone().octave_jumble(42, 2);
+        let pitches: Vec<_> = Vec::from(jumbled.clone())
+            .into_iter()
+            .filter_map(|m| m.as_note().map(|(_, p)| *p))
+            .collect();
+        assert_eq!(pitches.len(), scale.len());
+        for (p, orig) in pitches.iter().zip(&scale) {
+            assert_eq!(p.class(), orig.class());
+            let shift = p.octave().into_usize().abs_diff(orig.octave().into_usize());
+            assert!(shift <= 2);
+        }
+        assert!(pitches != scale);
+
+        assert_eq!(jumbled, m.clone().octave_jumble(42, 2));
+        assert_eq!(m.clone().octave_jumble(42, 0), m);
+    }
+
+    #[test]
+    fn try_trans_in_range() {
+        let m = Music::with_dur(vec![p!(C 4), p!(E 4), p!(G 4)], Dur::QUARTER);
+        assert_eq!(
+            m.clone().try_trans(Interval::from(7)),
+            Ok(m.trans(Interval::from(7)))
+        );
+    }
+
+    #[test]
+    fn try_trans_reports_clipping() {
+        let top = Pitch::G(Octave::SixLined);
+        let m = Music::with_dur(vec![p!(C 4), top], Dur::QUARTER);
+        assert_eq!(
+            m.clone().try_trans(Interval::semi_tone()),
+            Err(ErrorPitchClipping::TooHigh)
+        );
+        assert_eq!(
+            m.try_trans(-Interval::from(61)),
+            Err(ErrorPitchClipping::TooLow)
+        );
+    }
+
+    #[test]
+    fn humanize_is_reproducible() {
+        let m = Music::with_dur(p!(C 4).major_scale().collect(), Dur::QUARTER);
+        let spread = Dur::THIRTY_SECOND;
+
+        let humanized = m.clone().humanize(7, spread);
+        assert_eq!(humanized, m.clone().humanize(7, spread));
+        assert_ne!(humanized, m.clone().humanize(8, spread));
+        assert_ne!(humanized, m);
+        assert_eq!(m.clone().humanize(7, Dur::ZERO), m);
+    }
+
+    #[test]
+    fn humanize_keeps_duration_and_bounds() {
+        let scale: Vec<_> = p!(C 4).major_scale().collect();
+        let m = Music::with_dur(scale.clone(), Dur::EIGHTH);
+        let spread = Dur::SIXTEENTH;
+
+        let humanized = m.clone().humanize(42, spread);
+        assert_eq!(humanized.duration(), m.duration());
+
+        let notes: Vec<_> = Vec::from(humanized)
+            .into_iter()
+            .filter_map(|m| m.as_note().map(|(d, p)| (d, *p)))
+            .collect();
+        assert_eq!(notes.len(), scale.len());
+        for ((d, p), orig) in notes.into_iter().zip(scale) {
+            assert_eq!(p, orig);
+            assert!(d <= Dur::EIGHTH);
+            assert!(d >= Dur::EIGHTH - spread.min(Dur::SIXTEENTH));
+        }
+    }
+
+    #[test]
+    fn octave_jumble_clamps_to_range() {
+        let m = Music::with_dur(vec![Pitch::C(Octave::OctoContra); 20], Dur::EIGHTH);
+        let jumbled = m.octave_jumble(1, 3);
+        for m in Vec::from(jumbled) {
+            let (_, p) = m.as_note().unwrap();
+            assert_eq!(p.class(), PitchClass::C);
+            assert!(p.abs_checked().is_ok());
+        }
+    }
+
+    #[test]
+    fn retrograde_pitches_keeps_rhythm() {
+        let oc4 = Octave::OneLined;
+        let m = Music::line(vec![
+            Music::C(oc4, Dur::EIGHTH),
+            Music::rest(Dur::SIXTEENTH),
+            Music::D(oc4, Dur::QUARTER),
+        ]);
+
+        assert_eq!(
+            m.retrograde_pitches().unwrap(),
+            Music::line(vec![
+                Music::D(oc4, Dur::EIGHTH),
+                Music::rest(Dur::SIXTEENTH),
+                Music::C(oc4, Dur::QUARTER),
+            ])
+        );
+    }
+
+    #[test]
+    fn retrograde_pitches_keeps_rests_in_place() {
+        let oc4 = Octave::OneLined;
+        let m = Music::line(vec![
+            Music::rest(Dur::EIGHTH),
+            Music::C(oc4, Dur::EIGHTH),
+            Music::D(oc4, Dur::QUARTER),
+            Music::E(oc4, Dur::HALF),
+        ]);
+
+        assert_eq!(
+            m.retrograde_pitches().unwrap(),
+            Music::line(vec![
+                Music::rest(Dur::EIGHTH),
+                Music::E(oc4, Dur::EIGHTH),
+                Music::D(oc4, Dur::QUARTER),
+                Music::C(oc4, Dur::HALF),
+            ])
+        );
+
+        let chord = Music::C(oc4, Dur::QUARTER) | Music::E(oc4, Dur::QUARTER);
+        assert!((Music::C(oc4, Dur::QUARTER) + chord)
+            .retrograde_pitches()
+            .is_none());
+    }
+
+    #[test]
+    fn accent_every_other_note() {
+        let m = Music::line(vec![
+            Music::C(Octave::OneLined, Dur::EIGHTH),
+            Music::D(Octave::OneLined, Dur::EIGHTH),
+            Music::rest(Dur::EIGHTH),
+            Music::E(Octave::OneLined, Dur::EIGHTH),
+            Music::F(Octave::OneLined, Dur::EIGHTH),
+        ]);
+        let accented = m.accent_pattern(&[true, false]);
+
+        let volumes: Vec<_> = accented.perform().iter().map(|e| e.volume).collect();
+        let (ff, mf) = (
+            StdLoudness::Fortissimo.get_volume(),
+            StdLoudness::MezzoForte.get_volume(),
+        );
+        assert_eq!(volumes, [ff, mf, ff, mf]);
+    }
+
+    #[test]
+    fn echo_without_repeats() {
+        let m = Music::with_dur(vec![p!(C 4), p!(E 4)], Dur::QUARTER);
+        assert_eq!(
+            m.clone().echo(Dur::EIGHTH, 0, Ratio::new(1, 2)),
+            m.with_volume(Volume::loudest())
+        );
+    }
+
+    #[test]
+    fn chromatic_line_snapped_to_c_major() {
+        let chromatic: Vec<_> = (0..12).map(|i| p!(C 4).trans(Interval::from(i))).collect();
+        let m = Music::with_dur(chromatic, Dur::EIGHTH).snap_to_scale(KeySig::default());
+
+        let white_keys = [
+            PitchClass::C,
+            PitchClass::D,
+            PitchClass::E,
+            PitchClass::F,
+            PitchClass::G,
+            PitchClass::A,
+            PitchClass::B,
+        ];
+        for m in Vec::from(m) {
+            if let Music::Prim(Primitive::Note(_, p)) = m {
+                assert!(white_keys.contains(&p.class()), "{p:?}");
+            } else {
+                panic!("Notes expected");
+            }
+        }
+    }
+
+    #[test]
+    fn snap_to_scale_spells_in_key() {
+        let m = Music::with_dur(vec![p!(A 4), p!(B 4)], Dur::EIGHTH);
+        let snapped = m.snap_to_scale(KeySig::Major(PitchClass::F));
+        assert_eq!(
+            snapped,
+            Music::with_dur(vec![p!(A 4), p!(Bf 4)], Dur::EIGHTH)
+        );
+    }
+
+    #[test]
+    fn conform_to_key_resolves_stepwise() {
+        let key = KeySig::default();
+        let m = Music::with_dur(vec![p!(D 4), p!(Ef 4), p!(F 4)], Dur::EIGHTH);
+        assert_eq!(
+            m.clone().conform_to_key(key),
+            Music::with_dur(vec![p!(D 4), p!(E 4), p!(F 4)], Dur::EIGHTH)
+        );
+        // the simple snapping creates the repeated note instead
+        assert_eq!(
+            m.snap_to_scale(key),
+            Music::with_dur(vec![p!(D 4), p!(D 4), p!(F 4)], Dur::EIGHTH)
+        );
+
+        let passing = Music::with_dur(vec![p!(E 4), p!(Fs 4), p!(G 4)], Dur::EIGHTH);
+        assert_eq!(
+            passing.conform_to_key(key),
+            Music::with_dur(vec![p!(E 4), p!(F 4), p!(G 4)], Dur::EIGHTH)
+        );
+    }
+
+    #[test]
+    fn conform_to_key_skips_rests_looking_for_neighbours() {
+        let m = Music::line(vec![
+            Music::note(Dur::QUARTER, p!(C 4)),
+            Music::rest(Dur::QUARTER),
+            Music::note(Dur::QUARTER, p!(Cs 4)),
+            Music::note(Dur::QUARTER, p!(C 4)),
+        ]);
+        let pitches: Vec<_> = Vec::from(m.conform_to_key(KeySig::default()))
+            .into_iter()
+            .filter_map(|m| match m {
+                Music::Prim(Primitive::Note(_, p)) => Some(p),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pitches, [p!(C 4), p!(D 4), p!(C 4)]);
+    }
+
+    #[test]
+    fn retrograde_bounded_lazy_line() {
+        let m = Music::with_dur_lazy([p!(C 4), p!(D 4), p!(E 4)].into_iter(), Dur::QUARTER);
+        let reversed = m.retrograde_bounded(3).unwrap();
+        assert_eq!(
+            Vec::from(reversed),
+            Vec::from(Music::with_dur(
+                vec![p!(E 4), p!(D 4), p!(C 4)],
+                Dur::QUARTER
+            ))
+        );
+    }
+
+    #[test]
+    fn retrograde_bounded_exceeding_line() {
+        let finite = Music::with_dur_lazy([p!(C 4), p!(D 4), p!(E 4)].into_iter(), Dur::QUARTER);
+        assert!(finite.retrograde_bounded(2).is_err());
+
+        let infinite = Music::with_dur_lazy(iter::repeat(p!(C 4)), Dur::QUARTER);
+        assert!(infinite.retrograde_bounded(100).is_err());
+    }
+
+    #[test]
+    fn envelope_interpolates_between_breakpoints() {
+        let m = Music::with_dur(
+            vec![p!(C 4), p!(D 4), p!(E 4), p!(F 4), p!(G 4)],
+            Dur::QUARTER,
+        );
+        let m = m.envelope(&[
+            (Dur::ZERO, Volume::from(20)),
+            (Dur::WHOLE, Volume::from(100)),
+        ]);
+        let volumes: Vec<_> = Vec::from(m)
+            .into_iter()
+            .filter_map(|m| match m {
+                Music::Prim(Primitive::Note(_, (_, vol))) => Some(u8::from(vol.get_inner())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(volumes, [20, 40, 60, 80, 100]);
+    }
+
+    #[test]
+    fn envelope_decreasing_and_clamped() {
+        let m = Music::with_dur(vec![p!(C 4), p!(D 4), p!(E 4), p!(F 4)], Dur::HALF);
+        let m = m.envelope(&[
+            (Dur::HALF, Volume::from(90)),
+            (Dur::from(3) / 2, Volume::from(30)),
+        ]);
+        let volumes: Vec<_> = Vec::from(m)
+            .into_iter()
+            .filter_map(|m| match m {
+                Music::Prim(Primitive::Note(_, (_, vol))) => Some(u8::from(vol.get_inner())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(volumes, [90, 90, 60, 30]);
+    }
+
+    #[test]
+    fn scale_degrees_of_line() {
+        let m = Music::with_dur(
+            vec![
+                p!(C 4),
+                p!(D 4),
+                p!(E 4),
+                p!(F 4),
+                p!(G 4),
+                p!(A 4),
+                p!(B 4),
+                p!(C 5),
+            ],
+            Dur::EIGHTH,
+        );
+        assert_eq!(
+            m.scale_degrees(KeySig::default()),
+            [1, 2, 3, 4, 5, 6, 7, 1].map(Some)
+        );
+
+        let m = Music::line(vec![
+            Music::note(Dur::QUARTER, p!(G 4)),
+            Music::rest(Dur::QUARTER),
+            Music::note(Dur::QUARTER, p!(Fs 4)),
+        ]);
+        assert_eq!(m.scale_degrees(KeySig::default()), [Some(5), None]);
+        assert_eq!(
+            m.scale_degrees(KeySig::Major(PitchClass::G)),
+            [Some(1), Some(7)]
+        );
+    }
+
+    #[test]
+    fn harmonize_in_diatonic_thirds() {
+        let melody = Music::with_dur(vec![p!(C 4), p!(D 4), p!(E 4), p!(F 4)], Dur::QUARTER);
+        let harmony = melody.clone().harmonize_diatonic(KeySig::default(), 2);
+        assert_eq!(
+            harmony,
+            melody | Music::with_dur(vec![p!(E 4), p!(F 4), p!(G 4), p!(A 4)], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn harmonize_in_parallel_fifths() {
+        let melody = Music::with_dur(vec![p!(C 4), p!(D 4)], Dur::QUARTER);
+        let harmony = melody.clone().harmonize(Interval::from(7));
+        assert_eq!(
+            harmony,
+            melody | Music::with_dur(vec![p!(G 4), p!(A 4)], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn find_clipped_after_transposition() {
+        let m = Music::with_dur(vec![p!(C 8), p!(G 8), p!(C 9)], Dur::QUARTER);
+        assert!(m.find_clipped().is_empty());
+
+        let up = m.with_transpose(Interval::octave());
+        assert_eq!(up.find_clipped(), [(2, ErrorPitchClipping::TooHigh)]);
+
+        // the nested transpositions are summed up before clipping
+        let back = up.clone().with_transpose(-Interval::octave());
+        assert!(back.find_clipped().is_empty());
+
+        let low = Music::with_dur(vec![p!(C 0), p!(B 0)], Dur::QUARTER)
+            .with_transpose(Interval::from(-25));
+        assert_eq!(
+            (low | up).find_clipped(),
+            [
+                (0, ErrorPitchClipping::TooLow),
+                (1, ErrorPitchClipping::TooLow),
+                (4, ErrorPitchClipping::TooHigh)
+            ]
+        );
+    }
+
+    #[test]
+    fn transpose_diatonic_triad() {
+        let triad = Music::chord(vec![
+            Music::note(Dur::HALF, p!(C 4)),
+            Music::note(Dur::HALF, p!(E 4)),
+            Music::note(Dur::HALF, p!(G 4)),
+        ]);
+
+        assert_eq!(
+            triad.transpose_diatonic(KeySig::default(), 2),
+            Music::chord(vec![
+                Music::note(Dur::HALF, p!(E 4)),
+                Music::note(Dur::HALF, p!(G 4)),
+                Music::note(Dur::HALF, p!(B 4)),
+            ])
+        );
+    }
+
+    #[test]
+    fn transpose_diatonic_spells_in_key() {
+        let m = Music::with_dur(vec![p!(F 4), p!(G 4)], Dur::QUARTER);
+        assert_eq!(
+            m.transpose_diatonic(KeySig::Major(PitchClass::F), 3),
+            Music::with_dur(vec![p!(Bf 4), p!(C 5)], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn fit_too_high_line_down_by_octave() {
+        let m = Music::with_dur(vec![p!(E 5), p!(G 5), p!(Cs 6)], Dur::QUARTER);
+        let fitted = m.fit_to_range(p!(C 4), p!(C 6));
+
+        assert_eq!(
+            fitted,
+            Music::with_dur(vec![p!(E 4), p!(G 4), p!(Cs 5)], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn fit_line_already_in_range() {
+        let m = Music::with_dur(vec![p!(C 4), p!(E 4), p!(G 4)], Dur::QUARTER);
+        assert_eq!(m.clone().fit_to_range(p!(C 4), p!(C 5)), m);
+    }
+
+    #[test]
+    fn fit_too_wide_line_minimizes_out_of_range_notes() {
+        let m = Music::with_dur(vec![p!(C 2), p!(C 5), p!(D 5), p!(E 5)], Dur::QUARTER);
+        let fitted = m.fit_to_range(p!(C 3), p!(G 4));
+
+        // the whole line is shifted as a unit, so the lowest note falls out
+        assert_eq!(
+            fitted,
+            Music::with_dur(vec![p!(C 1), p!(C 4), p!(D 4), p!(E 4)], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn flatten_transposed_line() {
+        let m = Music::with_dur(vec![p!(C 4), p!(E 4), p!(G 4)], Dur::QUARTER)
+            .with_transpose(Interval::from(2))
+            .with_transpose(Interval::from(5));
+
+        assert_eq!(
+            m.flatten_controls(),
+            Music::with_dur(vec![p!(G 4), p!(B 4), p!(D 5)], Dur::QUARTER)
+        );
+    }
+
+    #[test]
+    fn flatten_tempo_and_keep_instrument() {
+        use crate::midi::Instrument;
+
+        let m = (Music::note(Dur::HALF, p!(C 4)).with_tempo(2) + Music::rest(Dur::QUARTER))
+            .with_instrument(Instrument::Violin)
+            .with_tempo(Ratio::new(1, 2));
+        let flat = m.clone().flatten_controls();
+
+        assert_eq!(
+            flat,
+            (Music::note(Dur::HALF, p!(C 4)) + Music::rest(Dur::HALF))
+                .with_instrument(Instrument::Violin)
+        );
+        assert_eq!(
+            m.perform().iter().collect::<Vec<_>>(),
+            flat.perform().iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn keep_every_second_note() {
+        let m = Music::with_dur(vec![p!(C 4), p!(D 4), p!(E 4), p!(F 4)], Dur::QUARTER);
+        let thinned = m.keep_every_nth(2);
+
+        assert_eq!(
+            thinned,
+            Music::line(vec![
+                Music::note(Dur::QUARTER, p!(C 4)),
+                Music::rest(Dur::QUARTER),
+                Music::note(Dur::QUARTER, p!(E 4)),
+                Music::rest(Dur::QUARTER),
+            ])
+        );
+        assert_eq!(thinned.duration(), Dur::WHOLE);
+    }
+
+    #[test]
+    fn keep_every_nth_skips_rests() {
+        let m = Music::line(vec![
+            Music::note(Dur::QUARTER, p!(C 4)),
+            Music::rest(Dur::EIGHTH),
+            Music::note(Dur::QUARTER, p!(D 4)),
+            Music::note(Dur::HALF, p!(E 4)),
+        ]);
+
+        assert_eq!(
+            m.clone().keep_every_nth(2),
+            Music::line(vec![
+                Music::note(Dur::QUARTER, p!(C 4)),
+                Music::rest(Dur::EIGHTH),
+                Music::rest(Dur::QUARTER),
+                Music::note(Dur::HALF, p!(E 4)),
+            ])
+        );
+        assert_eq!(m.clone().keep_every_nth(1), m);
+    }
+
+    #[test]
+    fn trim_two_trailing_rests() {
+        let m = Music::line(vec![
+            Music::note(Dur::QUARTER, p!(C 4)),
+            Music::rest(Dur::EIGHTH),
+            Music::note(Dur::QUARTER, p!(D 4)),
+            Music::rest(Dur::QUARTER),
+            Music::rest(Dur::HALF),
+        ]);
+        assert_eq!(m.duration(), Dur::from(Ratio::new(11, 8)));
+
+        let trimmed = m.trim_trailing_rests();
+        assert_eq!(
+            trimmed,
+            Music::line(vec![
+                Music::note(Dur::QUARTER, p!(C 4)),
+                Music::rest(Dur::EIGHTH),
+                Music::note(Dur::QUARTER, p!(D 4)),
+            ])
+        );
+        assert_eq!(trimmed.duration(), Dur::from(Ratio::new(5, 8)));
+    }
+
+    #[test]
+    fn trim_trailing_silent_parts() {
+        let silent_chord = Music::rest(Dur::HALF) | Music::rest(Dur::QUARTER);
+        let voice = Music::note(Dur::QUARTER, p!(C 4)) + Music::rest(Dur::HALF);
+        let m = Music::line(vec![
+            Music::note(Dur::QUARTER, p!(E 4)),
+            voice | Music::note(Dur::HALF, p!(G 4)),
+            silent_chord,
+        ]);
+
+        let trimmed = m.trim_trailing_rests();
+        assert_eq!(trimmed.duration(), Dur::from(Ratio::new(3, 4)));
+    }
+
+    #[test]
+    fn quantize_dotted_eighth() {
+        let m = Music::note(Dur::DOTTED_EIGHTH, p!(C 4));
+        assert_eq!(
+            m.clone().quantize_durations(Dur::SIXTEENTH),
+            Music::note(Dur::new(3, 16), p!(C 4))
+        );
+        // 1.5 of eighth rounded to the nearest 2 eighths
+        assert_eq!(
+            m.quantize_durations(Dur::EIGHTH),
+            Music::note(Dur::QUARTER, p!(C 4))
+        );
+    }
+
+    #[test]
+    fn quantize_keeps_short_notes_and_rests() {
+        let m = Music::line(vec![
+            Music::note(Dur::THIRTY_SECOND, p!(C 4)),
+            Music::rest(Dur::new(1, 12)),
+            Music::note(Dur::new(5, 12), p!(E 4)),
+        ]);
+
+        let q = m.quantize_durations(Dur::SIXTEENTH);
+        assert_eq!(
+            Vec::from(q),
+            vec![
+                Music::note(Dur::SIXTEENTH, p!(C 4)),
+                Music::rest(Dur::SIXTEENTH),
+                Music::note(Dur::new(7, 16), p!(E 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_two_voices() {
+        let soprano = Music::with_dur(vec![p!(E 5), p!(D 5), p!(C 5)], Dur::QUARTER);
+        let bass = Music::with_dur(vec![p!(C 3), p!(G 2), p!(C 3)], Dur::QUARTER);
+        let m = (soprano.clone() | bass.clone().with_tempo(2)).with_transpose(Interval::tone());
+
+        let voices = m.split_voices();
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices[0], soprano.with_transpose(Interval::tone()));
+        assert_eq!(
+            voices[1],
+            bass.with_tempo(2).with_transpose(Interval::tone())
+        );
+        assert!(Music::chord(voices).performs_same_as(&m));
+    }
+
+    #[test]
+    fn split_nested_voices() {
+        let m = (Music::C(Octave::OneLined, Dur::HALF) | Music::E(Octave::OneLined, Dur::HALF))
+            | Music::G(Octave::OneLined, Dur::HALF);
+        assert_eq!(m.split_voices().len(), 3);
+
+        let melody = Music::with_dur(vec![p!(C 4), p!(E 4)], Dur::QUARTER);
+        assert_eq!(melody.split_voices(), vec![melody]);
+    }
+
+    #[test]
+    fn balance_pads_the_shorter_voice() {
+        let whole = Music::C(Octave::OneLined, Dur::WHOLE);
+        let half = Music::E(Octave::OneLined, Dur::HALF);
+
+        let balanced = (whole.clone() | half.clone()).balance();
+        assert_eq!(
+            balanced,
+            whole.clone() | (half.clone() + Music::rest(Dur::HALF))
+        );
+
+        let balanced = (half.clone() | whole.clone()).balance();
+        assert_eq!(balanced, (half + Music::rest(Dur::HALF)) | whole);
+    }
+
+    #[test]
+    fn pad_half_note_line_to_whole() {
+        let oc4 = Octave::OneLined;
+        let line = Music::line(vec![
+            Music::C(oc4, Dur::QUARTER),
+            Music::E(oc4, Dur::QUARTER),
+        ]);
+
+        let padded = line.clone().pad_to(Dur::WHOLE);
+        assert_eq!(padded.duration(), Dur::WHOLE);
+        assert_eq!(padded, line.clone() + Music::rest(Dur::HALF));
+
+        assert_eq!(line.clone().pad_to(Dur::HALF), line);
+        assert_eq!(line.clone().pad_to(Dur::QUARTER), line);
+    }
+
+    #[test]
+    fn balance_nested_voices() {
+        let m = (Music::C(Octave::OneLined, Dur::WHOLE) | Music::E(Octave::OneLined, Dur::HALF))
+            | Music::G(Octave::OneLined, Dur::QUARTER);
+        let balanced = m.balance();
+        assert_eq!(balanced.duration(), Dur::WHOLE);
+        assert!(balanced
+            .split_voices()
+            .iter()
+            .all(|v| v.duration() == Dur::WHOLE));
+    }
+
+    #[test]
+    fn canonical_regroups_the_line() {
+        let c = Music::C(Octave::OneLined, Dur::QUARTER);
+        let e = Music::E(Octave::OneLined, Dur::QUARTER);
+        let g = Music::G(Octave::OneLined, Dur::QUARTER);
+
+        let left = (c.clone() + e.clone()) + g.clone();
+        let right = c.clone() + (e.clone() + g.clone());
+        let line = Music::line(vec![c.clone(), e.clone(), g.clone()]);
+        let lazy = Music::lazy_line(vec![c.clone(), e.clone(), g.clone()].into_iter());
+
+        assert_ne!(left, right);
+        assert_eq!(left.clone().canonical(), right.clone().canonical());
+        assert_eq!(line.canonical(), right.clone().canonical());
+        assert_eq!(lazy.canonical(), right.canonical());
+        assert_eq!(left.canonical(), c + (e + g));
+    }
+
+    #[test]
+    fn canonical_merges_rests() {
+        let c = Music::C(Octave::OneLined, Dur::QUARTER);
+        let m1 = Music::rest(Dur::EIGHTH) + (Music::rest(Dur::EIGHTH) + c.clone());
+        let m2 = (Music::rest(Dur::QUARTER) + Music::rest(Dur::ZERO)) + c.clone();
+
+        assert_eq!(m1.clone().canonical(), m2.canonical());
+        assert_eq!(m1.canonical(), Music::rest(Dur::QUARTER) + c);
+    }
+
+    #[test]
+    fn canonical_orders_voices() {
+        let c = Music::C(Octave::OneLined, Dur::HALF);
+        let e = Music::E(Octave::OneLined, Dur::HALF);
+        let g = Music::G(Octave::OneLined, Dur::HALF);
+
+        let m1 = (g.clone() | c.clone()) | e.clone();
+        let m2 = e.clone() | (c.clone() | g.clone());
+        let m3 = Music::chord(vec![c.clone(), e.clone(), g.clone()]);
+
+        assert_eq!(m1.clone().canonical(), m2.canonical());
+        assert_eq!(m1.clone().canonical(), m3.canonical());
+        assert_eq!(m1.canonical(), c | (e | g));
+    }
+
+    #[test]
+    fn canonical_orders_voices_with_the_same_first_pitch() {
+        let c = Music::C(Octave::OneLined, Dur::QUARTER);
+        let d = Music::D(Octave::OneLined, Dur::QUARTER);
+        let e = Music::E(Octave::OneLined, Dur::QUARTER);
+
+        let c_d = c.clone() + d;
+        let c_e = c + e;
+        let m1 = c_e.clone() | c_d.clone();
+        let m2 = c_d.clone() | c_e.clone();
+        assert_eq!(m1.clone().canonical(), m2.canonical());
+        assert_eq!(m1.canonical(), c_d | c_e);
+    }
+
+    #[test]
+    fn sustain_final_chord() {
+        let chord = Music::chord(vec![
+            Music::C(Octave::OneLined, Dur::HALF),
+            Music::E(Octave::OneLined, Dur::HALF),
+            Music::G(Octave::OneLined, Dur::HALF),
+        ]);
+        let m = Music::with_dur(vec![p!(D 4), p!(B 3)], Dur::QUARTER) + chord;
+        let original: Vec<_> = m.clone().perform().iter().collect();
+
+        let sustained: Vec<_> = m.sustain_final(Dur::QUARTER).perform().iter().collect();
+        assert_eq!(sustained.len(), original.len());
+        assert_eq!(sustained[..2], original[..2]);
+        for (e, orig) in sustained[2..].iter().zip(&original[2..]) {
+            assert_eq!(e.start_time, orig.start_time);
+            // the quarter note lasts 1/2 second
+            assert_eq!(e.duration, orig.duration + Ratio::new(1, 2));
+        }
+    }
+
+    #[test]
+    fn sustain_final_keeps_trailing_rests_and_short_voices() {
+        let short = Music::E(Octave::OneLined, Dur::QUARTER);
+        let long = Music::C(Octave::OneLined, Dur::HALF) + Music::rest(Dur::QUARTER);
+        let m = short.clone() | long;
+
+        let sustained = m.sustain_final(Dur::EIGHTH);
+        assert_eq!(
+            sustained.remove_zeros(),
+            short
+                | (Music::C(Octave::OneLined, Dur::HALF + Dur::EIGHTH) + Music::rest(Dur::QUARTER))
+        );
+    }
+
+    #[test]
+    fn sustain_final_picks_the_voice_sounding_last() {
+        let long = Music::C(Octave::OneLined, Dur::WHOLE);
+        let short = Music::E(Octave::OneLined, Dur::QUARTER) + Music::rest(Dur::WHOLE);
+        let m = long | short.clone();
+
+        let sustained = m.sustain_final(Dur::EIGHTH);
+        assert_eq!(
+            sustained.remove_zeros(),
+            Music::C(Octave::OneLined, Dur::WHOLE + Dur::EIGHTH) | short
+        );
+    }
+
+    #[test]
+    fn contour_of_major_scale() {
+        let scale: Vec<_> = p!(C 4).major_scale().collect();
+        let m = Music::with_dur(scale, Dur::EIGHTH);
+
+        let contour = m.contour();
+        assert_eq!(contour, [2, 2, 1, 2, 2, 2, 1].map(Interval::from));
+        assert_eq!(Music::from_contour(p!(C 4), &contour, Dur::EIGHTH), m);
+    }
+
+    #[test]
+    fn contour_skips_rests() {
+        let m = Music::line(vec![
+            Music::C(Octave::OneLined, Dur::QUARTER),
+            Music::rest(Dur::QUARTER),
+            Music::G(Octave::Small, Dur::QUARTER),
+        ]);
+        assert_eq!(m.contour(), [Interval::from(-5)]);
+        assert!(Music::rest(Dur::WHOLE).contour().is_empty());
     }
 }
@@ -0,0 +1,148 @@
+//! Exporting the [`Performance`] as a plain list of [`Event`]s
+//! for the interchange with the other tools.
+//!
+//! See more:
+//! - <https://en.wikipedia.org/wiki/Comma-separated_values>
+//! - <https://en.wikipedia.org/wiki/JSON>
+use std::fmt::Write as _;
+
+use num_rational::Ratio;
+
+use crate::music::perf::{Event, Performance};
+
+const CSV_HEADER: &str = "start,duration,pitch,volume,instrument";
+
+impl Performance {
+    /// Serialize the finite [`Performance`] into the JSON array
+    /// with a single object for every [`Event`], e.g.:
+    ///
+    /// ```json
+    /// [{"start":0,"duration":0.5,"pitch":60,"volume":127,"instrument":"AcousticGrandPiano"}]
+    /// ```
+    ///
+    /// The `start` and `duration` fields are measured in seconds.
+    pub fn to_event_json(&self) -> String {
+        let records: Vec<_> = self.iter().map(|e| e.to_json()).collect();
+        format!("[{}]", records.join(","))
+    }
+
+    /// Serialize the finite [`Performance`] into the CSV table
+    /// with a header and a single row for every [`Event`], e.g.:
+    ///
+    /// ```csv
+    /// start,duration,pitch,volume,instrument
+    /// 0,0.5,60,127,AcousticGrandPiano
+    /// ```
+    ///
+    /// The `start` and `duration` columns are measured in seconds.
+    pub fn to_csv(&self) -> String {
+        self.iter().fold(format!("{CSV_HEADER}\n"), |mut csv, e| {
+            csv.push_str(&e.to_csv_row());
+            csv.push('\n');
+            csv
+        })
+    }
+}
+
+impl Event {
+    fn to_json(&self) -> String {
+        format!(
+            r#"{{"start":{},"duration":{},"pitch":{},"volume":{},"instrument":{}}}"#,
+            seconds(self.start_time),
+            seconds(self.duration),
+            u8::from(self.pitch.get_inner()),
+            u8::from(self.volume.get_inner()),
+            json_string(&self.instrument.to_string()),
+        )
+    }
+
+    fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            seconds(self.start_time),
+            seconds(self.duration),
+            u8::from(self.pitch.get_inner()),
+            u8::from(self.volume.get_inner()),
+            csv_field(&self.instrument.to_string()),
+        )
+    }
+}
+
+fn seconds(t: Ratio<u32>) -> f64 {
+    f64::from(*t.numer()) / f64::from(*t.denom())
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", u32::from(c));
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{instruments::InstrumentName, music::Music, n, Performable as _};
+
+    fn two_notes() -> Performance {
+        let m: Music = Music::from(n!(C 4 / 4)) + Music::from(n!(E 4 / 8));
+        m.perform()
+    }
+
+    #[test]
+    fn json_records() {
+        assert_eq!(
+            two_notes().to_event_json(),
+            concat!(
+                "[",
+                r#"{"start":0,"duration":0.5,"pitch":60,"volume":127,"instrument":"AcousticGrandPiano"},"#,
+                r#"{"start":0.5,"duration":0.25,"pitch":64,"volume":127,"instrument":"AcousticGrandPiano"}"#,
+                "]"
+            )
+        );
+    }
+
+    #[test]
+    fn csv_rows() {
+        assert_eq!(
+            two_notes().to_csv(),
+            "start,duration,pitch,volume,instrument\n\
+             0,0.5,60,127,AcousticGrandPiano\n\
+             0.5,0.25,64,127,AcousticGrandPiano\n"
+        );
+    }
+
+    #[test]
+    fn empty_performance() {
+        let perf = Music::<crate::Pitch>::rest(crate::Dur::WHOLE).perform();
+        assert_eq!(perf.to_event_json(), "[]");
+        assert_eq!(perf.to_csv(), format!("{CSV_HEADER}\n"));
+    }
+
+    #[test]
+    fn escape_custom_instrument() {
+        let name = InstrumentName::from(String::from(r#"my "synth", v2"#));
+        assert_eq!(json_string(&name.to_string()), r#""my \"synth\", v2""#);
+        assert_eq!(csv_field(&name.to_string()), r#""my ""synth"", v2""#);
+    }
+}
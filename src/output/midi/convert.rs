@@ -2,23 +2,28 @@
 
 #![cfg_attr(not(feature = "play-midi"), allow(dead_code))]
 
-use std::{fmt, iter, time::Duration};
+use std::{collections::BTreeMap as Map, fmt, iter, time::Duration};
 
 use itertools::Itertools as _;
 use midly::{
-    num::u15, Format, Fps, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent,
+    num::{u15, u4, u7},
+    Format, Fps, Header, MetaMessage, MidiMessage, PitchBend, Smf, Timing, TrackEvent,
     TrackEventKind,
 };
-use num_traits::{CheckedAdd, CheckedMul};
+use num_rational::Ratio;
+use num_traits::{CheckedAdd, CheckedMul, Zero as _};
 
 use crate::{
     instruments::InstrumentName,
-    music::perf::{Event, Performance},
-    prim::volume::Volume,
+    music::{
+        perf::{Event, Item, Performance, TimePoint},
+        phrase::Vibrato,
+    },
+    prim::{interval::Octave, scale::KeySig, volume::Volume},
     utils::iter::{append_with_last, merge_pairs_by, partition, LazyList},
 };
 
-use super::{Channel, ProgNum, UserPatchMap};
+use super::{Channel, ProgNum, UserPatchMap, VelocityCurve};
 
 pub(super) fn into_relative_time<'t>(
     track: impl Iterator<Item = TimedMessage<'t, u32>>,
@@ -40,7 +45,31 @@ impl Performance {
     /// Optionally, the [patch map][UserPatchMap] could be provided to
     /// explicitly assign MIDI channels to instruments.
     pub fn into_midi(self, user_patch: Option<UserPatchMap>) -> Result<Smf<'static>, Error> {
-        let (tracks, timing) = self.into_lazy_midi(user_patch);
+        self.into_midi_with_velocity(user_patch, VelocityCurve::default())
+    }
+
+    /// Convert the [`Performance`] into the MIDI stream representation
+    /// using the given [`VelocityCurve`] to map the volumes of the notes.
+    ///
+    /// See more details in the [`Self::into_midi`].
+    pub fn into_midi_with_velocity(
+        self,
+        user_patch: Option<UserPatchMap>,
+        velocity: VelocityCurve,
+    ) -> Result<Smf<'static>, Error> {
+        self.into_midi_with_resolution(user_patch, velocity, DEFAULT_TIME_DIV)
+    }
+
+    /// Convert the [`Performance`] into the MIDI stream representation
+    /// with the given number of ticks per quarter note.
+    pub(super) fn into_midi_with_resolution(
+        self,
+        user_patch: Option<UserPatchMap>,
+        velocity: VelocityCurve,
+        ticks_per_quarter: u15,
+    ) -> Result<Smf<'static>, Error> {
+        let (tracks, timing) =
+            self.into_lazy_midi_with_resolution(user_patch, velocity, ticks_per_quarter);
         let tracks: Result<Vec<_>, _> = tracks.collect();
         let tracks: Vec<_> = tracks?.into_iter().map(Iterator::collect).collect();
 
@@ -67,48 +96,132 @@ impl Performance {
         impl Iterator<Item = Result<Box<dyn Iterator<Item = TrackEvent<'static>> + 'a>, Error>> + 'a,
         Timing,
     ) {
-        let mut user_patch = user_patch.unwrap_or_default();
+        self.into_lazy_midi_with_velocity(user_patch, VelocityCurve::default())
+    }
 
-        let split = self.split_by_instruments();
-        let stream = split.map(move |(i, p)| {
-            let (channel, program) = user_patch.get_or_insert(i)?;
+    /// Convert the [`Performance`] into the MIDI stream representation
+    /// using the given [`VelocityCurve`] to map the volumes of the notes.
+    ///
+    /// See more details in the [`Self::into_lazy_midi`].
+    pub fn into_lazy_midi_with_velocity<'a>(
+        self,
+        user_patch: Option<UserPatchMap>,
+        velocity: VelocityCurve,
+    ) -> (
+        impl Iterator<Item = Result<Box<dyn Iterator<Item = TrackEvent<'static>> + 'a>, Error>> + 'a,
+        Timing,
+    ) {
+        self.into_lazy_midi_with_resolution(user_patch, velocity, DEFAULT_TIME_DIV)
+    }
 
-            let track = into_relative_time(p.as_midi_track(channel, program));
-            let track = track.chain(iter::once(TrackEvent {
-                delta: 0.into(),
-                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
-            }));
+    fn into_lazy_midi_with_resolution<'a>(
+        self,
+        user_patch: Option<UserPatchMap>,
+        velocity: VelocityCurve,
+        ticks_per_quarter: u15,
+    ) -> (
+        impl Iterator<Item = Result<Box<dyn Iterator<Item = TrackEvent<'static>> + 'a>, Error>> + 'a,
+        Timing,
+    ) {
+        let ticks_per_second = u32::from(u16::from(ticks_per_quarter)) * BEATS_PER_SECOND;
+        let mut user_patch = user_patch.unwrap_or_default();
 
-            let ret: Box<dyn Iterator<Item = TrackEvent<'_>>> = Box::new(track);
-            Ok(ret)
+        let end_time = self.end_time();
+        // the track ends right after its last event, unless the explicit end provided
+        let end_tick = end_time
+            .and_then(|t| to_ticks(t, ticks_per_second))
+            .unwrap_or(0);
+        let with_end = move |track: Box<dyn Iterator<Item = TimedMessage<'static>> + 'a>| {
+            let track = track
+                .chain(iter::once((
+                    end_tick,
+                    TrackEventKind::Meta(MetaMessage::EndOfTrack),
+                )))
+                .scan(0, |last, (t, kind)| {
+                    *last = t.max(*last);
+                    Some((*last, kind))
+                });
+            let ret: Box<dyn Iterator<Item = TrackEvent<'_>>> = Box::new(into_relative_time(track));
+            ret
+        };
+
+        let mut split = self.split_by_instruments().peekable();
+        // the silent performance still needs a track to mark its end
+        let silent_track = (end_time.is_some() && split.peek().is_none()).then(|| {
+            let set_tempo = Self::setup_channel(Channel::new(0), ProgNum::new(0)).take(1);
+            Ok(with_end(Box::new(set_tempo)))
         });
 
-        (stream, Timing::Metrical(DEFAULT_TIME_DIV))
+        let stream = split
+            .map(move |(i, p)| {
+                let (channel, program) = user_patch.get_or_insert(i)?;
+                Ok(with_end(Box::new(p.as_midi_track(
+                    channel,
+                    program,
+                    velocity,
+                    ticks_per_second,
+                ))))
+            })
+            .chain(silent_track);
+
+        (stream, Timing::Metrical(ticks_per_quarter))
+    }
+
+    /// Convert the [`Performance`] into the list of [`MidiNote`]s
+    /// with the absolute timing measured in ticks,
+    /// given the number of ticks per quarter note.
+    ///
+    /// This is a building block for the custom MIDI pipelines.
+    ///
+    /// Optionally, the [patch map][UserPatchMap] could be provided to
+    /// explicitly assign MIDI channels to instruments.
+    pub fn to_midi_notes(
+        &self,
+        user_patch: Option<UserPatchMap>,
+        ticks_per_quarter: u15,
+    ) -> Result<Vec<MidiNote>, Error> {
+        let mut user_patch = user_patch.unwrap_or_default();
+        let ticks_per_second = u32::from(u16::from(ticks_per_quarter)) * BEATS_PER_SECOND;
+
+        self.iter()
+            .filter_map(|e| {
+                user_patch
+                    .get_or_insert(e.instrument.clone())
+                    .map(|(channel, _)| {
+                        e.as_midi_note(channel, VelocityCurve::default(), ticks_per_second)
+                    })
+                    .transpose()
+            })
+            .collect()
     }
 
     // after one hour stop trying to find new instruments in the Performance
     const FIND_NEW_INSTRUMENTS_IN: Option<Duration> = Some(Duration::from_secs(3_600));
 
     fn split_by_instruments(self) -> impl Iterator<Item = (InstrumentName, Self)> {
-        let mut stream = {
-            let x: LazyList<_> = self.into_iter();
-            Some(x.peekable())
-        };
+        let mut stream = Some(self.items());
 
         iter::from_fn(move || {
-            let mut current_stream = stream.take()?;
-            let head = current_stream.peek()?;
-            let instrument = head.instrument.clone();
+            let current_stream = stream.take()?;
+            let head = current_stream.clone().find_map(Item::into_event)?;
+            let instrument = head.instrument;
             let i = instrument.clone();
 
             let trying_fn = Self::FIND_NEW_INSTRUMENTS_IN.map(|max_dur| {
-                move |ev: &Event| u64::from(ev.start_time.to_integer()) < max_dur.as_secs()
+                move |item: &Item| u64::from(item.start_time().to_integer()) < max_dur.as_secs()
             });
 
-            let (this_instrument, other) =
-                partition(current_stream, move |e| e.instrument == i, trying_fn);
-            stream = Some(LazyList(Box::new(other)).peekable());
-            Some((instrument, Self::with_events(this_instrument)))
+            // the marks of the key signature changes all go into the first track
+            let (this_instrument, other) = partition(
+                current_stream,
+                move |item| match item {
+                    Item::Note(e, _) => e.instrument == i,
+                    Item::KeySig(..) => true,
+                },
+                trying_fn,
+            );
+            stream = Some(LazyList(Box::new(other)));
+            Some((instrument, Self::with_items(this_instrument)))
         })
     }
 
@@ -119,13 +232,69 @@ impl Performance {
         &self,
         channel: Channel,
         program: ProgNum,
+        velocity: VelocityCurve,
+        ticks_per_second: u32,
     ) -> impl Iterator<Item = TimedMessage<'static>> {
         let setup_channel = Self::setup_channel(channel, program);
 
-        let pairs = self.iter().filter_map(move |e| e.as_midi(channel));
-        let sorted = merge_pairs_by(pairs, |e1, e2| e1.0 < e2.0);
+        // the key signature is announced only before the next note,
+        // so the marks with no notes in between (e.g. restoring the previous key
+        // right before the next change) are collapsed into the latest one
+        let events = self
+            .items()
+            .scan(
+                (KeySig::default(), None),
+                move |(current_key, pending), item| {
+                    let (e, vibrato) = match item {
+                        Item::Note(e, vibrato) => (e, vibrato),
+                        Item::KeySig(t, key) => {
+                            *pending = Some((t, key));
+                            return Some(None);
+                        }
+                    };
+                    let key_change = pending
+                        .take()
+                        .filter(|(_, key)| key != current_key)
+                        .and_then(|(t, key)| {
+                            *current_key = key;
+                            let start = to_ticks(t, ticks_per_second)?;
+                            Some(((start, Some(key_signature(key))), (start, None)))
+                        });
+                    Some(Some((e, vibrato, key_change)))
+                },
+            )
+            .flatten();
+        let pairs = events.flat_map(move |(e, vibrato, key_change)| {
+            let note = e
+                .as_midi(channel, velocity, ticks_per_second)
+                .map(|((t1, on), (t2, off))| ((t1, Some(on)), (t2, Some(off))));
+            // the pitch bends are paired with the empty placeholder
+            // at the start of the note to keep the pairs sorted by the first element.
+            // The pitch bend affects the whole channel, so the overlapping notes
+            // of the same instrument (e.g. a chord) are bent together.
+            let bends = note
+                .zip(vibrato)
+                .and_then(|(((start, _), _), vibrato)| {
+                    Some((start, e.vibrato_bends(vibrato, channel, ticks_per_second)?))
+                })
+                .into_iter()
+                .flat_map(|(start, bends)| {
+                    bends
+                        .into_iter()
+                        .map(move |(t, bend)| ((start, None), (t, Some(bend))))
+                });
+            key_change.into_iter().chain(note).chain(bends)
+        });
+        // All the note-ons are emitted before any other message with the same time,
+        // so the notes of a chord sound as a true simultaneity
+        // and are never interleaved with the note-offs of the previous notes
+        // (or the zero-length notes).
+        let sorted = merge_pairs_by(pairs, |e1, e2| {
+            (e1.0, !is_attack(e1.1.as_ref())) < (e2.0, !is_attack(e2.1.as_ref()))
+        })
+        .filter_map(|(t, message)| Some((t, message?)));
 
-        setup_channel.chain(sorted)
+        setup_channel.chain(release_reattacked_first(sorted))
     }
 
     fn setup_channel(
@@ -142,6 +311,94 @@ impl Performance {
     }
 }
 
+/// Whether the message should precede the other messages at the same time:
+/// the note-ons and the meta messages (as well as the placeholders for the absent messages)
+/// are, the note-offs and the other channel messages are not.
+const fn is_attack(message: Option<&TrackEventKind<'_>>) -> bool {
+    match message {
+        Some(TrackEventKind::Midi { message, .. }) => {
+            matches!(message, MidiMessage::NoteOn { .. })
+        }
+        Some(_) | None => true,
+    }
+}
+
+/// Reorder the messages of every tick, so the note re-attacked
+/// at the moment of its release gets the note-off of its previous occurrence
+/// before the new note-on, while all the other note-offs
+/// still follow the note-ons of the same tick.
+fn release_reattacked_first<'a>(
+    messages: impl Iterator<Item = TimedMessage<'a>>,
+) -> impl Iterator<Item = TimedMessage<'a>> {
+    let mut sounding: Map<(u4, u7), usize> = Map::new();
+    messages
+        .peekable()
+        .batching(|it| {
+            let (tick, first) = it.next()?;
+            let same_tick = iter::from_fn(|| it.next_if(|(t, _)| *t == tick));
+            Some(
+                iter::once((tick, first))
+                    .chain(same_tick)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flat_map(move |messages| {
+            let attacked: Vec<_> = messages
+                .iter()
+                .filter_map(|(_, kind)| match note_key(kind) {
+                    Some((true, key)) => Some(key),
+                    _ => None,
+                })
+                .collect();
+
+            let (mut released, mut others) = (vec![], vec![]);
+            for message in messages {
+                // the note sounding before this tick is released first
+                let reattacked = match note_key(&message.1) {
+                    Some((false, key)) if attacked.contains(&key) => sounding
+                        .get_mut(&key)
+                        .filter(|count| **count > 0)
+                        .map(|count| *count -= 1)
+                        .is_some(),
+                    _ => false,
+                };
+                if reattacked {
+                    released.push(message);
+                } else {
+                    others.push(message);
+                }
+            }
+
+            for (_, kind) in &others {
+                match note_key(kind) {
+                    Some((true, key)) => *sounding.entry(key).or_default() += 1,
+                    Some((false, key)) => {
+                        if let Some(count) = sounding.get_mut(&key) {
+                            *count = count.saturating_sub(1);
+                        }
+                    }
+                    None => {}
+                }
+            }
+            released.into_iter().chain(others)
+        })
+}
+
+/// The channel and the key of the note-on (`true`) or the note-off (`false`) message.
+const fn note_key(kind: &TrackEventKind<'_>) -> Option<(bool, (u4, u7))> {
+    match kind {
+        TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::NoteOn { key, .. },
+        } => Some((true, (*channel, *key))),
+        TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::NoteOff { key, .. },
+        } => Some((false, (*channel, *key))),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Error while converting to MIDI.
 pub enum Error {
@@ -174,35 +431,158 @@ const BEATS_PER_SECOND: u32 = 2;
 pub(super) type TimedMessage<'a, T = u32> = (T, TrackEventKind<'a>);
 type Pair<T> = (T, T);
 
-impl Event {
-    fn as_midi(&self, channel: Channel) -> Option<Pair<TimedMessage<'static>>> {
-        let ticks_per_second = u32::from(u16::from(DEFAULT_TIME_DIV)) * BEATS_PER_SECOND;
-
-        let start = (self.start_time.checked_mul(&ticks_per_second.into())?).to_integer();
-        let end = self
-            .start_time
-            .checked_add(&self.duration)?
-            .checked_mul(&ticks_per_second.into())?
-            .to_integer();
-        let key = u8::from(self.pitch.get_inner());
-        let vel = self.volume.clamp(Volume::softest(), Volume::loudest());
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Single note with all the data required
+/// to produce a pair of MIDI messages.
+pub struct MidiNote {
+    /// MIDI channel to play the note on.
+    pub channel: u4,
+    /// MIDI key (pitch) of the note.
+    pub key: u7,
+    /// How hard the note is struck.
+    pub velocity: u7,
+    /// Absolute time of the `NoteOn` message in ticks.
+    pub on_tick: u32,
+    /// Absolute time of the `NoteOff` message in ticks.
+    pub off_tick: u32,
+}
+
+impl MidiNote {
+    const fn into_messages(self) -> Pair<TimedMessage<'static>> {
+        let Self {
+            channel,
+            key,
+            velocity: vel,
+            on_tick,
+            off_tick,
+        } = self;
 
         let event_on = TrackEventKind::Midi {
             channel,
-            message: MidiMessage::NoteOn {
-                key: key.into(),
-                vel: u8::from(vel.0).into(),
-            },
+            message: MidiMessage::NoteOn { key, vel },
         };
-
         let event_off = TrackEventKind::Midi {
             channel,
-            message: MidiMessage::NoteOff {
-                key: key.into(),
-                vel: u8::from(vel.0).into(),
-            },
+            message: MidiMessage::NoteOff { key, vel },
+        };
+        ((on_tick, event_on), (off_tick, event_off))
+    }
+}
+
+/// The MIDI meta message for the [`KeySig`].
+///
+/// The theoretical keys with more than 7 accidentals
+/// are replaced by their enharmonic equivalents, e.g. the G# major becomes the Ab major.
+fn key_signature(key: KeySig) -> TrackEventKind<'static> {
+    let oct_size = i8::try_from(u8::from(Octave::semitones_number())).expect("12 is low enough");
+    let sharps = match key.sharps() {
+        s if s > 7 => s - oct_size,
+        s if s < -7 => s + oct_size,
+        s => s,
+    };
+    let is_minor = matches!(key, KeySig::Minor(_));
+    TrackEventKind::Meta(MetaMessage::KeySignature(sharps, is_minor))
+}
+
+/// Convert the exact time point into the MIDI ticks.
+///
+/// All the timings of the [`Performance`] are kept as exact rationals,
+/// so the rounding (to the nearest tick) happens only once here,
+/// and the error does not accumulate over the long performances.
+fn to_ticks(t: TimePoint, ticks_per_second: u32) -> Option<u32> {
+    Some(
+        t.checked_mul(&ticks_per_second.into())?
+            .round()
+            .to_integer(),
+    )
+}
+
+impl Event {
+    fn as_midi(
+        &self,
+        channel: Channel,
+        velocity: VelocityCurve,
+        ticks_per_second: u32,
+    ) -> Option<Pair<TimedMessage<'static>>> {
+        self.as_midi_note(channel, velocity, ticks_per_second)
+            .map(MidiNote::into_messages)
+    }
+
+    /// The start and the end of the [`Event`] in MIDI ticks.
+    fn ticks_span(&self, ticks_per_second: u32) -> Option<Pair<u32>> {
+        let start = to_ticks(self.start_time, ticks_per_second)?;
+        let end = to_ticks(
+            self.start_time.checked_add(&self.duration)?,
+            ticks_per_second,
+        )?;
+        Some((start, end))
+    }
+
+    fn as_midi_note(
+        &self,
+        channel: Channel,
+        velocity: VelocityCurve,
+        ticks_per_second: u32,
+    ) -> Option<MidiNote> {
+        let (on_tick, off_tick) = self.ticks_span(ticks_per_second)?;
+        let key = u8::from(self.pitch.get_inner());
+        let vel = velocity.velocity(self.volume.clamp(Volume::softest(), Volume::loudest()));
+
+        Some(MidiNote {
+            channel,
+            key: key.into(),
+            velocity: u8::from(vel.0).into(),
+            on_tick,
+            off_tick,
+        })
+    }
+}
+
+// the default pitch bend range of the General MIDI devices is 2 semitones
+const PITCH_BEND_RANGE_CENTS: f64 = 200.0;
+
+// how many pitch bend messages describe the single period of the vibrato
+const VIBRATO_RESOLUTION: u32 = 16;
+
+impl Event {
+    /// The series of pitch bend messages for the [`Vibrato`]
+    /// oscillating during the note and returning to the center at its end.
+    fn vibrato_bends(
+        &self,
+        vibrato: Vibrato,
+        channel: Channel,
+        ticks_per_second: u32,
+    ) -> Option<Vec<TimedMessage<'static>>> {
+        let Vibrato { rate, depth_cents } = vibrato;
+        if rate.is_zero() || depth_cents == 0 {
+            return None;
+        }
+
+        let (start, end) = self.ticks_span(ticks_per_second)?;
+
+        let step = Ratio::from(ticks_per_second) / rate / VIBRATO_RESOLUTION;
+        let depth = f64::from(depth_cents).min(PITCH_BEND_RANGE_CENTS) / PITCH_BEND_RANGE_CENTS;
+        let rate = f64::from(*rate.numer()) / f64::from(*rate.denom());
+
+        let bend = move |value: PitchBend| TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::PitchBend { bend: value },
         };
-        Some(((start, event_on), (end, event_off)))
+
+        let oscillation = (1..)
+            .map_while(move |i: u32| Some(start + step.checked_mul(&i.into())?.to_integer()))
+            .take_while(move |t| *t < end)
+            .dedup()
+            .map(move |t| {
+                let seconds = f64::from(t - start) / f64::from(ticks_per_second);
+                let phase = 2.0 * std::f64::consts::PI * rate * seconds;
+                (t, bend(PitchBend::from_f64(depth * phase.sin())))
+            });
+        Some(
+            oscillation
+                .chain(iter::once((end, bend(PitchBend::mid_raw_value()))))
+                .collect(),
+        )
     }
 }
 
@@ -261,3 +641,353 @@ pub(super) fn tick_size(timing: Timing) -> Duration {
 
     Duration::from_secs_f64(f64::from(ticks_per_second).recip())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{music::Music, n, p, Dur, Performable as _, PitchClass};
+
+    fn absolute_messages(perf: Performance) -> Vec<TimedMessage<'static>> {
+        let smf = perf.into_midi(None).unwrap();
+        let track = smf.tracks.into_iter().next().unwrap();
+        to_absolute(track.into_iter(), true).collect()
+    }
+
+    fn is_note_on(kind: &TrackEventKind<'_>) -> bool {
+        matches!(
+            kind,
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOn { .. },
+                ..
+            }
+        )
+    }
+
+    fn is_note_off(kind: &TrackEventKind<'_>) -> bool {
+        matches!(
+            kind,
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOff { .. },
+                ..
+            }
+        )
+    }
+
+    #[test]
+    fn block_chord_note_ons_at_the_same_tick() {
+        let chord = Music::chord(vec![
+            n!(C 4 / 4).into(),
+            n!(E 4 / 4).into(),
+            n!(G 4 / 4).into(),
+        ]);
+        let m = Music::from(n!(B 3 / 4)) + chord;
+        let messages = absolute_messages(m.perform());
+
+        let notes: Vec<_> = messages
+            .iter()
+            .filter(|(_, kind)| is_note_on(kind) || is_note_off(kind))
+            .collect();
+        assert_eq!(notes.len(), 8);
+        assert!(is_note_on(&notes[0].1));
+
+        // the first note ends exactly when the chord starts,
+        // but its note-off follows all the chord's note-ons
+        let chord_start = notes[1].0;
+        let chord_ons: Vec<_> = notes[1..4]
+            .iter()
+            .map(|(t, kind)| (*t, is_note_on(kind)))
+            .collect();
+        assert_eq!(chord_ons, [(chord_start, true); 3]);
+        assert_eq!(notes[4].0, chord_start);
+        assert!(notes[4..].iter().all(|(_, kind)| is_note_off(kind)));
+    }
+
+    #[test]
+    fn repeated_note_is_released_before_re_attacked() {
+        let m: Music = Music::from(n!(C 4 / 4)) + Music::from(n!(C 4 / 4));
+        let messages = absolute_messages(m.perform());
+
+        let notes: Vec<_> = messages
+            .iter()
+            .filter_map(|(t, kind)| Some((*t, note_key(kind)?)))
+            .collect();
+        let c4 = (u4::new(0), u7::new(60));
+        assert_eq!(
+            notes,
+            [
+                (0, (true, c4)),
+                (96, (false, c4)),
+                (96, (true, c4)),
+                (192, (false, c4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_length_note_does_not_split_the_chord() {
+        let chord = Music::chord(vec![
+            n!(C 4 / 4).into(),
+            Music::note(Dur::ZERO, p!(D 4)),
+            n!(E 4 / 4).into(),
+            n!(G 4 / 4).into(),
+        ]);
+        let messages = absolute_messages(chord.perform());
+
+        let notes: Vec<_> = messages
+            .iter()
+            .filter(|(_, kind)| is_note_on(kind) || is_note_off(kind))
+            .map(|(t, kind)| (*t, is_note_on(kind)))
+            .collect();
+        assert_eq!(notes.len(), 8);
+        assert_eq!(notes[..4], [(0, true); 4]);
+        assert_eq!(notes[4], (0, false));
+        assert!(notes[5..].iter().all(|&(t, on)| t > 0 && !on));
+    }
+
+    fn key_signatures(perf: Performance) -> Vec<TimedMessage<'static>> {
+        absolute_messages(perf)
+            .into_iter()
+            .filter(|(_, kind)| matches!(kind, TrackEventKind::Meta(MetaMessage::KeySignature(..))))
+            .collect()
+    }
+
+    #[test]
+    fn key_signature_with_one_sharp() {
+        let m = Music::line(vec![n!(G 4 / 4).into(), n!(Fs 4 / 4).into()])
+            .with_key_sig(KeySig::Major(PitchClass::G));
+
+        assert_eq!(
+            key_signatures(m.perform()),
+            [(0, TrackEventKind::Meta(MetaMessage::KeySignature(1, false)))]
+        );
+    }
+
+    #[test]
+    fn key_signature_changes() {
+        let c_major: Music = n!(C 4 / 4).into();
+        let c_minor = Music::from(n!(C 4 / 4)).with_key_sig(KeySig::Minor(PitchClass::C));
+        let gs_major = Music::from(n!(Gs 4 / 4)).with_key_sig(KeySig::Major(PitchClass::Gs));
+        let m = Music::line(vec![c_major, c_minor, gs_major]);
+
+        assert_eq!(
+            key_signatures(m.perform()),
+            [
+                (
+                    96,
+                    TrackEventKind::Meta(MetaMessage::KeySignature(-3, true))
+                ),
+                // enharmonic Ab major instead of the G# major with 8 sharps
+                (
+                    192,
+                    TrackEventKind::Meta(MetaMessage::KeySignature(-4, false))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_signature_restored_and_retimed() {
+        let g_major = Music::from(n!(C 4 / 4)).with_key_sig(KeySig::Major(PitchClass::G));
+        let m = Music::line(vec![g_major, n!(C 4 / 4).into()]);
+        let perf = m.perform().retime(Ratio::from(2), Ratio::from(4));
+
+        assert_eq!(
+            key_signatures(perf),
+            [
+                (0, TrackEventKind::Meta(MetaMessage::KeySignature(1, false))),
+                (
+                    192,
+                    TrackEventKind::Meta(MetaMessage::KeySignature(0, false))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn single_midi_note() {
+        let perf = Music::from(n!(A 4 / 2)).with_delay(Dur::QUARTER).perform();
+        let notes = perf.to_midi_notes(None, u15::new(480)).unwrap();
+
+        assert_eq!(
+            notes,
+            [MidiNote {
+                channel: u4::new(0),
+                key: u7::new(69),
+                velocity: u7::new(127),
+                on_tick: 480,
+                off_tick: 1440,
+            }]
+        );
+    }
+
+    #[test]
+    fn vibrato_emits_pitch_bends() {
+        use crate::Performable;
+        use crate::{
+            attributes::Ornament,
+            music::{
+                perf::{Context, FancyPlayer},
+                AttrNote,
+            },
+            PhraseAttribute,
+        };
+
+        let m =
+            Music::from(n!(A 4 / 2)).with_phrase(vec![PhraseAttribute::Orn(Ornament::Vibrato {
+                rate: Ratio::from_integer(5),
+                depth_cents: 50,
+            })]);
+        let ctx = Context::with_default_player::<FancyPlayer>();
+        let perf = Performable::<AttrNote>::perform_with_context(m, ctx);
+        let messages = absolute_messages(perf);
+
+        let bends: Vec<_> = messages
+            .iter()
+            .filter_map(|(t, kind)| match kind {
+                TrackEventKind::Midi {
+                    message: MidiMessage::PitchBend { bend },
+                    ..
+                } => Some((*t, *bend)),
+                _ => None,
+            })
+            .collect();
+        // 5 periods during the second with 16 bends for every period
+        assert_eq!(bends.len(), 80);
+        assert!(bends.iter().any(|(_, b)| b.as_f64() > 0.2));
+        assert!(bends.iter().any(|(_, b)| b.as_f64() < -0.2));
+        assert!(bends.iter().all(|(_, b)| b.as_f64().abs() <= 0.25));
+
+        let note_off = messages.iter().find(|(_, kind)| is_note_off(kind)).unwrap();
+        assert_eq!(
+            bends.last(),
+            Some(&(note_off.0, PitchBend::mid_raw_value()))
+        );
+    }
+
+    #[test]
+    fn silent_performance_lasts_until_the_end_marker() {
+        // 4'33"
+        let perf = Performance::with_events(iter::empty())
+            .with_total_duration(crate::perf::Duration::from_integer(273));
+        let smf = perf.into_midi(None).unwrap();
+        assert_eq!(smf.tracks.len(), 1);
+
+        let track = smf.tracks.into_iter().next().unwrap();
+        let messages: Vec<_> = to_absolute(track.into_iter(), false).collect();
+        assert_eq!(
+            messages.last(),
+            Some(&(273 * 192, TrackEventKind::Meta(MetaMessage::EndOfTrack)))
+        );
+    }
+
+    #[test]
+    fn end_marker_extends_the_track() {
+        let perf = Music::from(n!(C 4 / 4))
+            .perform()
+            .with_total_duration(crate::perf::Duration::from_integer(2));
+        let messages = to_absolute(
+            perf.into_midi(None).unwrap().tracks.remove(0).into_iter(),
+            false,
+        )
+        .collect::<Vec<_>>();
+        assert!(is_note_off(&messages[messages.len() - 2].1));
+        assert_eq!(messages[messages.len() - 2].0, 96);
+        assert_eq!(messages.last().unwrap().0, 384);
+    }
+
+    #[test]
+    fn long_triplets_do_not_drift() {
+        // 3 eighth-note triplets fit into a quarter note
+        let triplet = Dur::EIGHTH * Ratio::new(2, 3);
+        let m: Music = Music::lazy_line((0..300).map(move |_| Music::C(Octave::OneLined, triplet)));
+        let perf = m.perform();
+
+        let end = perf
+            .iter()
+            .map(|e| e.start_time + e.duration)
+            .max()
+            .unwrap();
+        // 100 quarter notes at 120 BPM
+        assert_eq!(end, TimePoint::from_integer(50));
+
+        let messages = absolute_messages(perf);
+        let last_off = messages
+            .iter()
+            .rev()
+            .find(|(_, kind)| is_note_off(kind))
+            .unwrap();
+        assert_eq!(last_off.0, 50 * 192);
+    }
+
+    #[test]
+    fn ticks_rounded_to_the_nearest() {
+        // 1/5 of a quarter note lasts 19.2 ticks
+        let quintuplet = Dur::QUARTER * Ratio::new(1, 5);
+        let m: Music = Music::line(vec![Music::C(Octave::OneLined, quintuplet); 5]);
+        let messages = absolute_messages(m.perform());
+
+        let ons: Vec<_> = messages
+            .iter()
+            .filter(|(_, kind)| is_note_on(kind))
+            .map(|(t, _)| *t)
+            .collect();
+        assert_eq!(ons, [0, 19, 38, 58, 77]);
+    }
+
+    #[test]
+    fn higher_resolution_places_tuplets_exactly() {
+        let quintuplet = Dur::QUARTER * Ratio::new(1, 5);
+        let m: Music = Music::line(vec![Music::C(Octave::OneLined, quintuplet); 5]);
+
+        let path = std::env::temp_dir().join("musik-quintuplets-960.mid");
+        m.perform().save_to_file_with(&path, 960).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let smf = Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.header.timing, Timing::Metrical(u15::new(960)));
+
+        let track = smf.tracks.into_iter().next().unwrap();
+        let ons: Vec<_> = to_absolute(track.into_iter(), true)
+            .filter(|(_, kind)| is_note_on(kind))
+            .map(|(t, _)| t)
+            .collect();
+        assert_eq!(ons, [0, 192, 384, 576, 768]);
+    }
+
+    #[test]
+    fn higher_resolution_is_exact_where_the_default_one_rounds() {
+        let quintuplet = Dur::QUARTER * Ratio::new(1, 5);
+        let m: Music = Music::line(vec![Music::C(Octave::OneLined, quintuplet); 5]);
+        let note_ons = |ticks_per_quarter| -> Vec<u32> {
+            let smf = m
+                .clone()
+                .perform()
+                .into_midi_with_resolution(None, VelocityCurve::default(), ticks_per_quarter)
+                .unwrap();
+            let track = smf.tracks.into_iter().next().unwrap();
+            to_absolute(track.into_iter(), true)
+                .filter(|(_, kind)| is_note_on(kind))
+                .map(|(t, _)| t)
+                .collect()
+        };
+
+        let default = note_ons(DEFAULT_TIME_DIV);
+        let precise = note_ons(u15::new(960));
+        // the quintuplet lasts exactly 192 ticks, but 19.2 ticks by default
+        assert_eq!(precise, [0, 192, 384, 576, 768]);
+        let rounded: Vec<_> = precise.iter().map(|t| (t + 5) / 10).collect();
+        assert_eq!(default, rounded);
+        assert_ne!(default.iter().map(|t| t * 10).collect::<Vec<_>>(), precise);
+    }
+
+    #[test]
+    fn invalid_resolution() {
+        let m: Music = Music::C(Octave::OneLined, Dur::QUARTER);
+        let path = std::env::temp_dir().join("musik-invalid-resolution.mid");
+        assert!(m.clone().perform().save_to_file_with(&path, 0).is_err());
+        assert!(m.perform().save_to_file_with(&path, 40000).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -8,8 +8,13 @@ use enum_map::Enum;
 use ux2::u7;
 
 use crate::{
+    instruments::InstrumentName,
     music::Music,
-    prim::{duration::Dur, pitch::AbsPitch},
+    prim::{
+        duration::Dur,
+        pitch::{AbsPitch, Pitch},
+        volume::Volume,
+    },
 };
 
 // https://github.com/rust-lang/rfcs/issues/284#issuecomment-1592343574
@@ -146,6 +151,209 @@ pub enum Instrument {
     Gunshot,
 }
 
+impl Instrument {
+    /// The group of similar instruments this [`Instrument`] belongs to.
+    pub fn family(self) -> InstrumentFamily {
+        InstrumentFamily::from_usize(self.into_usize() / InstrumentFamily::SIZE)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, PartialOrd, Ord, Hash, Enum, Sequence)]
+#[allow(missing_docs)]
+/// The groups of 8 consecutive MIDI [`Instrument`]s
+/// as defined in the General MIDI specification.
+///
+/// See more: <https://en.wikipedia.org/wiki/General_MIDI#Program_change_events>
+pub enum InstrumentFamily {
+    Piano,
+    ChromaticPercussion,
+    Organ,
+    Guitar,
+    Bass,
+    Strings,
+    Ensemble,
+    Brass,
+    Reed,
+    Pipe,
+    SynthLead,
+    SynthPad,
+    SynthEffects,
+    Ethnic,
+    Percussive,
+    SoundEffects,
+}
+
+impl InstrumentFamily {
+    const SIZE: usize = 8;
+
+    /// All the [`Instrument`]s belonging to the family.
+    #[allow(clippy::too_many_lines)]
+    pub const fn instruments(self) -> &'static [Instrument] {
+        match self {
+            Self::Piano => &[
+                Instrument::AcousticGrandPiano,
+                Instrument::BrightAcousticPiano,
+                Instrument::ElectricGrandPiano,
+                Instrument::HonkyTonkPiano,
+                Instrument::RhodesPiano,
+                Instrument::ChorusedPiano,
+                Instrument::Harpsichord,
+                Instrument::Clavinet,
+            ],
+            Self::ChromaticPercussion => &[
+                Instrument::Celesta,
+                Instrument::Glockenspiel,
+                Instrument::MusicBox,
+                Instrument::Vibraphone,
+                Instrument::Marimba,
+                Instrument::Xylophone,
+                Instrument::TubularBells,
+                Instrument::Dulcimer,
+            ],
+            Self::Organ => &[
+                Instrument::HammondOrgan,
+                Instrument::PercussiveOrgan,
+                Instrument::RockOrgan,
+                Instrument::ChurchOrgan,
+                Instrument::ReedOrgan,
+                Instrument::Accordion,
+                Instrument::Harmonica,
+                Instrument::TangoAccordion,
+            ],
+            Self::Guitar => &[
+                Instrument::AcousticGuitarNylon,
+                Instrument::AcousticGuitarSteel,
+                Instrument::ElectricGuitarJazz,
+                Instrument::ElectricGuitarClean,
+                Instrument::ElectricGuitarMuted,
+                Instrument::OverdrivenGuitar,
+                Instrument::DistortionGuitar,
+                Instrument::GuitarHarmonics,
+            ],
+            Self::Bass => &[
+                Instrument::AcousticBass,
+                Instrument::ElectricBassFingered,
+                Instrument::ElectricBassPicked,
+                Instrument::FretlessBass,
+                Instrument::SlapBass1,
+                Instrument::SlapBass2,
+                Instrument::SynthBass1,
+                Instrument::SynthBass2,
+            ],
+            Self::Strings => &[
+                Instrument::Violin,
+                Instrument::Viola,
+                Instrument::Cello,
+                Instrument::Contrabass,
+                Instrument::TremoloStrings,
+                Instrument::PizzicatoStrings,
+                Instrument::OrchestralHarp,
+                Instrument::Timpani,
+            ],
+            Self::Ensemble => &[
+                Instrument::StringEnsemble1,
+                Instrument::StringEnsemble2,
+                Instrument::SynthStrings1,
+                Instrument::SynthStrings2,
+                Instrument::ChoirAahs,
+                Instrument::VoiceOohs,
+                Instrument::SynthVoice,
+                Instrument::OrchestraHit,
+            ],
+            Self::Brass => &[
+                Instrument::Trumpet,
+                Instrument::Trombone,
+                Instrument::Tuba,
+                Instrument::MutedTrumpet,
+                Instrument::FrenchHorn,
+                Instrument::BrassSection,
+                Instrument::SynthBrass1,
+                Instrument::SynthBrass2,
+            ],
+            Self::Reed => &[
+                Instrument::SopranoSax,
+                Instrument::AltoSax,
+                Instrument::TenorSax,
+                Instrument::BaritoneSax,
+                Instrument::Oboe,
+                Instrument::Bassoon,
+                Instrument::EnglishHorn,
+                Instrument::Clarinet,
+            ],
+            Self::Pipe => &[
+                Instrument::Piccolo,
+                Instrument::Flute,
+                Instrument::Recorder,
+                Instrument::PanFlute,
+                Instrument::BlownBottle,
+                Instrument::Shakuhachi,
+                Instrument::Whistle,
+                Instrument::Ocarina,
+            ],
+            Self::SynthLead => &[
+                Instrument::Lead1Square,
+                Instrument::Lead2Sawtooth,
+                Instrument::Lead3Calliope,
+                Instrument::Lead4Chiff,
+                Instrument::Lead5Charang,
+                Instrument::Lead6Voice,
+                Instrument::Lead7Fifths,
+                Instrument::Lead8BassLead,
+            ],
+            Self::SynthPad => &[
+                Instrument::Pad1NewAge,
+                Instrument::Pad2Warm,
+                Instrument::Pad3Polysynth,
+                Instrument::Pad4Choir,
+                Instrument::Pad5Bowed,
+                Instrument::Pad6Metallic,
+                Instrument::Pad7Halo,
+                Instrument::Pad8Sweep,
+            ],
+            Self::SynthEffects => &[
+                Instrument::FX1Train,
+                Instrument::FX2Soundtrack,
+                Instrument::FX3Crystal,
+                Instrument::FX4Atmosphere,
+                Instrument::FX5Brightness,
+                Instrument::FX6Goblins,
+                Instrument::FX7Echoes,
+                Instrument::FX8SciFi,
+            ],
+            Self::Ethnic => &[
+                Instrument::Sitar,
+                Instrument::Banjo,
+                Instrument::Shamisen,
+                Instrument::Koto,
+                Instrument::Kalimba,
+                Instrument::Bagpipe,
+                Instrument::Fiddle,
+                Instrument::Shanai,
+            ],
+            Self::Percussive => &[
+                Instrument::TinkleBell,
+                Instrument::Agogo,
+                Instrument::SteelDrums,
+                Instrument::Woodblock,
+                Instrument::TaikoDrum,
+                Instrument::MelodicDrum,
+                Instrument::SynthDrum,
+                Instrument::ReverseCymbal,
+            ],
+            Self::SoundEffects => &[
+                Instrument::GuitarFretNoise,
+                Instrument::BreathNoise,
+                Instrument::Seashore,
+                Instrument::BirdTweet,
+                Instrument::TelephoneRing,
+                Instrument::Helicopter,
+                Instrument::Applause,
+                Instrument::Gunshot,
+            ],
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Enum, Sequence)]
 #[allow(missing_docs)]
 pub enum PercussionSound {
@@ -201,10 +409,237 @@ pub enum PercussionSound {
 impl PercussionSound {
     /// Produce a MIDI note for the [`PercussionSound`].
     pub fn note(self, dur: Dur) -> Music {
+        Music::note(dur, self.pitch())
+    }
+
+    /// Produce a MIDI note for the [`PercussionSound`] played with the given [`Volume`],
+    /// e.g. to make the accents or the ghost notes.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Ghost_note>
+    pub fn note_with_volume(self, dur: Dur, volume: Volume) -> Music<(Pitch, Volume)> {
+        Music::note(dur, (self.pitch(), volume))
+    }
+
+    fn pitch(self) -> Pitch {
         let midi_key = u7::try_from(self.into_usize())
             .expect("<=46 fits into u7")
             .checked_add(u7::new(35))
             .expect("<=81 fits into u7");
-        Music::note(dur, AbsPitch::from(midi_key).into())
+        AbsPitch::from(midi_key).into()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+/// The set of [`PercussionSound`]s usually found in a drum kit
+/// to simplify building the grooves without remembering
+/// the General MIDI percussion key map.
+///
+/// See more: <https://en.wikipedia.org/wiki/Drum_kit>
+pub struct DrumKit {
+    kick: PercussionSound,
+    snare: PercussionSound,
+    hihat_closed: PercussionSound,
+    hihat_open: PercussionSound,
+    crash: PercussionSound,
+    ride: PercussionSound,
+    tom_low: PercussionSound,
+    tom_high: PercussionSound,
+}
+
+impl Default for DrumKit {
+    fn default() -> Self {
+        Self::acoustic()
+    }
+}
+
+impl DrumKit {
+    /// The standard acoustic drum kit.
+    pub const fn acoustic() -> Self {
+        Self {
+            kick: PercussionSound::AcousticBassDrum,
+            snare: PercussionSound::AcousticSnare,
+            hihat_closed: PercussionSound::ClosedHiHat,
+            hihat_open: PercussionSound::OpenHiHat,
+            crash: PercussionSound::CrashCymbal1,
+            ride: PercussionSound::RideCymbal1,
+            tom_low: PercussionSound::LowTom,
+            tom_high: PercussionSound::HighTom,
+        }
+    }
+
+    /// The drum kit with the electric snare and the alternative bass drum.
+    pub const fn electric() -> Self {
+        Self {
+            kick: PercussionSound::BassDrum1,
+            snare: PercussionSound::ElectricSnare,
+            ..Self::acoustic()
+        }
+    }
+
+    /// The bass (kick) drum.
+    pub const fn kick(&self) -> PercussionSound {
+        self.kick
+    }
+
+    /// The snare drum.
+    pub const fn snare(&self) -> PercussionSound {
+        self.snare
+    }
+
+    /// The closed hi-hat.
+    pub const fn hihat_closed(&self) -> PercussionSound {
+        self.hihat_closed
+    }
+
+    /// The open hi-hat.
+    pub const fn hihat_open(&self) -> PercussionSound {
+        self.hihat_open
+    }
+
+    /// The crash cymbal.
+    pub const fn crash(&self) -> PercussionSound {
+        self.crash
+    }
+
+    /// The ride cymbal.
+    pub const fn ride(&self) -> PercussionSound {
+        self.ride
+    }
+
+    /// The low tom-tom.
+    pub const fn tom_low(&self) -> PercussionSound {
+        self.tom_low
+    }
+
+    /// The high tom-tom.
+    pub const fn tom_high(&self) -> PercussionSound {
+        self.tom_high
+    }
+
+    /// Combine multiple rhythm lanes into a single parallel percussion [`Music`].
+    ///
+    /// Every lane is described as a string where every character
+    /// defines a single `step`:
+    /// - `x` (or `X`) means the hit of the lane's [`PercussionSound`];
+    /// - `.` or `-` means the rest.
+    ///
+    /// The spaces and the `|` characters are ignored,
+    /// so they could be used to visually separate beats or measures:
+    ///
+    /// ```
+    /// # use musik::{midi::DrumKit, Dur};
+    /// let kit = DrumKit::default();
+    /// let rock_beat = kit.pattern(
+    ///     &[
+    ///         (kit.kick(), "x... ..x. | x... ...."),
+    ///         (kit.snare(), ".... x... | .... x..."),
+    ///         (kit.hihat_closed(), "x.x. x.x. | x.x. x.x."),
+    ///     ],
+    ///     Dur::SIXTEENTH,
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    /// If the lane contains any other character.
+    pub fn pattern(&self, lanes: &[(PercussionSound, &str)], step: Dur) -> Music {
+        let lanes = lanes
+            .iter()
+            .map(|(sound, lane)| {
+                let steps = lane
+                    .chars()
+                    .filter(|c| !c.is_whitespace() && *c != '|')
+                    .map(|c| match c {
+                        'x' | 'X' => sound.note(step),
+                        '.' | '-' => Music::rest(step),
+                        other => panic!("Unexpected character in the rhythm lane: {other:?}"),
+                    })
+                    .collect();
+                Music::line(steps)
+            })
+            .collect();
+        Music::chord(lanes).with_instrument(InstrumentName::Percussion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    use crate::Performable as _;
+
+    #[test]
+    fn piano_family() {
+        assert_eq!(
+            Instrument::AcousticGrandPiano.family(),
+            InstrumentFamily::Piano
+        );
+        assert!(InstrumentFamily::Piano
+            .instruments()
+            .contains(&Instrument::AcousticGrandPiano));
+        assert_eq!(Instrument::Gunshot.family(), InstrumentFamily::SoundEffects);
+    }
+
+    #[test]
+    fn families_partition_all_programs() {
+        let mut seen = HashSet::new();
+        for family in enum_iterator::all::<InstrumentFamily>() {
+            let instruments = family.instruments();
+            assert_eq!(instruments.len(), 8);
+            for &instrument in instruments {
+                assert_eq!(instrument.family(), family);
+                assert!(seen.insert(instrument));
+            }
+        }
+        assert_eq!(seen.len(), 128);
+        assert_eq!(seen.len(), enum_iterator::cardinality::<Instrument>());
+    }
+
+    #[test]
+    fn kick_and_snare_pattern() {
+        let kit = DrumKit::default();
+        let step = Dur::QUARTER;
+        let m = kit.pattern(&[(kit.kick(), "x.x."), (kit.snare(), "-x | -x")], step);
+
+        let kick = kit.kick().note(step);
+        let snare = kit.snare().note(step);
+        let rest = Music::rest(step);
+        let expected = Music::chord(vec![
+            Music::line(vec![kick.clone(), rest.clone(), kick, rest.clone()]),
+            Music::line(vec![rest.clone(), snare.clone(), rest, snare]),
+        ])
+        .with_instrument(InstrumentName::Percussion);
+        assert_eq!(m, expected);
+    }
+
+    #[test]
+    fn ghost_note_is_quieter_than_accent() {
+        let snare = DrumKit::default().snare();
+        let m = Music::line(vec![
+            snare.note_with_volume(Dur::EIGHTH, Volume::from(30)),
+            snare.note_with_volume(Dur::EIGHTH, Volume::from(120)),
+        ])
+        .with_instrument(InstrumentName::Percussion);
+
+        let events: Vec<_> = m.perform().iter().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].pitch, events[1].pitch);
+        assert!(events[0].volume < events[1].volume);
+        assert_eq!(events[1].volume, Volume::from(120));
+    }
+
+    #[test]
+    fn electric_kit() {
+        let kit = DrumKit::electric();
+        assert_eq!(kit.snare(), PercussionSound::ElectricSnare);
+        assert_eq!(kit.hihat_closed(), DrumKit::acoustic().hihat_closed());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected character")]
+    fn invalid_pattern() {
+        let kit = DrumKit::default();
+        drop(kit.pattern(&[(kit.kick(), "x.o.")], Dur::EIGHTH));
     }
 }
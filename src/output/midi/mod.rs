@@ -3,15 +3,19 @@ use std::{collections::BTreeMap as Map, path::Path};
 
 use enum_map::Enum;
 use log::{info, trace};
-use midly::num::{u4, u7};
+use midly::{
+    num::{u15, u4, u7},
+    Smf,
+};
 
 use crate::{instruments::InstrumentName, music::perf::Performance};
 
 #[cfg(feature = "play-midi")]
 pub use self::player::{Config as MidiPlayerConfig, MidiPlayer};
 pub use self::{
-    convert::Error,
-    instruments::{Instrument, PercussionSound},
+    convert::{Error, MidiNote},
+    instruments::{DrumKit, Instrument, InstrumentFamily, PercussionSound},
+    velocity::VelocityCurve,
 };
 
 mod convert;
@@ -20,6 +24,7 @@ pub(crate) mod instruments;
 mod io;
 #[cfg(feature = "play-midi")]
 mod player;
+mod velocity;
 
 type AnyError = Box<dyn std::error::Error>;
 
@@ -28,6 +33,28 @@ impl Performance {
     /// using the [`midly`](https://crates.io/crates/midly) library.
     pub fn save_to_file<P: AsRef<Path>>(self, path: P) -> Result<(), AnyError> {
         let midi = self.into_midi(None)?;
+        Self::save_midi(&midi, path)
+    }
+
+    /// Save the [`Performance`] into MIDI file format
+    /// with the given resolution in ticks per quarter note (PPQ).
+    ///
+    /// The higher resolution allows to represent
+    /// the tuplets and other irregular durations more precisely.
+    /// The resolution should be a positive number below 32768.
+    pub fn save_to_file_with<P: AsRef<Path>>(
+        self,
+        path: P,
+        resolution: u16,
+    ) -> Result<(), AnyError> {
+        let resolution = u15::try_from(resolution)
+            .filter(|r| u16::from(*r) > 0)
+            .ok_or_else(|| format!("Invalid MIDI resolution: {resolution}"))?;
+        let midi = self.into_midi_with_resolution(None, VelocityCurve::default(), resolution)?;
+        Self::save_midi(&midi, path)
+    }
+
+    fn save_midi<P: AsRef<Path>>(midi: &Smf<'_>, path: P) -> Result<(), AnyError> {
         info!("Saving to MIDI file {}", path.as_ref().display());
 
         if log::log_enabled!(log::Level::Trace) {
@@ -0,0 +1,99 @@
+//! Mapping the [`Volume`] of the notes into the MIDI velocity.
+//!
+//! See more: <https://en.wikipedia.org/wiki/Velocity_(music)>
+use ux2::u7;
+
+use crate::prim::volume::Volume;
+
+#[derive(Debug, Copy, Clone, Default)]
+/// Defines how the [`Volume`] of a note maps into the MIDI velocity.
+///
+/// All the predefined curves map the [softest][Volume::softest] volume
+/// into the velocity 0 and the [loudest][Volume::loudest] into the velocity 127.
+pub enum VelocityCurve {
+    /// The velocity is equal to the volume.
+    #[default]
+    Linear,
+
+    /// The velocity grows slowly for the quiet notes
+    /// and faster for the loud ones, which gives more control
+    /// over the soft dynamics.
+    Exponential,
+
+    /// The velocity changes slowly near the extremes
+    /// and faster for the mid-range volumes.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Smoothstep>
+    SCurve,
+
+    /// User-defined mapping.
+    Custom(fn(Volume) -> Volume),
+}
+
+impl VelocityCurve {
+    /// The steepness of the [exponential curve][Self::Exponential].
+    const EXP_RATE: f64 = 3.0;
+
+    /// Convert the [`Volume`] into the MIDI velocity.
+    pub fn velocity(self, volume: Volume) -> Volume {
+        let max = f64::from(u8::from(u7::MAX));
+        let x = f64::from(u8::from(volume.get_inner())) / max;
+        let y = match self {
+            Self::Linear => return volume,
+            Self::Custom(f) => return f(volume),
+            Self::Exponential => (Self::EXP_RATE * x).exp_m1() / Self::EXP_RATE.exp_m1(),
+            Self::SCurve => x * x * 2.0f64.mul_add(-x, 3.0),
+        };
+        Volume::from(to_u8((y * max).round()))
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // the value is clamped before casting
+fn to_u8(x: f64) -> u8 {
+    x.clamp(0.0, f64::from(u8::MAX)) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn velocity(curve: VelocityCurve, volume: u8) -> u8 {
+        u8::from(curve.velocity(Volume::from(volume)).get_inner())
+    }
+
+    #[test]
+    fn endpoints_are_preserved() {
+        for curve in [
+            VelocityCurve::Linear,
+            VelocityCurve::Exponential,
+            VelocityCurve::SCurve,
+        ] {
+            assert_eq!(velocity(curve, 0), 0, "{curve:?}");
+            assert_eq!(velocity(curve, 127), 127, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn exponential_is_softer_in_the_middle() {
+        let linear = velocity(VelocityCurve::Linear, 64);
+        let exp = velocity(VelocityCurve::Exponential, 64);
+        assert_eq!(linear, 64);
+        assert!(exp < linear, "{exp}");
+    }
+
+    #[test]
+    fn s_curve_is_monotonic_and_symmetric() {
+        let vels: Vec<_> = (0..=127)
+            .map(|v| velocity(VelocityCurve::SCurve, v))
+            .collect();
+        assert!(vels.windows(2).all(|w| w[0] <= w[1]));
+        assert!(velocity(VelocityCurve::SCurve, 20) < 20);
+        assert!(velocity(VelocityCurve::SCurve, 107) > 107);
+    }
+
+    #[test]
+    fn custom_curve() {
+        let curve = VelocityCurve::Custom(|_| Volume::from(100));
+        assert_eq!(velocity(curve, 1), 100);
+    }
+}
@@ -1 +1,3 @@
+mod events;
 pub mod midi;
+pub mod wav;
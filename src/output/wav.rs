@@ -0,0 +1,577 @@
+//! Rendering the [`Performance`] into an audio signal
+//! using simple additive synthesis and saving it as a WAV file.
+//!
+//! See more:
+//! - <https://en.wikipedia.org/wiki/Additive_synthesis>
+//! - <https://en.wikipedia.org/wiki/WAV>
+use std::{
+    collections::BTreeMap as Map,
+    f64::consts::TAU,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use num_rational::Ratio;
+
+use crate::{
+    instruments::InstrumentName,
+    music::perf::{Event, Performance},
+    prim::{
+        pitch::Pitch,
+        scale::KeySig,
+        tuning::{EqualTemperament, Tuning, TuningSystem},
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Defines the spectrum of a rendered note
+/// by specifying the relative amplitudes of its harmonics.
+///
+/// The first value is the amplitude of the fundamental frequency,
+/// the second one is the amplitude of the doubled frequency, and so on.
+///
+/// See more:
+/// - <https://en.wikipedia.org/wiki/Timbre>
+/// - <https://en.wikipedia.org/wiki/Harmonic_series_(music)>
+pub struct Timbre {
+    harmonics: Vec<f64>,
+}
+
+impl Default for Timbre {
+    fn default() -> Self {
+        Self::pure()
+    }
+}
+
+impl Timbre {
+    /// Create a [`Timbre`] with the given amplitudes of harmonics,
+    /// e.g. `[1.0, 0.5, 0.25]`.
+    pub const fn new(harmonics: Vec<f64>) -> Self {
+        Self { harmonics }
+    }
+
+    /// The [`Timbre`] of a pure sine wave without any overtones.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Sine_wave>
+    pub fn pure() -> Self {
+        Self::new(vec![1.0])
+    }
+
+    /// Relative amplitudes of the harmonics.
+    pub fn harmonics(&self) -> &[f64] {
+        &self.harmonics
+    }
+
+    /// The value of the signal with the given fundamental frequency
+    /// at the time `t` (in seconds).
+    ///
+    /// The amplitudes of the harmonics are normalized,
+    /// so the result always belongs to the range `[-1..1]`.
+    pub fn sample(&self, frequency: f64, t: f64) -> f64 {
+        let total: f64 = self.harmonics.iter().copied().map(f64::abs).sum();
+        if total == 0.0 {
+            return 0.0;
+        }
+
+        let signal: f64 = self
+            .harmonics
+            .iter()
+            .zip(1_u32..)
+            .map(|(amplitude, n)| amplitude * (TAU * frequency * f64::from(n) * t).sin())
+            .sum();
+        signal / total
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Configuration for rendering the [`Performance`] into audio signal.
+///
+/// Use it in [`Performance::render`] or [`Performance::save_to_wav`].
+pub struct Config {
+    /// Number of samples per second.
+    ///
+    /// Default: 44100.
+    pub sample_rate: u32,
+
+    /// The spectrum of every rendered note.
+    ///
+    /// Default: [pure sine][Timbre::pure].
+    pub timbre: Timbre,
+
+    /// The reference frequency to tune the notes.
+    ///
+    /// Default: [A4 = 440 Hz][Tuning::CONCERT].
+    pub tuning: Tuning,
+
+    /// Number of the audio channels, e.g. 2 for stereo.
+    ///
+    /// Default: 1 (mono).
+    pub channels: u16,
+
+    /// Assign the instruments to the audio channels (numbered from zero)
+    /// to make the surround mix.
+    /// The instruments not mentioned here are played in all the channels,
+    /// while the ones assigned to the missing channels are not played at all.
+    ///
+    /// Default: empty.
+    pub routing: Map<InstrumentName, u16>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44_100,
+            timbre: Timbre::default(),
+            tuning: Tuning::default(),
+            channels: 1,
+            routing: Map::new(),
+        }
+    }
+}
+
+impl Config {
+    fn is_routed_to(&self, instrument: &InstrumentName, channel: u16) -> bool {
+        self.routing
+            .get(instrument)
+            .into_iter()
+            .all(|c| *c == channel)
+    }
+}
+
+fn to_sample_index(t: Ratio<u32>, sample_rate: u32) -> usize {
+    let t = Ratio::new(u64::from(*t.numer()), u64::from(*t.denom()));
+    let index = (t * u64::from(sample_rate)).to_integer();
+    usize::try_from(index).expect("The system should be at least 32-bit")
+}
+
+impl Event {
+    fn render_into(&self, buf: &mut [f64], config: &Config, frequency: f64) {
+        let amplitude = f64::from(u8::from(self.volume.get_inner())) / 127.0;
+
+        let start = to_sample_index(self.start_time, config.sample_rate);
+        let end = to_sample_index(self.start_time + self.duration, config.sample_rate);
+        let sample_rate = f64::from(config.sample_rate);
+
+        for (i, sample) in buf.iter_mut().enumerate().take(end).skip(start) {
+            let t = f64::from(u32::try_from(i - start).unwrap_or(u32::MAX)) / sample_rate;
+            *sample += amplitude * config.timbre.sample(frequency, t);
+        }
+    }
+}
+
+impl Performance {
+    /// Render the finite [`Performance`] into the mono audio signal
+    /// by summing up the waves of every single [`Event`].
+    ///
+    /// The returned samples are not normalized, so their absolute values
+    /// could exceed 1 if multiple [`Event`]s are playing simultaneously.
+    pub fn render(&self, config: &Config) -> Vec<f64> {
+        let system = EqualTemperament {
+            tuning: config.tuning,
+        };
+        self.render_with(config, &system, KeySig::default())
+    }
+
+    /// Render the finite [`Performance`] into the mono audio signal
+    /// using the given [`TuningSystem`] instead of the [`Config::tuning`].
+    ///
+    /// The `key` is required for the tuning systems
+    /// which depend on the tonic of the piece.
+    pub fn render_with<T>(&self, config: &Config, system: &T, key: KeySig) -> Vec<f64>
+    where
+        T: TuningSystem + ?Sized,
+    {
+        let events: Vec<_> = self.iter().collect();
+        let len = samples_len(&events, config.sample_rate);
+        render_events(&events.iter().collect::<Vec<_>>(), len, config, system, key)
+    }
+
+    /// Render the finite [`Performance`] into the [`Config::channels`] audio signals
+    /// of the same length routing the instruments according to the [`Config::routing`].
+    pub fn render_channels(&self, config: &Config) -> Vec<Vec<f64>> {
+        let system = EqualTemperament {
+            tuning: config.tuning,
+        };
+        self.render_channels_with(config, &system, KeySig::default())
+    }
+
+    /// Render the finite [`Performance`] into the [`Config::channels`] audio signals
+    /// using the given [`TuningSystem`] instead of the [`Config::tuning`].
+    pub fn render_channels_with<T>(&self, config: &Config, system: &T, key: KeySig) -> Vec<Vec<f64>>
+    where
+        T: TuningSystem + ?Sized,
+    {
+        let events: Vec<_> = self.iter().collect();
+        let len = samples_len(&events, config.sample_rate);
+        (0..config.channels)
+            .map(|channel| {
+                let events: Vec<_> = events
+                    .iter()
+                    .filter(|e| config.is_routed_to(&e.instrument, channel))
+                    .collect();
+                render_events(&events, len, config, system, key)
+            })
+            .collect()
+    }
+
+    /// Render the finite [`Performance`] and save it into
+    /// the 16-bit PCM WAV file with the [`Config::channels`] channels.
+    pub fn save_to_wav<P: AsRef<Path>>(&self, path: P, config: &Config) -> io::Result<()> {
+        let channels = self.render_channels(config);
+        save_samples(path, &channels, config.sample_rate)
+    }
+
+    /// Render the finite [`Performance`] using the given [`TuningSystem`]
+    /// and save it into the 16-bit PCM WAV file with the [`Config::channels`] channels.
+    pub fn save_to_wav_with<P, T>(
+        &self,
+        path: P,
+        config: &Config,
+        system: &T,
+        key: KeySig,
+    ) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        T: TuningSystem + ?Sized,
+    {
+        let channels = self.render_channels_with(config, system, key);
+        save_samples(path, &channels, config.sample_rate)
+    }
+
+    /// Render every instrument of the finite [`Performance`] separately
+    /// and save them into the 16-bit PCM mono WAV files (the stems)
+    /// inside the given directory, named after the instruments.
+    ///
+    /// All the stems have the same length, so they could be mixed back together.
+    /// The [`Config::channels`] and [`Config::routing`] are ignored.
+    ///
+    /// Returns the paths of the saved files.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Stem_mixing_and_mastering>
+    pub fn render_stems<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        config: &Config,
+    ) -> io::Result<Vec<PathBuf>> {
+        let system = EqualTemperament {
+            tuning: config.tuning,
+        };
+        let events: Vec<_> = self.iter().collect();
+        let len = samples_len(&events, config.sample_rate);
+
+        let mut by_instrument: Map<_, Vec<_>> = Map::new();
+        for e in &events {
+            by_instrument.entry(&e.instrument).or_default().push(e);
+        }
+
+        by_instrument
+            .into_iter()
+            .map(|(instrument, events)| {
+                let name: String = instrument
+                    .to_string()
+                    .chars()
+                    .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                    .collect();
+                let path = dir.as_ref().join(format!("{name}.wav"));
+                let samples = render_events(&events, len, config, &system, KeySig::default());
+                save_samples(&path, &[samples], config.sample_rate)?;
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+/// The number of samples enough to render all the [`Event`]s.
+fn samples_len(events: &[Event], sample_rate: u32) -> usize {
+    events
+        .iter()
+        .map(|e| to_sample_index(e.start_time + e.duration, sample_rate))
+        .max()
+        .unwrap_or_default()
+}
+
+fn render_events<T>(
+    events: &[&Event],
+    len: usize,
+    config: &Config,
+    system: &T,
+    key: KeySig,
+) -> Vec<f64>
+where
+    T: TuningSystem + ?Sized,
+{
+    let mut buf = vec![0.0; len];
+    for event in events {
+        let frequency = system.frequency(Pitch::from(event.pitch), key);
+        event.render_into(&mut buf, config, frequency);
+    }
+    buf
+}
+
+fn save_samples<P: AsRef<Path>>(
+    path: P,
+    channels: &[Vec<f64>],
+    sample_rate: u32,
+) -> io::Result<()> {
+    info!(
+        "Saving {} channel(s) to WAV file {}",
+        channels.len(),
+        path.as_ref().display()
+    );
+
+    // the samples of the channels are interleaved
+    let len = channels.iter().map(Vec::len).max().unwrap_or_default();
+    let samples: Vec<_> = (0..len)
+        .flat_map(|i| {
+            channels
+                .iter()
+                .map(move |ch| ch.get(i).copied().unwrap_or_default())
+        })
+        .collect();
+    let n_channels = u16::try_from(channels.len())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    if n_channels == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "At least one channel should be saved",
+        ));
+    }
+
+    let mut file = BufWriter::new(File::create(path)?);
+    write_wav(&mut file, &samples, sample_rate, n_channels)?;
+    file.flush()
+}
+
+const BITS_PER_SAMPLE: u16 = 16;
+
+/// Write the interleaved samples of all the channels.
+fn write_wav(
+    w: &mut impl Write,
+    samples: &[f64],
+    sample_rate: u32,
+    channels: u16,
+) -> io::Result<()> {
+    let block_align = channels * BITS_PER_SAMPLE / 8;
+    let byte_rate = sample_rate * u32::from(block_align);
+    let data_size = u32::try_from(samples.len() * usize::from(BITS_PER_SAMPLE / 8))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    w.write_all(b"RIFF")?;
+    w.write_all(&(36 + data_size).to_le_bytes())?;
+    w.write_all(b"WAVE")?;
+
+    w.write_all(b"fmt ")?;
+    w.write_all(&16_u32.to_le_bytes())?;
+    // PCM
+    w.write_all(&1_u16.to_le_bytes())?;
+    w.write_all(&channels.to_le_bytes())?;
+    w.write_all(&sample_rate.to_le_bytes())?;
+    w.write_all(&byte_rate.to_le_bytes())?;
+    w.write_all(&block_align.to_le_bytes())?;
+    w.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    w.write_all(b"data")?;
+    w.write_all(&data_size.to_le_bytes())?;
+    for sample in samples {
+        w.write_all(&to_pcm(*sample).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::cast_possible_truncation)] // the value is clamped before casting
+fn to_pcm(sample: f64) -> i16 {
+    (sample.clamp(-1.0, 1.0) * f64::from(i16::MAX)).round() as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{music::Music, p, Dur, Performable as _};
+
+    /// Correlate the signal with the sine and cosine of the given frequency
+    /// to find out the amplitude of this frequency in the signal.
+    fn amplitude_at(signal: &[f64], frequency: f64, sample_rate: u32) -> f64 {
+        let sample_rate = f64::from(sample_rate);
+        let (re, im) = signal
+            .iter()
+            .zip(0_u32..)
+            .fold((0.0, 0.0), |(re, im), (x, i)| {
+                let phase = TAU * frequency * f64::from(i) / sample_rate;
+                (re + x * phase.cos(), im + x * phase.sin())
+            });
+        #[allow(clippy::cast_precision_loss)]
+        let n = signal.len() as f64;
+        2.0 * re.hypot(im) / n
+    }
+
+    #[test]
+    fn pure_sine_has_single_harmonic() {
+        // the whole note lasts for 2 seconds
+        let m = Music::note(Dur::WHOLE, p!(A 4));
+        let config = Config {
+            sample_rate: 8_000,
+            ..Config::default()
+        };
+        let signal = m.perform().render(&config);
+        assert_eq!(signal.len(), 16_000);
+
+        let fundamental = amplitude_at(&signal, 440.0, config.sample_rate);
+        let second = amplitude_at(&signal, 880.0, config.sample_rate);
+        assert!((fundamental - 1.0).abs() < 0.01, "{fundamental}");
+        assert!(second < 0.01, "{second}");
+    }
+
+    #[test]
+    fn two_harmonics_timbre() {
+        let m = Music::note(Dur::WHOLE, p!(A 4));
+        let config = Config {
+            sample_rate: 8_000,
+            timbre: Timbre::new(vec![1.0, 0.5]),
+            ..Config::default()
+        };
+        let signal = m.perform().render(&config);
+
+        let fundamental = amplitude_at(&signal, 440.0, config.sample_rate);
+        let second = amplitude_at(&signal, 880.0, config.sample_rate);
+        let third = amplitude_at(&signal, 1320.0, config.sample_rate);
+
+        // normalized amplitudes are 2/3 and 1/3
+        assert!((fundamental - 2.0 / 3.0).abs() < 0.01, "{fundamental}");
+        assert!((second - 1.0 / 3.0).abs() < 0.01, "{second}");
+        assert!(third < 0.01, "{third}");
+    }
+
+    #[test]
+    fn render_with_custom_tuning() {
+        let m = Music::note(Dur::WHOLE, p!(A 4));
+        let config = Config {
+            sample_rate: 8_000,
+            tuning: Tuning::new(432.0),
+            ..Config::default()
+        };
+        let signal = m.perform().render(&config);
+
+        let tuned = amplitude_at(&signal, 432.0, config.sample_rate);
+        let concert = amplitude_at(&signal, 440.0, config.sample_rate);
+        assert!((tuned - 1.0).abs() < 0.01, "{tuned}");
+        assert!(concert < 0.01, "{concert}");
+    }
+
+    #[test]
+    fn render_with_just_intonation() {
+        use crate::{prim::tuning::JustIntonation, PitchClass};
+
+        let m = Music::note(Dur::WHOLE, p!(E 4));
+        let config = Config {
+            sample_rate: 8_000,
+            ..Config::default()
+        };
+        let key = KeySig::Major(PitchClass::C);
+        let signal = m
+            .perform()
+            .render_with(&config, &JustIntonation::default(), key);
+
+        let just_third = Pitch::C(crate::Octave::OneLined).get_frequency() * 5.0 / 4.0;
+        let amplitude = amplitude_at(&signal, just_third, config.sample_rate);
+        assert!((amplitude - 1.0).abs() < 0.01, "{amplitude}");
+    }
+
+    #[test]
+    fn sample_at_known_phase() {
+        let timbre = Timbre::new(vec![1.0, 1.0]);
+        // at the 1/8 of the period: sin(pi/4) + sin(pi/2)
+        let x = timbre.sample(1.0, 0.125);
+        let expected = (std::f64::consts::SQRT_2 + 2.0) / 4.0;
+        assert!((x - expected).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn wav_header() {
+        let mut buf = Vec::new();
+        write_wav(&mut buf, &[0.0, 1.0, -1.0], 8_000, 1).unwrap();
+
+        assert_eq!(buf.len(), 44 + 6);
+        assert_eq!(&buf[0..4], b"RIFF");
+        assert_eq!(&buf[8..16], b"WAVEfmt ");
+        assert_eq!(&buf[36..40], b"data");
+        assert_eq!(&buf[44..], &[0, 0, 0xFF, 0x7F, 0x01, 0x80]);
+    }
+
+    #[test]
+    fn stereo_wav_header() {
+        let mut buf = Vec::new();
+        write_wav(&mut buf, &[0.0, 1.0, -1.0, 0.0], 8_000, 2).unwrap();
+
+        assert_eq!(buf.len(), 44 + 8);
+        // channels
+        assert_eq!(&buf[22..24], &[2, 0]);
+        // byte rate
+        assert_eq!(&buf[28..32], &32_000_u32.to_le_bytes());
+        // block align
+        assert_eq!(&buf[32..34], &[4, 0]);
+        // data size
+        assert_eq!(&buf[40..44], &[8, 0, 0, 0]);
+    }
+
+    fn duet() -> Music {
+        use crate::midi::Instrument;
+
+        let flute = Music::note(Dur::WHOLE, p!(A 4)).with_instrument(Instrument::Flute);
+        let cello = Music::note(Dur::HALF, p!(A 3)).with_instrument(Instrument::Cello);
+        flute | cello
+    }
+
+    #[test]
+    fn route_instruments_to_channels() {
+        use crate::midi::Instrument;
+
+        let config = Config {
+            sample_rate: 8_000,
+            channels: 2,
+            routing: Map::from([(Instrument::Flute.into(), 0), (Instrument::Cello.into(), 1)]),
+            ..Config::default()
+        };
+        let channels = duet().perform().render_channels(&config);
+        assert_eq!(channels.len(), 2);
+        assert!(channels.iter().all(|ch| ch.len() == 16_000));
+
+        let left = amplitude_at(&channels[0], 440.0, config.sample_rate);
+        let leaked = amplitude_at(&channels[0], 220.0, config.sample_rate);
+        assert!((left - 1.0).abs() < 0.01, "{left}");
+        assert!(leaked < 0.01, "{leaked}");
+
+        // the cello plays only in the first half of the right channel
+        let right = amplitude_at(&channels[1][..8_000], 220.0, config.sample_rate);
+        assert!((right - 1.0).abs() < 0.01, "{right}");
+        assert!(channels[1][8_000..].iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn render_two_stems() {
+        let config = Config {
+            sample_rate: 8_000,
+            ..Config::default()
+        };
+        let dir = std::env::temp_dir().join("musik-stems");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let paths = duet().perform().render_stems(&dir, &config).unwrap();
+        assert_eq!(paths.len(), 2);
+        for path in &paths {
+            let size = std::fs::metadata(path).unwrap().len();
+            std::fs::remove_file(path).unwrap();
+            // both stems last 2 seconds
+            assert_eq!(size, 44 + 2 * 16_000);
+        }
+        assert_eq!(
+            paths
+                .iter()
+                .map(|p| p.file_name().unwrap().to_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["Cello.wav", "Flute.wav"]
+        );
+    }
+}
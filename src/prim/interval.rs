@@ -1,4 +1,8 @@
-use std::ops::{Add, AddAssign, Neg};
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Neg},
+    str::FromStr,
+};
 
 use enum_iterator::Sequence;
 use enum_map::Enum;
@@ -266,6 +270,220 @@ impl From<PitchClass> for Interval {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Enum, Sequence)]
+/// The quality of the [`NamedInterval`]
+/// distinguishing intervals with the same number
+/// but of a different size in semitones.
+///
+/// See more: <https://en.wikipedia.org/wiki/Interval_(music)#Quality>
+pub enum IntervalQuality {
+    /// One semitone smaller than a [minor][Self::Minor]
+    /// or a [perfect][Self::Perfect] interval.
+    Diminished,
+
+    /// One semitone smaller than a [major][Self::Major] interval.
+    Minor,
+
+    /// The quality of unisons, fourths, fifths and octaves.
+    Perfect,
+
+    /// The quality of seconds, thirds, sixths and sevenths
+    /// found in the [major scale][Interval::major_scale].
+    Major,
+
+    /// One semitone larger than a [major][Self::Major]
+    /// or a [perfect][Self::Perfect] interval.
+    Augmented,
+}
+
+impl IntervalQuality {
+    const fn symbol(self) -> char {
+        match self {
+            Self::Diminished => 'd',
+            Self::Minor => 'm',
+            Self::Perfect => 'P',
+            Self::Major => 'M',
+            Self::Augmented => 'A',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// [`Interval`] named by its [quality][IntervalQuality] and number,
+/// e.g. perfect fifth (`P5`) or minor third (`m3`).
+///
+/// Unlike the [`Interval`], it distinguishes
+/// enharmonically equivalent intervals, e.g. `A4` and `d5`.
+///
+/// See more: <https://en.wikipedia.org/wiki/Interval_(music)#Naming_conventions>
+pub struct NamedInterval {
+    quality: IntervalQuality,
+    number: u8,
+}
+
+impl NamedInterval {
+    /// Create a [`NamedInterval`] checking that
+    /// the quality could be applied to the number:
+    /// - perfect intervals are unisons, fourths, fifths and octaves (and their compounds);
+    /// - major and minor intervals are all the others;
+    /// - the interval fits into the [`Interval`] (no more than 127 semitones).
+    pub fn new(quality: IntervalQuality, number: u8) -> Result<Self, String> {
+        if number == 0 {
+            return Err("Interval number should start from 1 (unison)".into());
+        }
+
+        let is_perfect_number = matches!(Self::simple_number(number), 1 | 4 | 5);
+        match (quality, is_perfect_number) {
+            (IntervalQuality::Perfect, false) => {
+                Err(format!("The interval {number} cannot be perfect"))
+            }
+            (IntervalQuality::Major | IntervalQuality::Minor, true) => {
+                Err(format!("The interval {number} cannot be major or minor"))
+            }
+            (IntervalQuality::Diminished, true) if number == 1 => {
+                Err("The unison cannot be diminished".into())
+            }
+            _ => {
+                let named = Self { quality, number };
+                if i8::try_from(named.size()).is_err() {
+                    return Err(format!(
+                        "The interval {named} is too large: {} semitones",
+                        named.size()
+                    ));
+                }
+                Ok(named)
+            }
+        }
+    }
+
+    /// The quality of the interval.
+    pub const fn quality(self) -> IntervalQuality {
+        self.quality
+    }
+
+    /// The number of the interval: 1 for unison, 2 for second, etc.
+    pub const fn number(self) -> u8 {
+        self.number
+    }
+
+    /// The number of the simple interval (inside a single octave).
+    const fn simple_number(number: u8) -> u8 {
+        (number - 1) % 7 + 1
+    }
+
+    /// The size of the interval in semitones.
+    pub fn semitones(self) -> i8 {
+        i8::try_from(self.size()).expect("The size is checked while creating the interval")
+    }
+
+    /// The size of the interval in semitones
+    /// without the restriction of the [`Interval`] range.
+    fn size(self) -> i16 {
+        // major or perfect intervals
+        const BASE: [i16; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+        let octaves = i16::from((self.number - 1) / 7);
+        let simple_number = usize::from(Self::simple_number(self.number));
+        let is_perfect_number = matches!(simple_number, 1 | 4 | 5);
+
+        let base = BASE[simple_number - 1] + octaves * i16::from(Interval::octave().0);
+        match self.quality {
+            IntervalQuality::Perfect | IntervalQuality::Major => base,
+            IntervalQuality::Minor => base - 1,
+            IntervalQuality::Augmented => base + 1,
+            IntervalQuality::Diminished if is_perfect_number => base - 1,
+            IntervalQuality::Diminished => base - 2,
+        }
+    }
+}
+
+impl From<NamedInterval> for Interval {
+    fn from(named: NamedInterval) -> Self {
+        Self(named.semitones())
+    }
+}
+
+impl From<Interval> for NamedInterval {
+    /// Choose the most common name for the (absolute value of the) [`Interval`].
+    ///
+    /// As the tritone could be named both as `A4` and `d5`,
+    /// the augmented fourth is chosen.
+    ///
+    /// The descending interval of 128 semitones (which could not appear
+    /// between two valid pitches) is named as the largest possible one.
+    fn from(interval: Interval) -> Self {
+        use IntervalQuality::{Augmented, Major, Minor, Perfect};
+
+        let semitones = interval.0.unsigned_abs().min(i8::MAX.unsigned_abs());
+        let octaves = semitones / 12;
+        let (quality, number) = match semitones % 12 {
+            0 => (Perfect, 1),
+            1 => (Minor, 2),
+            2 => (Major, 2),
+            3 => (Minor, 3),
+            4 => (Major, 3),
+            5 => (Perfect, 4),
+            6 => (Augmented, 4),
+            7 => (Perfect, 5),
+            8 => (Minor, 6),
+            9 => (Major, 6),
+            10 => (Minor, 7),
+            11 => (Major, 7),
+            _ => unreachable!("Modulo 12 is less than 12"),
+        };
+        Self {
+            quality,
+            number: number + octaves * 7,
+        }
+    }
+}
+
+impl fmt::Display for NamedInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.quality.symbol(), self.number)
+    }
+}
+
+impl FromStr for NamedInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let quality = chars
+            .next()
+            .and_then(|symbol| {
+                enum_iterator::all::<IntervalQuality>().find(|q| q.symbol() == symbol)
+            })
+            .ok_or_else(|| format!("{s:?} does not start with a valid interval quality"))?;
+        let number = chars
+            .as_str()
+            .parse()
+            .map_err(|err| format!("{s:?} does not contain a valid interval number: {err}"))?;
+        Self::new(quality, number)
+    }
+}
+
+impl fmt::Display for Interval {
+    /// Display the [`Interval`] using its [most common name][NamedInterval::from],
+    /// e.g. `P5` or `-m3` for the descending minor third.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        write!(f, "{sign}{}", NamedInterval::from(*self))
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    /// Parse the [`Interval`] from its name (e.g. `P5`, `m3`, `-M2`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix('-').map_or_else(
+            || s.parse::<NamedInterval>().map(Self::from),
+            |s| s.parse::<NamedInterval>().map(|i| -Self::from(i)),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -405,4 +623,61 @@ mod tests {
         // 11
         assert!(PitchClass::Ass.is_enharmonic_equivalent(PitchClass::B));
     }
+
+    #[test]
+    fn parse_named_intervals() {
+        assert_eq!("P5".parse::<Interval>().unwrap(), Interval(7));
+        assert_eq!("m3".parse::<Interval>().unwrap(), Interval(3));
+        assert_eq!("M7".parse::<Interval>().unwrap(), Interval(11));
+        assert_eq!("A4".parse::<Interval>().unwrap(), Interval(6));
+        assert_eq!("d5".parse::<Interval>().unwrap(), Interval(6));
+        assert_eq!("d7".parse::<Interval>().unwrap(), Interval(9));
+        assert_eq!("P8".parse::<Interval>().unwrap(), Interval::octave());
+        assert_eq!("M10".parse::<Interval>().unwrap(), Interval(16));
+        assert_eq!("-m2".parse::<Interval>().unwrap(), Interval(-1));
+    }
+
+    #[test]
+    fn parse_invalid_intervals() {
+        for s in ["", "P", "5", "X5", "P3", "m5", "M4", "d1", "P0", "Pfive"] {
+            assert!(s.parse::<NamedInterval>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn too_large_intervals() {
+        // 10 octaves and a perfect fifth (127 semitones)
+        let largest: NamedInterval = "P75".parse().unwrap();
+        assert_eq!(largest.semitones(), i8::MAX);
+        assert_eq!("-A74".parse::<Interval>().unwrap(), Interval(-126));
+        assert!("A75".parse::<NamedInterval>().is_err());
+        assert!("m76".parse::<NamedInterval>().is_err());
+        assert!("P78".parse::<NamedInterval>().is_err());
+        assert!("P253".parse::<NamedInterval>().is_err());
+
+        assert_eq!(Interval(i8::MIN).to_string(), "-P75");
+    }
+
+    #[test]
+    fn named_intervals_round_trip() {
+        for s in [
+            "P1", "m2", "M2", "m3", "M3", "P4", "A4", "d5", "P5", "m6", "M13",
+        ] {
+            let named: NamedInterval = s.parse().unwrap();
+            assert_eq!(named.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn display_intervals() {
+        for i in -24..=24 {
+            let interval = Interval(i);
+            let s = interval.to_string();
+            assert_eq!(s.parse::<Interval>().unwrap(), interval, "{s}");
+        }
+
+        assert_eq!(Interval(7).to_string(), "P5");
+        assert_eq!(Interval(-3).to_string(), "-m3");
+        assert_eq!(Interval(6).to_string(), "A4");
+    }
 }
@@ -3,4 +3,6 @@ pub mod helpers;
 pub mod interval;
 pub mod pitch;
 pub mod scale;
+pub mod time_sig;
+pub mod tuning;
 pub mod volume;
@@ -1,4 +1,5 @@
 use std::{
+    fmt, iter,
     ops::{Add, Shl, Shr, Sub},
     str::FromStr,
 };
@@ -7,11 +8,15 @@ use enum_iterator::Sequence;
 use enum_map::Enum;
 use ux2::u7;
 
-use super::interval::{Interval, Octave};
+use super::{
+    interval::{Interval, Octave},
+    scale::KeySig,
+    tuning::{Tuning, TuningSystem},
+};
 
 #[rustfmt::skip]
 #[allow(missing_docs)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Enum, Sequence)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Enum, Sequence)]
 /// Classes of perceived [octave equivalences](https://en.wikipedia.org/wiki/Octave#Equivalence).
 ///
 /// Every [`PitchClass`] relates to the same-named [`Pitch`]es
@@ -87,6 +92,57 @@ impl PitchClass {
     pub const fn is_enharmonic_equivalent(self, other: Self) -> bool {
         self.distance_from_c() == other.distance_from_c()
     }
+
+    /// The natural [`PitchClass`] (a white piano key) having the same letter name
+    /// along with the number of semitones it is altered by
+    /// (positive for the sharps and negative for the flats).
+    pub(crate) const fn spelling(self) -> (Self, i8) {
+        match self {
+            Self::Cff => (Self::C, -2),
+            Self::Cf => (Self::C, -1),
+            Self::C => (Self::C, 0),
+            Self::Cs => (Self::C, 1),
+            Self::Css => (Self::C, 2),
+            Self::Dff => (Self::D, -2),
+            Self::Df => (Self::D, -1),
+            Self::D => (Self::D, 0),
+            Self::Ds => (Self::D, 1),
+            Self::Dss => (Self::D, 2),
+            Self::Eff => (Self::E, -2),
+            Self::Ef => (Self::E, -1),
+            Self::E => (Self::E, 0),
+            Self::Es => (Self::E, 1),
+            Self::Ess => (Self::E, 2),
+            Self::Fff => (Self::F, -2),
+            Self::Ff => (Self::F, -1),
+            Self::F => (Self::F, 0),
+            Self::Fs => (Self::F, 1),
+            Self::Fss => (Self::F, 2),
+            Self::Gff => (Self::G, -2),
+            Self::Gf => (Self::G, -1),
+            Self::G => (Self::G, 0),
+            Self::Gs => (Self::G, 1),
+            Self::Gss => (Self::G, 2),
+            Self::Aff => (Self::A, -2),
+            Self::Af => (Self::A, -1),
+            Self::A => (Self::A, 0),
+            Self::As => (Self::A, 1),
+            Self::Ass => (Self::A, 2),
+            Self::Bff => (Self::B, -2),
+            Self::Bf => (Self::B, -1),
+            Self::B => (Self::B, 0),
+            Self::Bs => (Self::B, 1),
+            Self::Bss => (Self::B, 2),
+        }
+    }
+
+    /// The [`PitchClass`] having the given letter name
+    /// altered by the given number of semitones
+    /// or `None` if the alteration exceeds the double accidentals.
+    pub(crate) fn from_spelling(letter: Self, accidental: i8) -> Option<Self> {
+        let letter = letter.spelling().0;
+        enum_iterator::all::<Self>().find(|pc| pc.spelling() == (letter, accidental))
+    }
 }
 
 macro_rules! match_str_to_pitch_class {
@@ -202,11 +258,67 @@ impl Pitch {
     /// - <https://en.wikipedia.org/wiki/Piano_key_frequencies>
     /// - <https://en.wikipedia.org/wiki/Musical_note#Pitch_frequency_in_hertz>
     pub fn get_frequency(self) -> f64 {
+        self.get_frequency_with_tuning(Tuning::default())
+    }
+
+    /// Frequency of a pitch in Herz (Hz) using the custom reference [`Tuning`].
+    pub fn get_frequency_with_tuning(self, tuning: Tuning) -> f64 {
         let a4 = Self::A(Octave::OneLined);
         let interval_to_a4 = self.abs() - a4.abs();
         let octaves_from_a4 =
             f64::from(interval_to_a4.get_inner()) / f64::from(u8::from(Octave::semitones_number()));
-        octaves_from_a4.exp2() * Self::CONCERT_A_FREQUENCY
+        octaves_from_a4.exp2() * tuning.a4_hz
+    }
+
+    /// The nearest equal-tempered [`Pitch`] for the given frequency in Herz (Hz).
+    ///
+    /// The frequencies outside the range of [`AbsPitch`]
+    /// are clipped to the lowest or the highest pitch.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Piano_key_frequencies>
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // the value is clamped before casting
+    pub fn from_frequency(frequency: f64) -> Self {
+        let a4 = f64::from(Self::A(Octave::OneLined).abs().get_u8());
+        let semitones = f64::from(u8::from(Octave::semitones_number()));
+        let abs = semitones.mul_add((frequency / Self::CONCERT_A_FREQUENCY).log2(), a4);
+        let abs = abs.round().clamp(0.0, f64::from(u8::from(u7::MAX))) as u8;
+        Self::from(AbsPitch(u7::new(abs)))
+    }
+
+    /// The nearest [`Pitch`] for the given frequency in Herz (Hz)
+    /// in the arbitrary [`TuningSystem`] while playing in the given [key][KeySig].
+    pub fn from_frequency_in<T>(frequency: f64, system: &T, key: KeySig) -> Self
+    where
+        T: TuningSystem + ?Sized,
+    {
+        (0..=u8::from(u7::MAX))
+            .map(|p| Self::from(AbsPitch(u7::new(p))))
+            .min_by(|p1, p2| {
+                let d1 = (frequency / system.frequency(*p1, key)).ln().abs();
+                let d2 = (frequency / system.frequency(*p2, key)).ln().abs();
+                d1.total_cmp(&d2)
+            })
+            .expect("The range of pitches is not empty")
+    }
+
+    /// Build the first `count` harmonics of the `fundamental` [`Pitch`].
+    ///
+    /// Every harmonic is represented with its exact frequency (in Hz),
+    /// the nearest equal-tempered [`Pitch`] and the deviation of the harmonic
+    /// from the equal-tempered pitch in [cents](https://en.wikipedia.org/wiki/Cent_(music)).
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Harmonic_series_(music)>
+    pub fn harmonic_series(fundamental: Self, count: usize) -> Vec<(f64, Self, f64)> {
+        let f0 = fundamental.get_frequency();
+        (1..=count)
+            .map_while(|n| u32::try_from(n).ok())
+            .map(|n| {
+                let frequency = f0 * f64::from(n);
+                let pitch = Self::from_frequency(frequency);
+                let cents = 1200.0 * (frequency / pitch.get_frequency()).log2();
+                (frequency, pitch, cents)
+            })
+            .collect()
     }
 }
 
@@ -233,6 +345,25 @@ impl Pitch {
     pub fn prev(self) -> Self {
         self << Interval::semi_tone()
     }
+
+    /// Iterate over every chromatic [`Pitch`] from `low` to `high` inclusive.
+    ///
+    /// The iterator is empty if the `low` pitch is higher than the `high` one.
+    pub fn range(low: Self, high: Self) -> impl Iterator<Item = Self> + Clone {
+        let high = high.abs();
+        iter::successors(Some(low), |p| {
+            let next = p.next();
+            // the pitch could not be increased anymore
+            (next.abs() > p.abs()).then_some(next)
+        })
+        .take_while(move |p| p.abs() <= high)
+    }
+
+    /// Signed number of semitones to get from this [`Pitch`] to the `other`:
+    /// positive if the `other` is higher and negative otherwise.
+    pub fn steps_to(self, other: Self) -> i16 {
+        i16::from(other.abs().get_u8()) - i16::from(self.abs().get_u8())
+    }
 }
 
 impl Shr<Interval> for Pitch {
@@ -273,6 +404,88 @@ impl From<AbsPitch> for Pitch {
     }
 }
 
+/// Displays the [`Pitch`] in the scientific pitch notation,
+/// e.g. `C#4` or `Bb3`.
+///
+/// See more: <https://en.wikipedia.org/wiki/Scientific_pitch_notation>
+impl fmt::Display for Pitch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (letter, accidental) = self.class.spelling();
+        let letter = match letter {
+            PitchClass::C => 'C',
+            PitchClass::D => 'D',
+            PitchClass::E => 'E',
+            PitchClass::F => 'F',
+            PitchClass::G => 'G',
+            PitchClass::A => 'A',
+            PitchClass::B => 'B',
+            _ => unreachable!("The letter name is a natural pitch class"),
+        };
+        let accidentals =
+            if accidental < 0 { "b" } else { "#" }.repeat(accidental.unsigned_abs().into());
+        write!(f, "{letter}{accidentals}{}", self.octave as i8)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+/// Defines which of the [enharmonically equivalent][PitchClass::is_enharmonic_equivalent]
+/// [`PitchClass`]es to choose for the black piano keys
+/// while converting an [`AbsPitch`] into a [`Pitch`].
+///
+/// See more: <https://en.wikipedia.org/wiki/Enharmonic_equivalence>
+pub enum SpellingPreference {
+    /// Always use the sharps (e.g. `A#`), the same way as [`Pitch::from`] does.
+    #[default]
+    Sharps,
+
+    /// Always use the flats (e.g. `Bb`).
+    Flats,
+
+    /// Spell the notes of the scale as the [key signature][KeySig] requires
+    /// (e.g. the `Cb` in the Gb major),
+    /// and the other notes with the sharps in the sharp keys
+    /// or with the flats in the flat keys.
+    KeyAware(KeySig),
+}
+
+impl Pitch {
+    /// Convert the [`AbsPitch`] into a [`Pitch`]
+    /// spelled according to the given [`SpellingPreference`].
+    pub fn from_abs_with(abs_pitch: AbsPitch, preference: SpellingPreference) -> Self {
+        const FLAT_PITCHES: [PitchClass; 12] = [
+            PitchClass::C,
+            PitchClass::Df,
+            PitchClass::D,
+            PitchClass::Ef,
+            PitchClass::E,
+            PitchClass::F,
+            PitchClass::Gf,
+            PitchClass::G,
+            PitchClass::Af,
+            PitchClass::A,
+            PitchClass::Bf,
+            PitchClass::B,
+        ];
+
+        match preference {
+            SpellingPreference::Sharps => Self::from(abs_pitch),
+            SpellingPreference::Flats => {
+                let (octave, interval) = abs_pitch.into();
+                Self::new(FLAT_PITCHES[usize::from(interval)], octave)
+            }
+            SpellingPreference::KeyAware(key) => {
+                if key.contains(abs_pitch) {
+                    key.spell(abs_pitch)
+                } else if key.sharps() < 0 {
+                    Self::from_abs_with(abs_pitch, SpellingPreference::Flats)
+                } else {
+                    Self::from(abs_pitch)
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// A 7-bit number associated with the most audible [Pitch]es.
 ///
@@ -412,6 +625,64 @@ impl Sub for AbsPitch {
 mod tests {
     use super::*;
 
+    #[test]
+    fn display_scientific_notation() {
+        assert_eq!(Pitch::Cs(Octave::OneLined).to_string(), "C#4");
+        assert_eq!(Pitch::Bf(Octave::Small).to_string(), "Bb3");
+        assert_eq!(Pitch::Fss(Octave::Great).to_string(), "F##2");
+        assert_eq!(Pitch::A(Octave::OctoContra).to_string(), "A-1");
+    }
+
+    #[test]
+    fn spelling_round_trip() {
+        assert_eq!(PitchClass::Gss.spelling(), (PitchClass::G, 2));
+        assert_eq!(PitchClass::Ef.spelling(), (PitchClass::E, -1));
+        for pc in enum_iterator::all::<PitchClass>() {
+            let (letter, accidental) = pc.spelling();
+            assert_eq!(PitchClass::from_spelling(letter, accidental), Some(pc));
+        }
+        assert_eq!(PitchClass::from_spelling(PitchClass::C, 3), None);
+    }
+
+    #[test]
+    fn spell_black_key_with_sharps_or_flats() {
+        let abs = Pitch::As(Octave::OneLined).abs();
+        assert_eq!(
+            Pitch::from_abs_with(abs, SpellingPreference::Sharps),
+            Pitch::As(Octave::OneLined)
+        );
+        assert_eq!(
+            Pitch::from_abs_with(abs, SpellingPreference::Flats),
+            Pitch::Bf(Octave::OneLined)
+        );
+
+        let white = Pitch::E(Octave::Small).abs();
+        assert_eq!(
+            Pitch::from_abs_with(white, SpellingPreference::Flats),
+            Pitch::E(Octave::Small)
+        );
+    }
+
+    #[test]
+    fn spell_in_key() {
+        let oc4 = Octave::OneLined;
+        let spell = |pitch: Pitch, key| {
+            Pitch::from_abs_with(pitch.abs(), SpellingPreference::KeyAware(key))
+        };
+
+        let f_major = KeySig::Major(PitchClass::F);
+        assert_eq!(spell(Pitch::As(oc4), f_major), Pitch::Bf(oc4));
+        // the chromatic note in the flat key
+        assert_eq!(spell(Pitch::Cs(oc4), f_major), Pitch::Df(oc4));
+
+        let gf_major = KeySig::Major(PitchClass::Gf);
+        assert_eq!(spell(Pitch::B(oc4), gf_major), Pitch::Cf(Octave::TwoLined));
+
+        let d_major = KeySig::Major(PitchClass::D);
+        assert_eq!(spell(Pitch::Fs(oc4), d_major), Pitch::Fs(oc4));
+        assert_eq!(spell(Pitch::Gs(oc4), d_major), Pitch::Gs(oc4));
+    }
+
     #[test]
     fn get_a440_freq() {
         let pitch = Pitch::A(Octave::OneLined);
@@ -627,6 +898,97 @@ mod tests {
         assert_eq!(u8::from(p.prev().abs().0), 0);
     }
 
+    #[test]
+    fn chromatic_range() {
+        let c4 = Pitch::C(Octave::OneLined);
+        let c5 = Pitch::C(Octave::TwoLined);
+        let range: Vec<_> = Pitch::range(c4, c5).collect();
+        assert_eq!(range.len(), 13);
+        assert_eq!(range.first(), Some(&c4));
+        assert_eq!(range.last(), Some(&c5));
+        assert!(range.windows(2).all(|w| w[0].steps_to(w[1]) == 1));
+
+        assert_eq!(Pitch::range(c5, c4).count(), 0);
+        assert_eq!(Pitch::range(c4, c4).collect::<Vec<_>>(), [c4]);
+    }
+
+    #[test]
+    fn range_to_the_highest_pitch() {
+        let highest = Pitch::from(AbsPitch(u7::new(127)));
+        let low = Pitch::from(AbsPitch(u7::new(120)));
+        assert_eq!(Pitch::range(low, highest).count(), 8);
+    }
+
+    #[test]
+    fn steps_between_pitches() {
+        let c4 = Pitch::C(Octave::OneLined);
+        let g4 = Pitch::G(Octave::OneLined);
+        assert_eq!(c4.steps_to(g4), 7);
+        assert_eq!(g4.steps_to(c4), -7);
+        assert_eq!(c4.steps_to(Pitch::Bs(Octave::Small)), 0);
+    }
+
+    #[test]
+    fn pitch_from_frequency() {
+        assert_eq!(Pitch::from_frequency(440.0), Pitch::A(Octave::OneLined));
+        assert_eq!(Pitch::from_frequency(445.0), Pitch::A(Octave::OneLined));
+        assert_eq!(Pitch::from_frequency(261.6), Pitch::C(Octave::OneLined));
+        assert_eq!(Pitch::from_frequency(1.0), Pitch::C(Octave::OctoContra));
+        assert_eq!(Pitch::from_frequency(1e6), Pitch::G(Octave::SixLined));
+    }
+
+    #[test]
+    fn harmonics_of_c() {
+        let series = Pitch::harmonic_series(Pitch::C(Octave::Small), 5);
+        let pitches: Vec<_> = series.iter().map(|(_, p, _)| *p).collect();
+        assert_eq!(
+            pitches,
+            [
+                Pitch::C(Octave::Small),
+                Pitch::C(Octave::OneLined),
+                Pitch::G(Octave::OneLined),
+                Pitch::C(Octave::TwoLined),
+                Pitch::E(Octave::TwoLined),
+            ]
+        );
+
+        let (frequency, _, cents) = series[2];
+        assert!((frequency / series[0].0 - 3.0).abs() < 1e-9);
+        // the perfect fifth is slightly wider than the tempered one
+        assert!((cents - 1.955).abs() < 0.001, "{cents}");
+        // the major third is noticeably narrower
+        assert!((series[4].2 + 13.686).abs() < 0.001, "{}", series[4].2);
+    }
+
+    #[test]
+    fn frequency_with_custom_tuning() {
+        let tuning = Tuning::new(432.0);
+        let a4 = Pitch::A(Octave::OneLined);
+        assert!((a4.get_frequency_with_tuning(tuning) - 432.0).abs() < f64::EPSILON);
+
+        let c4 = Pitch::C(Octave::OneLined);
+        let ratio = c4.get_frequency_with_tuning(tuning) / c4.get_frequency();
+        assert!((ratio - 432.0 / 440.0).abs() < 1e-12);
+        assert_is_close_freq(c4.get_frequency_with_tuning(Tuning::BAROQUE), 246.761);
+    }
+
+    #[test]
+    fn pitch_from_frequency_in_just_intonation() {
+        use crate::prim::tuning::{EqualTemperament, JustIntonation};
+
+        let key = KeySig::Major(PitchClass::C);
+        let just = JustIntonation::default();
+        let e4 = Pitch::E(Octave::OneLined);
+        let just_e4 = just.frequency(e4, key);
+        // the just major third is 14 cents lower than the tempered one
+        assert!(just_e4 < e4.get_frequency());
+        assert_eq!(Pitch::from_frequency_in(just_e4, &just, key), e4);
+        assert_eq!(
+            Pitch::from_frequency_in(just_e4, &EqualTemperament::default(), key),
+            e4
+        );
+    }
+
     #[test]
     fn from_octave() {
         for (i, oc) in enum_iterator::all::<Octave>().enumerate() {
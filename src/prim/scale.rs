@@ -1,3 +1,5 @@
+use enum_iterator::Sequence;
+
 use super::{
     interval::{Interval, Octave},
     pitch::{AbsPitch, Pitch, PitchClass},
@@ -57,6 +59,105 @@ impl KeySig {
             Some(*state)
         })
     }
+
+    /// Whether the [`AbsPitch`] belongs to the scale (in any octave).
+    pub fn contains(self, pitch: AbsPitch) -> bool {
+        self.scale_degree(pitch).is_some()
+    }
+
+    /// The degree (from 1 for the tonic to 7 for the leading tone)
+    /// of the [`AbsPitch`] in the scale or `None` if the pitch
+    /// does not belong to the scale.
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Degree_(music)>
+    pub fn scale_degree(self, pitch: AbsPitch) -> Option<u8> {
+        let oct_size =
+            i8::try_from(u8::from(Octave::semitones_number())).expect("12 is low enough");
+        let pitch_offset = u8::from(pitch.get_inner()) % u8::from(Octave::semitones_number());
+        self.get_scale()
+            .take(7)
+            .position(|pc| {
+                u8::try_from(pc.distance_from_c().rem_euclid(oct_size)).ok() == Some(pitch_offset)
+            })
+            .map(|i| u8::try_from(i + 1).expect("Diatonic scale has 7 degrees"))
+    }
+
+    /// The number of sharps (if positive) or flats (if negative)
+    /// in the [key signature](https://en.wikipedia.org/wiki/Key_signature),
+    /// e.g. 1 for the G major and -3 for the C minor.
+    ///
+    /// The theoretical keys (e.g. the G# major with the double sharp)
+    /// have more than 7 accidentals.
+    pub fn sharps(self) -> i8 {
+        self.spelled_degrees()
+            .map(|(_letter, accidental)| accidental)
+            .sum()
+    }
+
+    /// Sequence of [`PitchClass`]-es of the scale (without the repeated tonic)
+    /// spelled according to the rules: every letter name
+    /// is used exactly once, e.g. the F major has the `Bb` rather than the `A#`.
+    fn spelled_scale(self) -> impl Iterator<Item = PitchClass> {
+        self.spelled_degrees().map(|(letter, accidental)| {
+            PitchClass::from_spelling(letter, accidental)
+                .expect("Diatonic scale requires at most double accidentals")
+        })
+    }
+
+    /// The letter names of the scale degrees (without the repeated tonic)
+    /// with the number of semitones they are altered by.
+    fn spelled_degrees(self) -> impl Iterator<Item = (PitchClass, i8)> {
+        const LETTERS: [PitchClass; 7] = [
+            PitchClass::C,
+            PitchClass::D,
+            PitchClass::E,
+            PitchClass::F,
+            PitchClass::G,
+            PitchClass::A,
+            PitchClass::B,
+        ];
+
+        let (tonic_letter, _) = self.pitch_class().spelling();
+        let tonic_index = LETTERS
+            .iter()
+            .position(|&l| l == tonic_letter)
+            .expect("Every pitch class has a letter name");
+
+        let oct_size =
+            i8::try_from(u8::from(Octave::semitones_number())).expect("12 is low enough");
+        self.get_intervals_scale()
+            .take(7)
+            .enumerate()
+            .map(move |(i, interval)| {
+                let letter = LETTERS[(tonic_index + i) % LETTERS.len()];
+                let mut accidental = (interval.0 - letter.distance_from_c()).rem_euclid(oct_size);
+                if accidental > oct_size / 2 {
+                    accidental -= oct_size;
+                }
+                (letter, accidental)
+            })
+    }
+
+    /// Convert the [`AbsPitch`] into a [`Pitch`]
+    /// spelled as a [`PitchClass`] of the scale if possible.
+    ///
+    /// The pitches that do not belong to the scale
+    /// are converted in a [default way][Pitch::from].
+    pub(crate) fn spell(self, pitch: AbsPitch) -> Pitch {
+        let oct_size = i16::from(u8::from(Octave::semitones_number()));
+        let abs = i16::from(pitch.get_u8());
+        self.spelled_scale()
+            .find_map(|pc| {
+                let distance = i16::from(pc.distance_from_c());
+                if (abs - distance).rem_euclid(oct_size) != 0 {
+                    return None;
+                }
+                let octave = i8::try_from((abs - distance) / oct_size - 1).ok()?;
+                let octave = Octave::from_i8(octave).ok()?;
+                Some(Pitch::new(pc, octave))
+            })
+            .unwrap_or_else(|| Pitch::from(pitch))
+    }
 }
 
 impl Interval {
@@ -123,9 +224,132 @@ impl Pitch {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Copy, Clone, PartialOrd, Ord, Hash, Sequence)]
+/// Diatonic scale started from one of the degrees of the [major scale][Interval::major_scale].
+///
+/// See more: <https://en.wikipedia.org/wiki/Mode_(music)#Modern_modes>
+pub enum Mode {
+    /// The major scale itself.
+    Ionian,
+    /// Started from the second degree: the minor scale with the major sixth.
+    Dorian,
+    /// Started from the third degree: the minor scale with the minor second.
+    Phrygian,
+    /// Started from the fourth degree: the major scale with the augmented fourth.
+    Lydian,
+    /// Started from the fifth degree: the major scale with the minor seventh.
+    Mixolydian,
+    /// Started from the sixth degree: the natural minor scale.
+    Aeolian,
+    /// Started from the seventh degree: the minor scale with the diminished fifth.
+    Locrian,
+}
+
+impl Mode {
+    /// Sequence of [`Interval`]s to create a scale of the [`Mode`]
+    /// (similar to [`Interval::major_scale`]).
+    pub fn intervals(self) -> [Interval; 8] {
+        let major = Interval::major_scale();
+        let shift = self as usize;
+        let mut intervals = [Interval::zero(); 8];
+        for (i, interval) in intervals.iter_mut().enumerate().skip(1) {
+            // skip the leading zero of the major scale while rotating
+            *interval = major[(shift + i - 1) % 7 + 1];
+        }
+        intervals
+    }
+
+    /// Distances of the [`Mode`]'s degrees from its tonic (without the repeated tonic).
+    fn degrees(self) -> impl Iterator<Item = Interval> {
+        self.intervals()
+            .into_iter()
+            .scan(Interval::zero(), |distance, interval| {
+                *distance += interval;
+                Some(*distance)
+            })
+            .take(7)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, PartialOrd, Ord, Hash, Sequence)]
+/// The type of the chord defined by the intervals of its tones above the root.
+///
+/// See more: <https://en.wikipedia.org/wiki/Chord_names_and_symbols_(popular_music)>
+pub enum ChordQuality {
+    /// The major third and the perfect fifth, e.g. `C`.
+    Major,
+    /// The minor third and the perfect fifth, e.g. `Cm`.
+    Minor,
+    /// The minor third and the diminished fifth, e.g. `Cdim`.
+    Diminished,
+    /// The major third and the augmented fifth, e.g. `Caug`.
+    Augmented,
+    /// The major triad with the major seventh, e.g. `Cmaj7`.
+    Major7,
+    /// The minor triad with the minor seventh, e.g. `Cm7`.
+    Minor7,
+    /// The major triad with the minor seventh, e.g. `C7`.
+    Dominant7,
+    /// The diminished triad with the minor seventh, e.g. `Cm7b5`.
+    HalfDiminished7,
+    /// The diminished triad with the diminished seventh, e.g. `Cdim7`.
+    Diminished7,
+}
+
+impl ChordQuality {
+    /// Distances of the chord's tones from its root (including the root itself).
+    pub fn intervals(self) -> Vec<Interval> {
+        let semitones: &[i8] = match self {
+            Self::Major => &[0, 4, 7],
+            Self::Minor => &[0, 3, 7],
+            Self::Diminished => &[0, 3, 6],
+            Self::Augmented => &[0, 4, 8],
+            Self::Major7 => &[0, 4, 7, 11],
+            Self::Minor7 => &[0, 3, 7, 10],
+            Self::Dominant7 => &[0, 4, 7, 10],
+            Self::HalfDiminished7 => &[0, 3, 6, 10],
+            Self::Diminished7 => &[0, 3, 6, 9],
+        };
+        semitones.iter().copied().map(Interval::from).collect()
+    }
+}
+
+/// Suggest the [`Mode`]s to improvise over the chord.
+///
+/// Every suggested [`Mode`] is built on the chord's `root` and contains
+/// all the tones of the chord, e.g. the `Dm7` suggests the D Dorian
+/// (along with the Phrygian and the Aeolian).
+/// The [`Mode`]s are returned in the order of their declaration.
+/// The chords which do not fit into any diatonic [`Mode`]
+/// (e.g. the [augmented][ChordQuality::Augmented] one) have no suggestions.
+///
+/// See more: <https://en.wikipedia.org/wiki/Chord-scale_system>
+pub fn scales_for_chord(root: PitchClass, quality: ChordQuality) -> Vec<(Mode, PitchClass)> {
+    let chord = quality.intervals();
+    enum_iterator::all::<Mode>()
+        .filter(|mode| {
+            let degrees: Vec<_> = mode.degrees().collect();
+            chord.iter().all(|tone| degrees.contains(tone))
+        })
+        .map(|mode| (mode, root))
+        .collect()
+}
+
 const DIATONIC_SIZE: i8 = 7;
 
 impl AbsPitch {
+    /// Find the closest [`AbsPitch`] that belongs
+    /// to the given [diatonic scale][KeySig].
+    ///
+    /// If two pitches of the scale are equally close,
+    /// the lower one is chosen.
+    pub fn snap_to_key(self, key: KeySig) -> Self {
+        (0..=i8::try_from(u8::from(Octave::semitones_number())).expect("12 is low enough"))
+            .flat_map(|d| [self - Interval(d), self + Interval(d)])
+            .find(|p| key.contains(*p))
+            .unwrap_or(self)
+    }
+
     /// Transpose current [`AbsPitch`]
     /// in the given [diatonic scale][KeySig].
     pub fn diatonic_trans(self, key: KeySig, degrees: i8) -> Self {
@@ -287,6 +511,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn key_sig_sharps_and_flats() {
+        assert_eq!(KeySig::Major(PitchClass::C).sharps(), 0);
+        assert_eq!(KeySig::Minor(PitchClass::A).sharps(), 0);
+        assert_eq!(KeySig::Major(PitchClass::G).sharps(), 1);
+        assert_eq!(KeySig::Minor(PitchClass::E).sharps(), 1);
+        assert_eq!(KeySig::Major(PitchClass::Cs).sharps(), 7);
+        assert_eq!(KeySig::Major(PitchClass::F).sharps(), -1);
+        assert_eq!(KeySig::Minor(PitchClass::C).sharps(), -3);
+        assert_eq!(KeySig::Major(PitchClass::Cf).sharps(), -7);
+        assert_eq!(KeySig::Major(PitchClass::Gs).sharps(), 8);
+    }
+
     #[test]
     fn diatonic_trans_c_major() {
         let oc4 = Octave::OneLined;
@@ -450,4 +687,98 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn snap_to_key_chooses_lower_on_ties() {
+        let key = KeySig::Major(PitchClass::C);
+        let snapped: Vec<_> = [
+            PitchClass::C,
+            PitchClass::Cs,
+            PitchClass::Fs,
+            PitchClass::Bf,
+        ]
+        .into_iter()
+        .map(|pc| Pitch::from(Pitch::new(pc, Octave::OneLined).abs().snap_to_key(key)))
+        .collect();
+
+        assert_eq!(
+            snapped,
+            [
+                Pitch::new(PitchClass::C, Octave::OneLined),
+                Pitch::new(PitchClass::C, Octave::OneLined),
+                Pitch::new(PitchClass::F, Octave::OneLined),
+                Pitch::new(PitchClass::A, Octave::OneLined),
+            ]
+        );
+    }
+
+    #[test]
+    fn spell_in_key() {
+        let key = KeySig::Major(PitchClass::F);
+        let b_flat = Pitch::new(PitchClass::As, Octave::OneLined).abs();
+        assert_eq!(
+            key.spell(b_flat),
+            Pitch::new(PitchClass::Bf, Octave::OneLined)
+        );
+
+        let key = KeySig::Major(PitchClass::Cs);
+        let b_sharp = Pitch::new(PitchClass::C, Octave::OneLined).abs();
+        assert_eq!(
+            key.spell(b_sharp),
+            Pitch::new(PitchClass::Bs, Octave::Small)
+        );
+
+        // not in a key
+        let key = KeySig::Major(PitchClass::C);
+        let c_sharp = Pitch::new(PitchClass::Cs, Octave::OneLined).abs();
+        assert_eq!(
+            key.spell(c_sharp),
+            Pitch::new(PitchClass::Cs, Octave::OneLined)
+        );
+    }
+
+    #[test]
+    fn dorian_mode() {
+        let oc4 = Octave::OneLined;
+        let dorian: Vec<_> = Pitch::D(oc4)
+            .get_scale(Mode::Dorian.intervals().into_iter())
+            .collect();
+        let white_keys: Vec<_> = Pitch::C(oc4).major_scale().skip(1).collect();
+        assert_eq!(dorian.len(), 8);
+        assert_eq!(
+            dorian.iter().map(|p| p.abs()).collect::<Vec<_>>(),
+            white_keys
+                .iter()
+                .chain(&[Pitch::D(Octave::TwoLined)])
+                .map(|p| p.abs())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn scale_degrees_of_minor() {
+        let key = KeySig::Minor(PitchClass::A);
+        let oc4 = Octave::OneLined;
+        assert_eq!(key.scale_degree(Pitch::A(oc4).abs()), Some(1));
+        assert_eq!(key.scale_degree(Pitch::C(Octave::TwoLined).abs()), Some(3));
+        assert_eq!(key.scale_degree(Pitch::G(oc4).abs()), Some(7));
+        assert_eq!(key.scale_degree(Pitch::Gs(oc4).abs()), None);
+    }
+
+    #[test]
+    fn dominant_seventh_suggests_mixolydian() {
+        let scales = scales_for_chord(PitchClass::G, ChordQuality::Dominant7);
+        assert!(scales.contains(&(Mode::Mixolydian, PitchClass::G)));
+        assert_eq!(scales, [(Mode::Mixolydian, PitchClass::G)]);
+    }
+
+    #[test]
+    fn minor_seventh_suggests_dorian() {
+        let scales = scales_for_chord(PitchClass::D, ChordQuality::Minor7);
+        assert_eq!(
+            scales,
+            [Mode::Dorian, Mode::Phrygian, Mode::Aeolian].map(|m| (m, PitchClass::D))
+        );
+        assert!(scales_for_chord(PitchClass::C, ChordQuality::Augmented).is_empty());
+    }
 }
@@ -0,0 +1,166 @@
+use num_rational::Ratio;
+
+use super::duration::{Dur, DurT};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Specifies how many beats are contained in each measure (bar),
+/// and which note value is equivalent to a beat.
+///
+/// The beats of a measure could be grouped together
+/// to define the strong beats of the [compound][Self::is_compound]
+/// or asymmetric (e.g. 7/8 as `2+2+3`) meters.
+///
+/// See more:
+/// - <https://en.wikipedia.org/wiki/Time_signature>
+/// - <https://en.wikipedia.org/wiki/Metre_(music)>
+pub struct TimeSignature {
+    beats: u8,
+    beat_unit: u8,
+    grouping: Vec<u8>,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        // the 'common time'
+        Self::new(4, 4)
+    }
+}
+
+impl TimeSignature {
+    /// Create a [`TimeSignature`] with the given number of beats
+    /// of the given note value (e.g. `4` for quarter notes) per measure.
+    ///
+    /// The beats are grouped by default:
+    /// - for the [compound meters][Self::is_compound] in groups of three;
+    /// - for all the others every beat forms its own group.
+    pub fn new(beats: u8, beat_unit: u8) -> Self {
+        assert!(beats > 0, "Measure should contain at least one beat");
+        assert!(
+            beat_unit.is_power_of_two(),
+            "Beat unit should be a power of 2"
+        );
+
+        let group_size = if is_compound(beats, beat_unit) { 3 } else { 1 };
+        let grouping = vec![group_size; usize::from(beats / group_size)];
+        Self {
+            beats,
+            beat_unit,
+            grouping,
+        }
+    }
+
+    /// Explicitly define the groups of beats,
+    /// e.g. `[2, 2, 3]` for the 7/8 meter.
+    ///
+    /// The total number of beats in the groups
+    /// should be equal to the number of beats in a measure.
+    pub fn with_grouping(self, grouping: Vec<u8>) -> Result<Self, String> {
+        let total: u32 = grouping.iter().copied().map(u32::from).sum();
+        if grouping.contains(&0) {
+            return Err("Every group should contain at least one beat".into());
+        }
+        if total != u32::from(self.beats) {
+            return Err(format!(
+                "The grouping {:?} does not match the number of beats: {}",
+                grouping, self.beats
+            ));
+        }
+        Ok(Self { grouping, ..self })
+    }
+
+    /// Number of beats in a measure.
+    pub const fn beats(&self) -> u8 {
+        self.beats
+    }
+
+    /// Note value of a single beat, e.g. `4` for quarter notes.
+    pub const fn beat_unit(&self) -> u8 {
+        self.beat_unit
+    }
+
+    /// The groups of beats in a measure.
+    pub fn grouping(&self) -> &[u8] {
+        &self.grouping
+    }
+
+    /// Duration of a single beat.
+    pub fn beat_duration(&self) -> Dur {
+        Dur::recip(DurT::from(self.beat_unit))
+    }
+
+    /// Duration of the whole measure.
+    pub fn measure_duration(&self) -> Dur {
+        self.beat_duration() * DurT::from(self.beats)
+    }
+
+    /// Whether the beats of the meter are naturally divided into three parts
+    /// (e.g. 6/8, 9/8, 12/8).
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Metre_(music)#Compound_metre>
+    pub const fn is_compound(&self) -> bool {
+        is_compound(self.beats, self.beat_unit)
+    }
+
+    /// Positions of the strong beats (the starts of the groups)
+    /// as offsets from the start of the measure in terms of whole notes.
+    pub fn beat_positions(&self) -> Vec<Ratio<u32>> {
+        let beat = self.beat_duration().into_ratio();
+        self.grouping
+            .iter()
+            .scan(0, |offset, size| {
+                let start = *offset;
+                *offset += u32::from(*size);
+                Some(beat * start)
+            })
+            .collect()
+    }
+}
+
+const fn is_compound(beats: u8, beat_unit: u8) -> bool {
+    beats > 3 && beats.is_multiple_of(3) && beat_unit >= 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_time() {
+        let ts = TimeSignature::default();
+        assert!(!ts.is_compound());
+        assert_eq!(ts.measure_duration(), Dur::WHOLE);
+        assert_eq!(
+            ts.beat_positions(),
+            [0, 1, 2, 3].map(|x| Ratio::new(x, 4)).to_vec()
+        );
+    }
+
+    #[test]
+    fn compound_six_eight() {
+        let ts = TimeSignature::new(6, 8);
+        assert!(ts.is_compound());
+        assert_eq!(ts.measure_duration(), Dur::DOTTED_HALF);
+        assert_eq!(ts.grouping(), [3, 3]);
+        assert_eq!(ts.beat_positions(), [Ratio::from(0), Ratio::new(3, 8)]);
+    }
+
+    #[test]
+    fn asymmetric_seven_eight() {
+        let ts = TimeSignature::new(7, 8)
+            .with_grouping(vec![2, 2, 3])
+            .unwrap();
+        assert!(!ts.is_compound());
+        assert_eq!(
+            ts.beat_positions(),
+            [Ratio::from(0), Ratio::new(1, 4), Ratio::new(1, 2)]
+        );
+    }
+
+    #[test]
+    fn invalid_grouping() {
+        assert!(TimeSignature::new(7, 8).with_grouping(vec![2, 2]).is_err());
+        assert!(TimeSignature::new(7, 8)
+            .with_grouping(vec![0, 4, 3])
+            .is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use super::{interval::Octave, pitch::Pitch, scale::KeySig};
+
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+/// The reference frequency used to tune all the other pitches,
+/// defined as the frequency of the [A4][Pitch::A].
+///
+/// See more:
+/// - <https://en.wikipedia.org/wiki/Concert_pitch>
+/// - <https://en.wikipedia.org/wiki/A440_(pitch_standard)>
+pub struct Tuning {
+    /// Frequency of the A4 in Herz (Hz).
+    pub a4_hz: f64,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::CONCERT
+    }
+}
+
+impl Tuning {
+    /// The modern standard concert pitch (A4 = 440 Hz).
+    pub const CONCERT: Self = Self::new(Pitch::CONCERT_A_FREQUENCY);
+
+    /// The common pitch for the historically informed performances
+    /// of the Baroque music (A4 = 415 Hz).
+    ///
+    /// See more: <https://en.wikipedia.org/wiki/Concert_pitch#Baroque_pitch>
+    pub const BAROQUE: Self = Self::new(415.0);
+
+    /// Create a [`Tuning`] with the given frequency of the A4.
+    pub const fn new(a4_hz: f64) -> Self {
+        Self { a4_hz }
+    }
+}
+
+/// The system of defining the frequency for every [`Pitch`].
+///
+/// See more: <https://en.wikipedia.org/wiki/Musical_tuning#Tuning_systems>
+pub trait TuningSystem {
+    /// Frequency of the [`Pitch`] in Herz (Hz)
+    /// while playing a piece in the given [key][KeySig].
+    fn frequency(&self, pitch: Pitch, key: KeySig) -> f64;
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, PartialOrd)]
+/// Every [`Octave`] is divided into 12 equal semitones,
+/// so the key of a piece does not matter.
+///
+/// See more: <https://en.wikipedia.org/wiki/12_equal_temperament>
+pub struct EqualTemperament {
+    /// The reference frequency.
+    pub tuning: Tuning,
+}
+
+impl TuningSystem for EqualTemperament {
+    fn frequency(&self, pitch: Pitch, _key: KeySig) -> f64 {
+        pitch.get_frequency_with_tuning(self.tuning)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, PartialOrd)]
+/// The intervals from the tonic of the key are defined
+/// by the ratios of small whole numbers (5-limit tuning).
+///
+/// The tonics themselves are tuned the same as in the [`EqualTemperament`].
+///
+/// See more:
+/// - <https://en.wikipedia.org/wiki/Just_intonation>
+/// - <https://en.wikipedia.org/wiki/Five-limit_tuning>
+pub struct JustIntonation {
+    /// The reference frequency.
+    pub tuning: Tuning,
+}
+
+impl JustIntonation {
+    /// The frequency ratios of every chromatic step from the tonic.
+    const RATIOS: [(u8, u8); 12] = [
+        (1, 1),
+        (16, 15),
+        (9, 8),
+        (6, 5),
+        (5, 4),
+        (4, 3),
+        (45, 32),
+        (3, 2),
+        (8, 5),
+        (5, 3),
+        (9, 5),
+        (15, 8),
+    ];
+}
+
+impl TuningSystem for JustIntonation {
+    fn frequency(&self, pitch: Pitch, key: KeySig) -> f64 {
+        let semitones = i16::from(u8::from(Octave::semitones_number()));
+        let pitch = i16::from(pitch.abs().get_u8());
+        let tonic = i16::from(key.pitch_class().distance_from_c());
+
+        let step = (pitch - tonic).rem_euclid(semitones);
+        let tonic_frequency = equal_temperament_frequency(pitch - step, self.tuning);
+        let (numer, denom) = Self::RATIOS[usize::try_from(step).expect("Step is non-negative")];
+        tonic_frequency * f64::from(numer) / f64::from(denom)
+    }
+}
+
+/// The frequency of the pitch given as a MIDI note number,
+/// which could also be out of the MIDI range.
+fn equal_temperament_frequency(pitch: i16, tuning: Tuning) -> f64 {
+    let a4 = i16::from(Pitch::A(Octave::OneLined).abs().get_u8());
+    let semitones = f64::from(u8::from(Octave::semitones_number()));
+    (f64::from(pitch - a4) / semitones).exp2() * tuning.a4_hz
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{p, PitchClass};
+
+    #[test]
+    fn equal_temperament_ignores_key() {
+        let et = EqualTemperament {
+            tuning: Tuning::new(432.0),
+        };
+        let a4 = p!(A 4);
+        assert!((et.frequency(a4, KeySig::default()) - 432.0).abs() < f64::EPSILON);
+        assert!((et.frequency(a4, KeySig::Minor(PitchClass::Fs)) - 432.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn just_perfect_fifth() {
+        let key = KeySig::Major(PitchClass::D);
+        let just = JustIntonation::default();
+        let et = EqualTemperament::default();
+
+        let ratio = just.frequency(p!(A 4), key) / just.frequency(p!(D 4), key);
+        assert!((ratio - 1.5).abs() < 1e-12, "{ratio}");
+
+        let ratio = et.frequency(p!(A 4), key) / et.frequency(p!(D 4), key);
+        assert!((ratio - 1.5).abs() > 1e-3, "{ratio}");
+    }
+
+    #[test]
+    fn just_tonic_is_tempered() {
+        let key = KeySig::Major(PitchClass::A);
+        let just = JustIntonation::default();
+        assert!((just.frequency(p!(A 4), key) - 440.0).abs() < 1e-9);
+        assert!((just.frequency(p!(A 5), key) - 880.0).abs() < 1e-9);
+        // the major third in A major
+        let cs = just.frequency(p!(Cs 5), key);
+        assert!((cs - 550.0).abs() < 1e-9, "{cs}");
+        // the pitch below the tonic belongs to the lower octave
+        let gs = just.frequency(p!(Gs 4), key);
+        assert!((gs - 412.5).abs() < 1e-9, "{gs}");
+    }
+}